@@ -0,0 +1,18 @@
+// renders the default scene without a window and saves it, for machines without a
+// display such as ci runners with a software vulkan driver like lavapipe
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "headless.png".to_string());
+
+    let mut backend = atom::graphics::backend::vulkan_backend::VulkanBackend::new_headless()?;
+    let mut target = backend.create_offscreen_target(640, 360)?;
+
+    let scene = atom::graphics::scene::Scene::default();
+    let camera = atom::graphics::camera::Camera::default();
+    let image = backend.render_offscreen(&mut target, &scene, &camera)?;
+
+    image.save(&path)?;
+    println!("Saved {}x{} image to {}", image.width, image.height, path);
+    Ok(())
+}
@@ -5,15 +5,18 @@ use winit::{
     window::{self, Fullscreen},
 };
 
-use crate::{graphics::backend::RenderContext, reader::obj_reader};
+use crate::{
+    graphics::backend::{GuiIntegration, RenderContext},
+    reader::obj_reader,
+};
 
-pub struct App<B: crate::graphics::backend::RenderBackend> {
+pub struct App<B: crate::graphics::backend::WindowBackend> {
     pub render_backend: B,
     pub main_editor: crate::editor::Editor,
     pub window_contexts: HashMap<winit::window::WindowId, B::Context>,
 }
 
-impl<B: crate::graphics::backend::RenderBackend> App<B> {
+impl<B: crate::graphics::backend::WindowBackend> App<B> {
     pub fn new(event_loop: &winit::event_loop::EventLoop<()>) -> Self {
         return App {
             render_backend: B::new(event_loop).unwrap(),
@@ -23,7 +26,7 @@ impl<B: crate::graphics::backend::RenderBackend> App<B> {
     }
 }
 
-impl<B: crate::graphics::backend::RenderBackend> ApplicationHandler for App<B> {
+impl<B: crate::graphics::backend::WindowBackend> ApplicationHandler for App<B> {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
         // create a default window
         let window = Arc::new(
@@ -57,7 +60,7 @@ impl<B: crate::graphics::backend::RenderBackend> ApplicationHandler for App<B> {
         event: winit::event::WindowEvent,
     ) {
         let window_context = self.window_contexts.get_mut(&window_id).unwrap();
-        if window_context.gui_update(&event) {
+        if window_context.gui().handle_event(&event) {
             window_context.window().request_redraw();
             return; // If the GUI handled the event, skip further processing
         }
@@ -88,16 +91,13 @@ impl<B: crate::graphics::backend::RenderBackend> ApplicationHandler for App<B> {
                 }
 
                 if let Some(window_context) = self.window_contexts.get_mut(&window_id) {
-                    let scene = self.main_editor.scene.clone();
+                    window_context.gui().build(|context| {
+                        self.main_editor.ui(context);
+                    });
 
-                    self.render_backend
-                        .draw_frame(
-                            window_context,
-                            |context| {
-                                self.main_editor.ui(&context);
-                            },
-                            &scene,
-                        )
+                    self.main_editor.culling = self
+                        .render_backend
+                        .draw_frame(window_context, &self.main_editor.scene)
                         .unwrap();
                     window_context.window().request_redraw();
                 }
@@ -1,7 +1,18 @@
+use crate::graphics::{arena::Handle, mesh::Mesh, scene::Node};
+
 #[derive(Debug)]
 pub enum EditorCommand {
     UpdateObjectTransform {
-        object_index: usize,
+        object: Handle<Mesh>,
         new_transform: glam::Mat4,
     },
+    // the node keeps its world transform, `None` makes it a root
+    ReparentNode {
+        node: Handle<Node>,
+        new_parent: Option<Handle<Node>>,
+    },
+    // removes the whole subtree with its objects
+    RemoveNode {
+        node: Handle<Node>,
+    },
 }
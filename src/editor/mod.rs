@@ -1,15 +1,32 @@
-use egui_winit_vulkano::{self, egui};
-
 use crate::{
-    graphics::light::Light,
-    reader::{FileType, obj_reader},
+    graphics::{
+        arena::Handle,
+        light::{AreaLight, DirectionalLight, Light, SpotLight},
+        material::MaterialHandle,
+        mesh::Mesh,
+        scene::{Node, SceneChange},
+    },
+    reader::{FileType, ImportOptions, gltf_reader, obj_reader},
 };
 
 pub mod command;
+
+// the size of the images rendered by the path tracer
+const RENDER_SIZE: [u32; 2] = [960, 540];
+
 #[derive(Debug)]
 pub struct Editor {
     pub scene: crate::graphics::scene::Scene,
+    // shares the data of everything imported into the scene
+    pub assets: crate::graphics::assets::AssetManager,
+    // what the renderer drew of the scene in the last frame
+    pub culling: crate::graphics::frustum::CullingStats,
     selected_file_type: FileType,
+    import_options: ImportOptions,
+    lod_options: crate::graphics::mesh::LodChainOptions,
+    // the path traced render in progress, saved once it has `render_samples` samples
+    render: Option<crate::graphics::path_tracer::BackgroundRender>,
+    render_samples: u32,
 }
 
 impl Default for Editor {
@@ -19,7 +36,13 @@ impl Default for Editor {
 
         Editor {
             scene,
+            assets: crate::graphics::assets::AssetManager::new(),
+            culling: crate::graphics::frustum::CullingStats::default(),
             selected_file_type: FileType::default(),
+            import_options: ImportOptions::default(),
+            lod_options: crate::graphics::mesh::LodChainOptions::default(),
+            render: None,
+            render_samples: 64,
         }
     }
 }
@@ -33,73 +56,115 @@ impl Editor {
                 .selected_text(selected_text.clone())
                 .show_ui(ui, |ui| {
                     ui.selectable_value(&mut self.selected_file_type, FileType::Obj, "OBJ");
+                    ui.selectable_value(&mut self.selected_file_type, FileType::Gltf, "glTF");
                     ui.selectable_value(&mut self.selected_file_type, FileType::Fbx, "FBX");
                 });
+            ui.checkbox(
+                &mut self.import_options.validate_meshes,
+                "Validate meshes on import",
+            );
+            ui.checkbox(
+                &mut self.import_options.repair_meshes,
+                "Repair meshes on import",
+            );
+            ui.checkbox(
+                &mut self.import_options.optimize_meshes,
+                "Optimize meshes on import",
+            );
             if ui.button("Load Scene").clicked() {
                 // Logic to load a scene
 
+                let extensions = match self.selected_file_type {
+                    FileType::Gltf => vec!["gltf".to_string(), "glb".to_string()],
+                    file_type => vec![file_type.to_string()],
+                };
                 let path = rfd::FileDialog::new()
-                    .add_filter(selected_text, &[self.selected_file_type.to_string()])
+                    .add_filter(selected_text, &extensions)
                     .pick_file();
 
                 if let Some(path) = path {
-                    let scene = obj_reader::read_file(path.to_str().unwrap()).unwrap();
+                    let read_file_with_assets = match self.selected_file_type {
+                        FileType::Gltf => gltf_reader::read_file_with_assets,
+                        _ => obj_reader::read_file_with_assets,
+                    };
+                    let scene = read_file_with_assets(
+                        path.to_str().unwrap(),
+                        &self.import_options,
+                        &mut self.assets,
+                    )
+                    .unwrap();
                     self.scene.merge(scene);
                     println!("Scene loaded from: {:?}", path);
                 }
             }
-        });
-
-        egui::Window::new("object properties").show(ctx, |ui| {
-            for (i, object) in self.scene.objects.iter_mut().enumerate() {
-                ui.label(format!("Object {}", i));
 
-                let (mut scale, mut rotation, mut translation) =
-                    object.world_transform.to_scale_rotation_translation();
+            ui.horizontal(|ui| {
+                ui.label("Samples:");
+                ui.add(egui::DragValue::new(&mut self.render_samples).range(1..=4096));
+                if ui.button("Render").clicked() {
+                    self.render = self.start_render(self.render_samples);
+                    if self.render.is_none() {
+                        println!("Render: no camera");
+                    }
+                }
+            });
+            self.render_ui(ui);
 
-                // translation
-                ui.horizontal(|ui| {
-                    ui.label("Position:");
-                    ui.add(egui::DragValue::new(&mut translation.x).speed(0.1));
-                    ui.add(egui::DragValue::new(&mut translation.y).speed(0.1));
-                    ui.add(egui::DragValue::new(&mut translation.z).speed(0.1));
-                });
+            ui.label(format!("Assets: {}", self.assets.stats()));
+            ui.label(format!("Culling: {}", self.culling));
 
-                let mut euler_degrees = rotation.to_euler(glam::EulerRot::XYZ);
-                euler_degrees.0 = euler_degrees.0.to_degrees();
-                euler_degrees.1 = euler_degrees.1.to_degrees();
-                euler_degrees.2 = euler_degrees.2.to_degrees();
+            ui.menu_button("Add Object", |ui| {
+                for primitive in crate::graphics::primitives::Primitive::ALL {
+                    if ui.button(primitive.name()).clicked() {
+                        // repeated primitives share their geometry, so they are drawn instanced
+                        let mut mesh = primitive.create_mesh();
+                        for submesh in &mut mesh.submeshes {
+                            self.assets.share_geometry(submesh);
+                        }
+                        if let Err(error) = self.scene.add_object(primitive.name(), mesh, None) {
+                            println!("Failed to add {}: {}", primitive.name(), error);
+                        }
+                        ui.close_menu();
+                    }
+                }
+            });
+        });
 
-                // rotation
-                ui.horizontal(|ui| {
-                    ui.label("Rotation:");
-                    ui.add(egui::DragValue::new(&mut euler_degrees.0).speed(0.1));
-                    ui.add(egui::DragValue::new(&mut euler_degrees.1).speed(0.1));
-                    ui.add(egui::DragValue::new(&mut euler_degrees.2).speed(0.1));
-                });
-                rotation = glam::Quat::from_euler(
-                    glam::EulerRot::XYZ,
-                    euler_degrees.0.to_radians(),
-                    euler_degrees.1.to_radians(),
-                    euler_degrees.2.to_radians(),
-                );
-
-                // scale
-                ui.horizontal(|ui| {
-                    ui.label("Scale:");
-                    ui.add(egui::DragValue::new(&mut scale.x).speed(0.1));
-                    ui.add(egui::DragValue::new(&mut scale.y).speed(0.1));
-                    ui.add(egui::DragValue::new(&mut scale.z).speed(0.1));
-                });
+        egui::Window::new("object properties").show(ctx, |ui| {
+            let mut commands = Vec::new();
+            for root in self.scene.roots().collect::<Vec<_>>() {
+                self.node_ui(ui, root, &mut commands);
+            }
 
-                object.world_transform =
-                    glam::Mat4::from_scale_rotation_translation(scale, rotation, translation);
+            // applied after the tree is drawn, the hierarchy must not change while walking it
+            for command in commands {
+                self.apply_command(command);
             }
+            self.scene.update_world_transforms();
         });
 
         // camera properties
         egui::Window::new("Camera Properties").show(ctx, |ui| {
-            let camera = &mut self.scene.cameras[self.scene.main_camera_index];
+            egui::ComboBox::new("main_camera", "Main Camera")
+                .selected_text(
+                    self.scene
+                        .main_camera
+                        .map_or("None".to_string(), |camera| format!("Camera {}", camera)),
+                )
+                .show_ui(ui, |ui| {
+                    for camera in self.scene.cameras.handles().collect::<Vec<_>>() {
+                        ui.selectable_value(
+                            &mut self.scene.main_camera,
+                            Some(camera),
+                            format!("Camera {}", camera),
+                        );
+                    }
+                });
+
+            let Some(camera) = self.scene.main_camera_mut() else {
+                ui.label("No camera");
+                return;
+            };
             ui.label("Camera Properties");
             ui.horizontal(|ui| {
                 ui.label("Position:");
@@ -135,42 +200,902 @@ impl Editor {
 
         //light controls
         egui::Window::new("Light Controls").show(ctx, |ui| {
-            for (i, light) in self.scene.lights.iter_mut().enumerate() {
+            let mut changed = false;
+            ui.menu_button("Add Light", |ui| {
+                let color = glam::Vec3::ONE;
+                let light = if ui.button("Point").clicked() {
+                    Some(Light::default())
+                } else if ui.button("Directional").clicked() {
+                    Some(Light::Directional(DirectionalLight {
+                        direction: glam::Vec3::NEG_Y,
+                        color,
+                        intensity: 1.0,
+                        cast_shadows: true,
+                    }))
+                } else if ui.button("Spot").clicked() {
+                    Some(Light::Spot(SpotLight {
+                        position: glam::Vec3::new(0.0, 3.0, 0.0),
+                        direction: glam::Vec3::NEG_Y,
+                        color,
+                        intensity: 1.0,
+                        inner_angle: 20.0,
+                        outer_angle: 30.0,
+                        range: 10.0,
+                    }))
+                } else if ui.button("Area").clicked() {
+                    Some(Light::Area(AreaLight {
+                        position: glam::Vec3::new(0.0, 3.0, 0.0),
+                        direction: glam::Vec3::NEG_Y,
+                        size: glam::Vec2::ONE,
+                        color,
+                        intensity: 1.0,
+                    }))
+                } else {
+                    None
+                };
+                if let Some(light) = light {
+                    self.scene.lights.insert(light);
+                    changed = true;
+                    ui.close_menu();
+                }
+            });
+            // shared by every light casting shadows, the renderers pick changes up themselves
+            egui::CollapsingHeader::new("Shadows").show(ui, |ui| {
+                shadow_settings_ui(ui, &mut self.scene.shadows);
+            });
+            for (handle, light) in self.scene.lights.iter_mut() {
                 match light {
                     Light::Directional(directional) => {
-                        ui.label(format!("Directional Light {}", i));
+                        ui.label(format!("Directional Light {}", handle));
                         ui.horizontal(|ui| {
                             ui.label("Direction:");
-                            ui.add(egui::DragValue::new(&mut directional.direction.x).speed(0.1));
-                            ui.add(egui::DragValue::new(&mut directional.direction.y).speed(0.1));
-                            ui.add(egui::DragValue::new(&mut directional.direction.z).speed(0.1));
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut directional.direction.x).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut directional.direction.y).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut directional.direction.z).speed(0.1))
+                                .changed();
                         });
                         ui.horizontal(|ui| {
                             ui.label("Color:");
                             ui.color_edit_button_rgb(&mut directional.color.into());
                         });
+                        changed |= ui
+                            .checkbox(&mut directional.cast_shadows, "Cast shadows")
+                            .changed();
                     }
                     Light::Point(point) => {
-                        ui.label(format!("Point Light {}", i));
+                        ui.label(format!("Point Light {}", handle));
                         ui.horizontal(|ui| {
                             ui.label("Position:");
-                            ui.add(egui::DragValue::new(&mut point.position.x).speed(0.1));
-                            ui.add(egui::DragValue::new(&mut point.position.y).speed(0.1));
-                            ui.add(egui::DragValue::new(&mut point.position.z).speed(0.1));
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut point.position.x).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut point.position.y).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut point.position.z).speed(0.1))
+                                .changed();
                         });
                         ui.horizontal(|ui| {
                             ui.label("Color:");
                             let mut color_arr = point.color.to_array();
-                            ui.color_edit_button_rgb(&mut color_arr);
+                            changed |= ui.color_edit_button_rgb(&mut color_arr).changed();
                             point.color = glam::Vec3::from_array(color_arr);
                         });
                         ui.horizontal(|ui| {
                             ui.label("Intensity:");
-                            ui.add(egui::DragValue::new(&mut point.intensity).speed(0.1));
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut point.intensity).speed(0.1))
+                                .changed();
+                        });
+                        changed |= ui
+                            .checkbox(&mut point.cast_shadows, "Cast shadows")
+                            .changed();
+                    }
+                    Light::Spot(spot) => {
+                        ui.label(format!("Spot Light {}", handle));
+                        ui.horizontal(|ui| {
+                            ui.label("Position:");
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut spot.position.x).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut spot.position.y).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut spot.position.z).speed(0.1))
+                                .changed();
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Direction:");
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut spot.direction.x).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut spot.direction.y).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut spot.direction.z).speed(0.1))
+                                .changed();
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Cone Angles (degrees):");
+                            changed |= ui
+                                .add(
+                                    egui::DragValue::new(&mut spot.inner_angle)
+                                        .speed(0.5)
+                                        .range(0.0..=90.0),
+                                )
+                                .changed();
+                            changed |= ui
+                                .add(
+                                    egui::DragValue::new(&mut spot.outer_angle)
+                                        .speed(0.5)
+                                        .range(0.0..=90.0),
+                                )
+                                .changed();
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Range:");
+                            changed |= ui
+                                .add(
+                                    egui::DragValue::new(&mut spot.range)
+                                        .speed(0.1)
+                                        .range(0.0..=f32::MAX),
+                                )
+                                .changed();
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Color:");
+                            let mut color_arr = spot.color.to_array();
+                            changed |= ui.color_edit_button_rgb(&mut color_arr).changed();
+                            spot.color = glam::Vec3::from_array(color_arr);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Intensity:");
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut spot.intensity).speed(0.1))
+                                .changed();
+                        });
+                    }
+                    Light::Area(area) => {
+                        ui.label(format!("Area Light {}", handle));
+                        ui.horizontal(|ui| {
+                            ui.label("Position:");
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut area.position.x).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut area.position.y).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut area.position.z).speed(0.1))
+                                .changed();
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Direction:");
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut area.direction.x).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut area.direction.y).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut area.direction.z).speed(0.1))
+                                .changed();
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Size:");
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut area.size.x).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut area.size.y).speed(0.1))
+                                .changed();
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Color:");
+                            let mut color_arr = area.color.to_array();
+                            changed |= ui.color_edit_button_rgb(&mut color_arr).changed();
+                            area.color = glam::Vec3::from_array(color_arr);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Intensity:");
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut area.intensity).speed(0.1))
+                                .changed();
                         });
                     }
                 }
             }
+            if changed {
+                self.scene.mark_changed(SceneChange::Lights);
+            }
+        });
+    }
+
+    // the passes run on a worker over a copy of the scene, none without a camera
+    fn start_render(&self, samples: u32) -> Option<crate::graphics::path_tracer::BackgroundRender> {
+        let camera = self.scene.main_camera()?;
+        Some(crate::graphics::path_tracer::BackgroundRender::start(
+            &self.scene,
+            camera,
+            RENDER_SIZE[0],
+            RENDER_SIZE[1],
+            samples,
+        ))
+    }
+
+    // shows how far the render got every frame, asks where to save it when done
+    fn render_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(render) = &self.render else {
+            return;
+        };
+        // edits made while rendering start it over on the new scene or view
+        if render.revision != self.scene.revision()
+            || self.scene.main_camera() != Some(&render.camera)
+        {
+            self.render = self.start_render(render.target_samples);
+        }
+        let Some(render) = &self.render else {
+            ui.label("Render: no camera");
+            return;
+        };
+
+        ui.label(format!(
+            "Render: {}/{} samples",
+            render.samples(),
+            render.target_samples
+        ));
+        if !render.is_finished() {
+            ui.ctx().request_repaint();
+            return;
+        }
+
+        let Some(image) = self.render.take().and_then(|render| render.finish()) else {
+            println!("Failed to render");
+            return;
+        };
+        let path = rfd::FileDialog::new()
+            .add_filter("Image", &["png", "exr"])
+            .set_file_name("render.png")
+            .save_file();
+        if let Some(path) = path {
+            match image.save(path.to_str().unwrap()) {
+                Ok(()) => println!("Render saved to: {:?}", path),
+                Err(e) => println!("Failed to save render: {}", e),
+            }
+        }
+    }
+
+    pub fn apply_command(&mut self, command: command::EditorCommand) {
+        match command {
+            command::EditorCommand::UpdateObjectTransform {
+                object,
+                new_transform,
+            } => match self.scene.object_node(object) {
+                Some(node) => self.scene.set_world_transform(node, new_transform),
+                None => {
+                    if let Some(object) = self.scene.objects.get_mut(object) {
+                        object.world_transform = new_transform;
+                        self.scene.mark_changed(SceneChange::Transforms);
+                    }
+                }
+            },
+            command::EditorCommand::ReparentNode { node, new_parent } => {
+                if let Err(error) = self.scene.set_parent(node, new_parent) {
+                    println!("Failed to reparent node {}: {}", node, error);
+                }
+            }
+            command::EditorCommand::RemoveNode { node } => {
+                if let Err(error) = self.scene.remove_node(node) {
+                    println!("Failed to remove node {}: {}", node, error);
+                }
+                self.assets.remove_unused();
+            }
+        }
+    }
+
+    fn node_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        handle: Handle<Node>,
+        commands: &mut Vec<command::EditorCommand>,
+    ) {
+        let Some(node) = self.scene.node(handle) else {
+            return;
+        };
+        let header = node.name.clone();
+        let parent = node.parent();
+        let object = node.object;
+        let children = node.children().to_vec();
+
+        egui::CollapsingHeader::new(header)
+            .id_salt(("node", handle))
+            .default_open(true)
+            .show(ui, |ui| {
+                if let Some(node) = self.scene.node_mut(handle) {
+                    ui.horizontal(|ui| {
+                        ui.label("Name:");
+                        ui.text_edit_singleline(&mut node.name);
+                        if ui.button("Remove").clicked() {
+                            commands.push(command::EditorCommand::RemoveNode { node: handle });
+                        }
+                    });
+                    transform_ui(ui, &mut node.local_transform);
+                }
+
+                let node_name = |node: Handle<Node>| {
+                    self.scene
+                        .node(node)
+                        .map_or(String::new(), |named| format!("{} ({})", named.name, node))
+                };
+                let mut new_parent = parent;
+                egui::ComboBox::new(("parent", handle), "Parent")
+                    .selected_text(parent.map_or("None".to_string(), node_name))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut new_parent, None, "None");
+                        // nodes inside this subtree can not become its parent
+                        for candidate in self.scene.nodes().handles() {
+                            if !self.scene.is_in_subtree(Some(candidate), handle) {
+                                ui.selectable_value(
+                                    &mut new_parent,
+                                    Some(candidate),
+                                    node_name(candidate),
+                                );
+                            }
+                        }
+                    });
+                if new_parent != parent {
+                    commands.push(command::EditorCommand::ReparentNode {
+                        node: handle,
+                        new_parent,
+                    });
+                }
+
+                if let Some(object) = object {
+                    self.object_ui(ui, object);
+                }
+
+                for child in children {
+                    self.node_ui(ui, child, commands);
+                }
+            });
+    }
+
+    fn object_ui(&mut self, ui: &mut egui::Ui, handle: Handle<Mesh>) {
+        let Some(object) = self.scene.objects.get_mut(handle) else {
+            return;
+        };
+
+        egui::CollapsingHeader::new("Bounds")
+            .id_salt(("bounds", handle))
+            .show(ui, |ui| {
+                bounds_ui(ui, "Local", object.bounds());
+                bounds_ui(ui, "World", &object.world_bounds());
+            });
+
+        let mut objects_changed = false;
+        let mut materials_changed = false;
+
+        if let Some(mut primitive) = object.primitive.clone() {
+            egui::CollapsingHeader::new(primitive.name())
+                .id_salt(("primitive", handle))
+                .show(ui, |ui| {
+                    if primitive_ui(ui, &mut primitive) {
+                        // rebuild in place, keeping where the object is, how smooth it is,
+                        // its materials and whether it has lods
+                        let subdivision_level = object.subdivision_level();
+                        let has_lods = object
+                            .submeshes
+                            .iter()
+                            .any(|submesh| !submesh.lods.is_empty());
+                        let mut mesh = primitive.create_mesh();
+                        mesh.world_transform = object.world_transform;
+                        for (submesh, old) in mesh.submeshes.iter_mut().zip(&object.submeshes) {
+                            submesh.material = old.material;
+                        }
+                        *object = mesh;
+                        objects_changed = true;
+                        if let Err(error) = object.set_subdivision_level(subdivision_level) {
+                            println!("Failed to subdivide object {}: {}", handle, error);
+                        }
+                        if has_lods {
+                            object.generate_lods(&self.lod_options);
+                        }
+                        // shared again with equal primitives, so they stay instanced
+                        for submesh in &mut object.submeshes {
+                            self.assets.share_geometry(submesh);
+                        }
+                    }
+                });
+        }
+
+        let mut subdivision_level = object.subdivision_level();
+        ui.horizontal(|ui| {
+            ui.label("Subdivision:");
+            ui.add(egui::Slider::new(&mut subdivision_level, 0..=3));
         });
+        if subdivision_level != object.subdivision_level() {
+            if let Err(error) = object.set_subdivision_level(subdivision_level) {
+                println!("Failed to subdivide object {}: {}", handle, error);
+            }
+            objects_changed = true;
+        }
+
+        egui::CollapsingHeader::new("Materials")
+            .id_salt(("materials", handle))
+            .show(ui, |ui| {
+                let materials = &self.scene.materials;
+                let material_name = |material: Option<MaterialHandle>| {
+                    material
+                        .and_then(|material| materials.get(material))
+                        .map_or("Default".to_string(), |material| material.name.clone())
+                };
+
+                for (submesh_index, submesh) in object.submeshes.iter_mut().enumerate() {
+                    egui::ComboBox::new(
+                        ("submesh_material", handle, submesh_index),
+                        format!("Submesh {}", submesh_index),
+                    )
+                    .selected_text(material_name(submesh.material))
+                    .show_ui(ui, |ui| {
+                        materials_changed |= ui
+                            .selectable_value(&mut submesh.material, None, "Default")
+                            .changed();
+                        for material in materials.handles() {
+                            materials_changed |= ui
+                                .selectable_value(
+                                    &mut submesh.material,
+                                    Some(material),
+                                    material_name(Some(material)),
+                                )
+                                .changed();
+                        }
+                    });
+                }
+            });
+
+        egui::CollapsingHeader::new("Level of Detail")
+            .id_salt(("lod", handle))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Levels:");
+                    ui.add(egui::DragValue::new(&mut self.lod_options.levels).range(1..=8));
+                    ui.label("Reduction:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.lod_options.reduction)
+                            .speed(0.01)
+                            .range(0.05..=0.95),
+                    );
+                });
+                if ui.button("Generate LODs").clicked() {
+                    object.generate_lods(&self.lod_options);
+                }
+                for (submesh_index, submesh) in object.submeshes.iter().enumerate() {
+                    ui.label(format!(
+                        "Submesh {}: {} triangles",
+                        submesh_index,
+                        submesh.indices.len() / 3
+                    ));
+                    for (level, lod) in submesh.lods.iter().enumerate() {
+                        ui.label(format!(
+                            "  LOD {}: {} triangles, error {:.4}, below {:.3} of screen",
+                            level + 1,
+                            lod.indices.len() / 3,
+                            lod.error,
+                            lod.screen_size
+                        ));
+                    }
+                }
+            });
+
+        if objects_changed {
+            self.scene.mark_changed(SceneChange::Objects);
+        }
+        if materials_changed {
+            self.scene.mark_changed(SceneChange::Materials);
+        }
+    }
+}
+
+// returns whether the transform was edited
+fn transform_ui(ui: &mut egui::Ui, transform: &mut glam::Mat4) -> bool {
+    let (mut scale, mut rotation, mut translation) = transform.to_scale_rotation_translation();
+    let mut changed = false;
+
+    // translation
+    ui.horizontal(|ui| {
+        ui.label("Position:");
+        changed |= ui
+            .add(egui::DragValue::new(&mut translation.x).speed(0.1))
+            .changed();
+        changed |= ui
+            .add(egui::DragValue::new(&mut translation.y).speed(0.1))
+            .changed();
+        changed |= ui
+            .add(egui::DragValue::new(&mut translation.z).speed(0.1))
+            .changed();
+    });
+
+    let mut euler_degrees = rotation.to_euler(glam::EulerRot::XYZ);
+    euler_degrees.0 = euler_degrees.0.to_degrees();
+    euler_degrees.1 = euler_degrees.1.to_degrees();
+    euler_degrees.2 = euler_degrees.2.to_degrees();
+
+    // rotation
+    ui.horizontal(|ui| {
+        ui.label("Rotation:");
+        changed |= ui
+            .add(egui::DragValue::new(&mut euler_degrees.0).speed(0.1))
+            .changed();
+        changed |= ui
+            .add(egui::DragValue::new(&mut euler_degrees.1).speed(0.1))
+            .changed();
+        changed |= ui
+            .add(egui::DragValue::new(&mut euler_degrees.2).speed(0.1))
+            .changed();
+    });
+    rotation = glam::Quat::from_euler(
+        glam::EulerRot::XYZ,
+        euler_degrees.0.to_radians(),
+        euler_degrees.1.to_radians(),
+        euler_degrees.2.to_radians(),
+    );
+
+    // scale
+    ui.horizontal(|ui| {
+        ui.label("Scale:");
+        changed |= ui
+            .add(egui::DragValue::new(&mut scale.x).speed(0.1))
+            .changed();
+        changed |= ui
+            .add(egui::DragValue::new(&mut scale.y).speed(0.1))
+            .changed();
+        changed |= ui
+            .add(egui::DragValue::new(&mut scale.z).speed(0.1))
+            .changed();
+    });
+
+    // only written back on edits, so the decomposition does not slowly drift
+    if changed {
+        *transform = glam::Mat4::from_scale_rotation_translation(scale, rotation, translation);
     }
+
+    changed
+}
+
+fn shadow_settings_ui(ui: &mut egui::Ui, settings: &mut crate::graphics::shadow::ShadowSettings) {
+    ui.horizontal(|ui| {
+        ui.label("Cascades:");
+        ui.add(
+            egui::DragValue::new(&mut settings.cascade_count)
+                .range(1..=crate::graphics::shadow::MAX_CASCADES_PER_LIGHT),
+        );
+    });
+    ui.horizontal(|ui| {
+        ui.label("Resolution:");
+        egui::ComboBox::new("shadow_resolution", "")
+            .selected_text(settings.resolution.to_string())
+            .show_ui(ui, |ui| {
+                for resolution in [512, 1024, 2048, 4096] {
+                    ui.selectable_value(
+                        &mut settings.resolution,
+                        resolution,
+                        resolution.to_string(),
+                    );
+                }
+            });
+    });
+    ui.horizontal(|ui| {
+        ui.label("Distance:");
+        ui.add(
+            egui::DragValue::new(&mut settings.distance)
+                .speed(0.5)
+                .range(1.0..=1000.0),
+        );
+    });
+    ui.horizontal(|ui| {
+        ui.label("Split:");
+        ui.add(egui::Slider::new(&mut settings.split_lambda, 0.0..=1.0).text("uniform to log"));
+    });
+    ui.horizontal(|ui| {
+        ui.label("PCF radius:");
+        ui.add(egui::DragValue::new(&mut settings.pcf_radius).range(0..=4));
+    });
+    ui.horizontal(|ui| {
+        ui.label("Depth bias:");
+        ui.add(
+            egui::DragValue::new(&mut settings.depth_bias)
+                .speed(0.0001)
+                .range(0.0..=0.05),
+        );
+    });
+    ui.horizontal(|ui| {
+        ui.label("Normal bias:");
+        ui.add(
+            egui::DragValue::new(&mut settings.normal_bias)
+                .speed(0.1)
+                .range(0.0..=10.0),
+        );
+    });
+    ui.horizontal(|ui| {
+        ui.label("Point shadow lights:");
+        ui.add(
+            egui::DragValue::new(&mut settings.point_shadow_lights)
+                .range(0..=crate::graphics::shadow::MAX_POINT_SHADOWS),
+        );
+    });
+    ui.horizontal(|ui| {
+        ui.label("Point resolution:");
+        egui::ComboBox::new("point_shadow_resolution", "")
+            .selected_text(settings.point_resolution.to_string())
+            .show_ui(ui, |ui| {
+                for resolution in [256, 512, 1024, 2048] {
+                    ui.selectable_value(
+                        &mut settings.point_resolution,
+                        resolution,
+                        resolution.to_string(),
+                    );
+                }
+            });
+    });
+}
+
+fn bounds_ui(ui: &mut egui::Ui, label: &str, bounds: &crate::graphics::bounds::Bounds) {
+    ui.label(label);
+    if bounds.is_empty() {
+        ui.label("Empty");
+        return;
+    }
+
+    let aabb = &bounds.aabb;
+    ui.label(format!(
+        "AABB min: ({:.3}, {:.3}, {:.3}) max: ({:.3}, {:.3}, {:.3})",
+        aabb.min.x, aabb.min.y, aabb.min.z, aabb.max.x, aabb.max.y, aabb.max.z
+    ));
+
+    let sphere = &bounds.sphere;
+    ui.label(format!(
+        "Sphere center: ({:.3}, {:.3}, {:.3}) radius: {:.3}",
+        sphere.center.x, sphere.center.y, sphere.center.z, sphere.radius
+    ));
+
+    let obb = &bounds.obb;
+    ui.label(format!(
+        "OBB center: ({:.3}, {:.3}, {:.3}) half extents: ({:.3}, {:.3}, {:.3})",
+        obb.center.x,
+        obb.center.y,
+        obb.center.z,
+        obb.half_extents.x,
+        obb.half_extents.y,
+        obb.half_extents.z
+    ));
+}
+
+// returns whether any parameter changed
+fn primitive_ui(ui: &mut egui::Ui, primitive: &mut crate::graphics::primitives::Primitive) -> bool {
+    use crate::graphics::primitives::Primitive;
+
+    let length = |ui: &mut egui::Ui, label: &str, value: &mut f32| {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            ui.add(
+                egui::DragValue::new(value)
+                    .speed(0.01)
+                    .range(0.001..=f32::MAX),
+            )
+        })
+        .inner
+        .changed()
+    };
+    let count = |ui: &mut egui::Ui, label: &str, value: &mut u32, min: u32| {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            ui.add(egui::DragValue::new(value).range(min..=256))
+        })
+        .inner
+        .changed()
+    };
+
+    match primitive {
+        Primitive::Sphere { radius, segments } => {
+            length(ui, "Radius:", radius) | count(ui, "Segments:", segments, 3)
+        }
+        Primitive::Cube { size } => {
+            length(ui, "Width:", &mut size.x)
+                | length(ui, "Height:", &mut size.y)
+                | length(ui, "Depth:", &mut size.z)
+        }
+        Primitive::Plane {
+            width,
+            depth,
+            subdivisions,
+        } => {
+            length(ui, "Width:", width)
+                | length(ui, "Depth:", depth)
+                | count(ui, "Subdivisions:", subdivisions, 1)
+        }
+        Primitive::Cylinder {
+            radius,
+            height,
+            segments,
+        }
+        | Primitive::Cone {
+            radius,
+            height,
+            segments,
+        } => {
+            length(ui, "Radius:", radius)
+                | length(ui, "Height:", height)
+                | count(ui, "Segments:", segments, 3)
+        }
+        Primitive::Torus {
+            major_radius,
+            minor_radius,
+            major_segments,
+            minor_segments,
+        } => {
+            length(ui, "Major radius:", major_radius)
+                | length(ui, "Minor radius:", minor_radius)
+                | count(ui, "Major segments:", major_segments, 3)
+                | count(ui, "Minor segments:", minor_segments, 3)
+        }
+        Primitive::Capsule {
+            radius,
+            height,
+            segments,
+            rings,
+        } => {
+            length(ui, "Radius:", radius)
+                | length(ui, "Height:", height)
+                | count(ui, "Segments:", segments, 3)
+                | count(ui, "Rings:", rings, 2)
+        }
+        Primitive::Icosphere {
+            radius,
+            subdivisions,
+        } => {
+            length(ui, "Radius:", radius)
+                | ui.horizontal(|ui| {
+                    ui.label("Subdivisions:");
+                    ui.add(egui::DragValue::new(subdivisions).range(0..=7))
+                })
+                .inner
+                .changed()
+        }
+        Primitive::Disc { radius, segments } => {
+            length(ui, "Radius:", radius) | count(ui, "Segments:", segments, 3)
+        }
+        Primitive::Terrain(options) => terrain_ui(ui, options),
+        Primitive::Text(options) => text_ui(ui, options),
+    }
+}
+
+fn text_ui(ui: &mut egui::Ui, options: &mut crate::graphics::text::TextOptions) -> bool {
+    let mut changed = ui.text_edit_multiline(options.text.to_mut()).changed();
+    ui.horizontal(|ui| {
+        ui.label("Size:");
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut options.size)
+                    .speed(0.01)
+                    .range(0.001..=f32::MAX),
+            )
+            .changed();
+        ui.label("Depth:");
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut options.depth)
+                    .speed(0.01)
+                    .range(0.0..=f32::MAX),
+            )
+            .changed();
+    });
+    ui.horizontal(|ui| {
+        ui.label("Bevel:");
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut options.bevel)
+                    .speed(0.001)
+                    .range(0.0..=f32::MAX),
+            )
+            .changed();
+        ui.label("Bevel segments:");
+        changed |= ui
+            .add(egui::DragValue::new(&mut options.bevel_segments).range(0..=16))
+            .changed();
+    });
+
+    changed
+}
+
+fn terrain_ui(
+    ui: &mut egui::Ui,
+    options: &mut crate::graphics::primitives::TerrainOptions,
+) -> bool {
+    use crate::graphics::primitives::{HeightSource, NoiseKind, NoiseOptions};
+
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Size:");
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut options.size)
+                    .speed(0.1)
+                    .range(0.01..=f32::MAX),
+            )
+            .changed();
+        ui.label("Resolution:");
+        changed |= ui
+            .add(egui::DragValue::new(&mut options.resolution).range(1..=1024))
+            .changed();
+        ui.label("Height scale:");
+        changed |= ui
+            .add(egui::DragValue::new(&mut options.height_scale).speed(0.05))
+            .changed();
+    });
+
+    let is_noise = matches!(options.source, HeightSource::Noise(_));
+    ui.horizontal(|ui| {
+        if ui.radio(is_noise, "Noise").clicked() && !is_noise {
+            options.source = HeightSource::Noise(NoiseOptions::default());
+            changed = true;
+        }
+        if ui.button("Load Heightmap").clicked()
+            && let Some(path) = rfd::FileDialog::new()
+                .add_filter("Image", &["png", "jpg", "jpeg"])
+                .pick_file()
+        {
+            match crate::reader::image_reader::read_heightmap(path.to_str().unwrap()) {
+                Ok(heightmap) => {
+                    options.source = HeightSource::Heightmap(std::sync::Arc::new(heightmap));
+                    changed = true;
+                }
+                Err(error) => println!("Failed to load heightmap {:?}: {}", path, error),
+            }
+        }
+    });
+
+    match &mut options.source {
+        HeightSource::Noise(noise) => {
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .selectable_value(&mut noise.kind, NoiseKind::Fbm, "fBm")
+                    .changed();
+                changed |= ui
+                    .selectable_value(&mut noise.kind, NoiseKind::Ridged, "Ridged")
+                    .changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("Seed:");
+                changed |= ui.add(egui::DragValue::new(&mut noise.seed)).changed();
+                ui.label("Octaves:");
+                changed |= ui
+                    .add(egui::DragValue::new(&mut noise.octaves).range(1..=12))
+                    .changed();
+                ui.label("Frequency:");
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut noise.frequency)
+                            .speed(0.05)
+                            .range(0.01..=f32::MAX),
+                    )
+                    .changed();
+            });
+        }
+        HeightSource::Heightmap(heightmap) => {
+            ui.label(format!(
+                "Heightmap {}x{}",
+                heightmap.width, heightmap.height
+            ));
+        }
+    }
+
+    changed
 }
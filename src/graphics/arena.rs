@@ -0,0 +1,283 @@
+use std::marker::PhantomData;
+
+// refers to a value in an `Arena`, the generation tells a handle to a removed value
+// apart from one to a newer value that reused its slot
+pub struct Handle<T> {
+    index: u32,
+    generation: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    // the slot in the arena, stable for as long as the value is alive
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+// implemented by hand, deriving would require `T` to implement them too
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> std::hash::Hash for Handle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle({}v{})", self.index, self.generation)
+    }
+}
+
+impl<T> std::fmt::Display for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+// values addressed by generational handles, removing a value keeps every other
+// handle valid and turns the removed one stale instead of pointing at a new value
+#[derive(Debug, Clone)]
+pub struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Arena::new()
+    }
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Self {
+        Arena {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn insert(&mut self, value: T) -> Handle<T> {
+        self.len += 1;
+
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.value = Some(value);
+            return Handle {
+                index,
+                generation: slot.generation,
+                marker: PhantomData,
+            };
+        }
+
+        let index = self.slots.len() as u32;
+        self.slots.push(Slot {
+            generation: 0,
+            value: Some(value),
+        });
+        Handle {
+            index,
+            generation: 0,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn remove(&mut self, handle: Handle<T>) -> Option<T> {
+        let slot = self.slots.get_mut(handle.index())?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn get(&self, handle: Handle<T>) -> Option<&T> {
+        self.slots
+            .get(handle.index())
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        self.slots
+            .get_mut(handle.index())
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.value.as_mut())
+    }
+
+    pub fn contains(&self, handle: Handle<T>) -> bool {
+        self.get(handle).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // one past the highest slot index in use, for tables indexed by `Handle::index`
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    // the handle of the live value in the slot, if any
+    pub fn handle_at(&self, index: usize) -> Option<Handle<T>> {
+        let slot = self.slots.get(index)?;
+        slot.value.as_ref().map(|_| Handle {
+            index: index as u32,
+            generation: slot.generation,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn clear(&mut self) {
+        for index in 0..self.slots.len() {
+            if let Some(handle) = self.handle_at(index) {
+                self.remove(handle);
+            }
+        }
+    }
+
+    // live values in slot order
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<T>, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.value.as_ref().map(|value| {
+                (
+                    Handle {
+                        index: index as u32,
+                        generation: slot.generation,
+                        marker: PhantomData,
+                    },
+                    value,
+                )
+            })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Handle<T>, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let generation = slot.generation;
+                slot.value.as_mut().map(|value| {
+                    (
+                        Handle {
+                            index: index as u32,
+                            generation,
+                            marker: PhantomData,
+                        },
+                        value,
+                    )
+                })
+            })
+    }
+
+    pub fn handles(&self) -> impl Iterator<Item = Handle<T>> + '_ {
+        self.iter().map(|(handle, _)| handle)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(|slot| slot.value.as_ref())
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slots.iter_mut().filter_map(|slot| slot.value.as_mut())
+    }
+}
+
+// panics on stale handles, for places that just checked the handle
+impl<T> std::ops::Index<Handle<T>> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, handle: Handle<T>) -> &T {
+        self.get(handle)
+            .unwrap_or_else(|| panic!("stale arena handle {}", handle))
+    }
+}
+
+impl<T> std::ops::IndexMut<Handle<T>> for Arena<T> {
+    fn index_mut(&mut self, handle: Handle<T>) -> &mut T {
+        self.get_mut(handle)
+            .unwrap_or_else(|| panic!("stale arena handle {}", handle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_handles_go_stale_when_the_slot_is_reused() {
+        let mut arena = Arena::new();
+        let first = arena.insert("first");
+        let second = arena.insert("second");
+
+        assert_eq!(arena.remove(first), Some("first"));
+        assert_eq!(arena.remove(first), None);
+        let third = arena.insert("third");
+
+        // the free slot is reused under a new generation
+        assert_eq!(third.index(), first.index());
+        assert_ne!(third.generation(), first.generation());
+        assert_ne!(third, first);
+        assert_eq!(arena.get(first), None);
+        assert_eq!(arena.get(third), Some(&"third"));
+        assert_eq!(arena.get(second), Some(&"second"));
+        assert_eq!(arena.len(), 2);
+        assert_eq!(arena.slot_count(), 2);
+    }
+
+    #[test]
+    fn iteration_skips_free_slots() {
+        let mut arena = Arena::new();
+        let handles = (0..4).map(|value| arena.insert(value)).collect::<Vec<_>>();
+        arena.remove(handles[1]);
+
+        assert_eq!(arena.values().copied().collect::<Vec<_>>(), vec![0, 2, 3]);
+        assert_eq!(
+            arena.handles().collect::<Vec<_>>(),
+            vec![handles[0], handles[2], handles[3]]
+        );
+        assert_eq!(arena.handle_at(1), None);
+        assert_eq!(arena.handle_at(2), Some(handles[2]));
+
+        arena.clear();
+        assert!(arena.is_empty());
+        assert!(handles.iter().all(|&handle| !arena.contains(handle)));
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+
+use crate::graphics::{
+    material::Material,
+    mesh::{Geometry, SubMesh},
+    texture::Texture,
+};
+use crate::reader::error::FileError;
+
+// never returns the same number twice, so revisions of different data never match
+pub fn next_revision() -> u64 {
+    static REVISION: AtomicU64 = AtomicU64::new(1);
+    REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Clone)]
+struct AssetData<T> {
+    revision: u64,
+    value: T,
+}
+
+// shared, reference counted asset data, cloning the handle only bumps the count
+pub struct Asset<T> {
+    value: Arc<AssetData<T>>,
+}
+
+impl<T> Asset<T> {
+    // an asset that is not known to any manager, so it is never shared by deduplication
+    pub fn new(value: T) -> Self {
+        Asset {
+            value: Arc::new(AssetData {
+                revision: next_revision(),
+                value,
+            }),
+        }
+    }
+
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.value, &other.value)
+    }
+
+    // identifies the data, changes whenever it is modified, so gpu copies can be keyed by it
+    pub fn revision(&self) -> u64 {
+        self.value.revision
+    }
+
+    // number of handles to the data, including this one
+    pub fn reference_count(&self) -> usize {
+        Arc::strong_count(&self.value)
+    }
+
+    fn downgrade(&self) -> Weak<AssetData<T>> {
+        Arc::downgrade(&self.value)
+    }
+}
+
+impl<T: Clone> Asset<T> {
+    // copy on write, a shared asset is copied first so the other users keep the old data
+    pub fn make_mut(&mut self) -> &mut T {
+        let data = Arc::make_mut(&mut self.value);
+        data.revision = next_revision();
+        &mut data.value
+    }
+}
+
+// implemented by hand, deriving would require `T: Clone`
+impl<T> Clone for Asset<T> {
+    fn clone(&self) -> Self {
+        Asset {
+            value: Arc::clone(&self.value),
+        }
+    }
+}
+
+impl<T> std::ops::Deref for Asset<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value.value
+    }
+}
+
+// two handles are equal when they share the data, not when the data compares equal
+impl<T> PartialEq for Asset<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other)
+    }
+}
+
+impl<T> Eq for Asset<T> {}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Asset<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.value.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AssetStats {
+    pub geometry: usize,
+    pub materials: usize,
+    pub textures: usize,
+}
+
+impl std::fmt::Display for AssetStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} geometry, {} materials, {} textures",
+            self.geometry, self.materials, self.textures
+        )
+    }
+}
+
+// hands out shared assets, adding data equal to a live asset returns that asset instead
+// of a copy, the manager only keeps weak references so unused assets are freed
+#[derive(Debug, Default)]
+pub struct AssetManager {
+    // keyed by a hash of the contents, equal hashes are compared in full
+    geometry: HashMap<u64, Vec<Weak<AssetData<Geometry>>>>,
+    materials: Vec<Weak<AssetData<Material>>>,
+    // keyed by the canonical path of the image
+    textures: HashMap<std::path::PathBuf, Weak<AssetData<Texture>>>,
+}
+
+impl AssetManager {
+    pub fn new() -> Self {
+        AssetManager::default()
+    }
+
+    pub fn add_geometry(&mut self, geometry: Geometry) -> Asset<Geometry> {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        geometry.vertices.hash(&mut hasher);
+        geometry.indices.hash(&mut hasher);
+        let entries = self.geometry.entry(hasher.finish()).or_default();
+
+        entries.retain(|entry| entry.strong_count() > 0);
+        if let Some(value) = entries
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|value| value.value == geometry)
+        {
+            return Asset { value };
+        }
+
+        let asset = Asset::new(geometry);
+        entries.push(asset.downgrade());
+        asset
+    }
+
+    // shares the geometry of the submesh with equal geometry that is already alive
+    pub fn share_geometry(&mut self, submesh: &mut SubMesh) {
+        let geometry = std::mem::take(&mut **submesh);
+        submesh.geometry = self.add_geometry(geometry);
+    }
+
+    pub fn add_material(&mut self, material: Material) -> Asset<Material> {
+        self.materials.retain(|entry| entry.strong_count() > 0);
+        if let Some(value) = self
+            .materials
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|value| value.value == material)
+        {
+            return Asset { value };
+        }
+
+        let asset = Asset::new(material);
+        self.materials.push(asset.downgrade());
+        asset
+    }
+
+    // the image is only read the first time, later loads share the texture while it is in use
+    pub fn load_texture(&mut self, path: &str) -> Result<Asset<Texture>, FileError> {
+        let key = std::fs::canonicalize(path).map_err(FileError::IoError)?;
+        if let Some(value) = self.textures.get(&key).and_then(Weak::upgrade) {
+            return Ok(Asset { value });
+        }
+
+        let asset = Asset::new(crate::reader::image_reader::read_texture(path)?);
+        self.textures.insert(key, asset.downgrade());
+        Ok(asset)
+    }
+
+    // forgets the assets nobody uses anymore, their data is already freed
+    pub fn remove_unused(&mut self) {
+        self.geometry.retain(|_, entries| {
+            entries.retain(|entry| entry.strong_count() > 0);
+            !entries.is_empty()
+        });
+        self.materials.retain(|entry| entry.strong_count() > 0);
+        self.textures.retain(|_, entry| entry.strong_count() > 0);
+    }
+
+    // assets that are still in use
+    pub fn stats(&self) -> AssetStats {
+        AssetStats {
+            geometry: self
+                .geometry
+                .values()
+                .flatten()
+                .filter(|entry| entry.strong_count() > 0)
+                .count(),
+            materials: self
+                .materials
+                .iter()
+                .filter(|entry| entry.strong_count() > 0)
+                .count(),
+            textures: self
+                .textures
+                .values()
+                .filter(|entry| entry.strong_count() > 0)
+                .count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::primitives;
+    use glam::Vec3;
+
+    fn cube_geometry(size: f32) -> Geometry {
+        let mesh = primitives::create_cube(Vec3::splat(size));
+        (*mesh.submeshes[0]).clone()
+    }
+
+    #[test]
+    fn equal_data_is_shared_while_it_is_alive() {
+        let mut assets = AssetManager::new();
+        let first = assets.add_geometry(cube_geometry(1.0));
+        let second = assets.add_geometry(cube_geometry(1.0));
+        let other = assets.add_geometry(cube_geometry(2.0));
+
+        assert!(first.ptr_eq(&second));
+        assert_eq!(first.revision(), second.revision());
+        assert_eq!(first.reference_count(), 2);
+        assert!(!first.ptr_eq(&other));
+        assert_eq!(assets.stats().geometry, 2);
+
+        drop((first, second));
+        assert_eq!(assets.stats().geometry, 1);
+        // nothing is left to share with, so the data is added again
+        let third = assets.add_geometry(cube_geometry(1.0));
+        assert_eq!(third.reference_count(), 1);
+
+        let material = assets.add_material(Material::default());
+        assert!(material.ptr_eq(&assets.add_material(Material::default())));
+        assert_eq!(assets.stats().materials, 1);
+    }
+
+    #[test]
+    fn make_mut_copies_shared_data() {
+        let mut assets = AssetManager::new();
+        let mut edited = assets.add_geometry(cube_geometry(1.0));
+        let kept = edited.clone();
+        let revision = kept.revision();
+
+        edited.make_mut().indices.truncate(3);
+
+        assert!(!edited.ptr_eq(&kept));
+        assert_ne!(edited.revision(), revision);
+        assert_eq!(kept.revision(), revision);
+        assert_eq!(edited.indices.len(), 3);
+        assert_eq!(kept.indices.len(), 36);
+        assert_eq!(kept.reference_count(), 1);
+    }
+}
@@ -1,29 +1,66 @@
 use std::{fmt::Debug, sync::Arc};
 
 pub mod error;
+pub mod software_backend;
+pub mod software_rasterizer;
 pub mod vulkan_backend;
+pub mod vulkan_offscreen;
+pub mod vulkan_scene;
+// a renderer, nothing of it depends on a window system, so it can render scenes into
+// images on machines without a display and backends without windows can be written
 pub trait RenderBackend: Sized + Debug {
-    type Context: RenderContext;
     type Error: Debug;
 
+    // a backend that only renders into images
+    fn new_headless() -> Result<Self, Self::Error>;
+
+    // renders the scene as seen from the camera into an image of the size
+    fn render_image(
+        &mut self,
+        scene: &crate::graphics::scene::Scene,
+        camera: &crate::graphics::camera::Camera,
+        width: u32,
+        height: u32,
+    ) -> Result<crate::graphics::rendered_image::RenderedImage, crate::graphics::error::GraphicsError>;
+}
+
+// a backend the app can draw its winit windows with, everything backend specific stays
+// behind the per window context and the scene is only read, so a backend uploads what
+// changed from the scene revisions itself
+pub trait WindowBackend: RenderBackend {
+    type Context: RenderContext;
+
     fn new(event_loop: &winit::event_loop::EventLoop<()>) -> Result<Self, Self::Error>;
 
+    // the surface of the window the backend presents to, with its gui
     fn create_window_context(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
         window: Arc<winit::window::Window>,
     ) -> Result<Self::Context, crate::graphics::error::GraphicsError>;
 
+    // draws the scene with the gui last built by `GuiIntegration::build` on top,
+    // returns what the frustum culling kept and skipped
     fn draw_frame(
         &mut self,
         context: &mut Self::Context,
-        gui_callback: impl FnOnce(&mut egui_winit_vulkano::egui::Context),
         scene: &crate::graphics::scene::Scene,
-    ) -> Result<(), crate::graphics::error::GraphicsError>;
+    ) -> Result<crate::graphics::frustum::CullingStats, crate::graphics::error::GraphicsError>;
 }
 
 pub trait RenderContext {
+    type Gui: GuiIntegration;
+
     fn window(&self) -> Arc<winit::window::Window>;
     fn resize(&mut self) -> Result<(), crate::graphics::error::GraphicsError>;
-    fn gui_update(&mut self, winit_event: &winit::event::WindowEvent) -> bool;
+    fn gui(&mut self) -> &mut Self::Gui;
+}
+
+// feeds the window events to egui and keeps what it built for the backend to draw
+pub trait GuiIntegration {
+    // returns whether egui used the event, it is not handled further then
+    fn handle_event(&mut self, event: &winit::event::WindowEvent) -> bool;
+    // builds the gui of the next frame, separate from drawing so the callback can
+    // modify the scene that is drawn afterwards
+    fn build(&mut self, ui: impl FnOnce(&egui::Context));
 }
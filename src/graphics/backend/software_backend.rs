@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use crate::graphics::backend::software_rasterizer::{Framebuffer, draw_scene};
+use crate::graphics::backend::{GuiIntegration, RenderBackend, RenderContext, WindowBackend};
+use crate::graphics::error::GraphicsError;
+
+// draws on the cpu, for machines without a vulkan driver and as the reference the
+// vulkan output is compared with
+#[derive(Debug, Default)]
+pub struct SoftwareBackend {}
+
+// the framebuffer is copied to the window with the gui drawn over it, it keeps the
+// frame in linear color until the next one, to save or compare it
+pub struct SoftwareContext {
+    window: Arc<winit::window::Window>,
+    // the surface keeps what it needs of the display, the context is only kept with it
+    _display: softbuffer::Context<Arc<winit::window::Window>>,
+    surface: softbuffer::Surface<Arc<winit::window::Window>, Arc<winit::window::Window>>,
+    pub framebuffer: Framebuffer,
+    gui: SoftwareGui,
+}
+
+impl std::fmt::Debug for SoftwareContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SoftwareContext")
+            .field("window", &self.window.id())
+            .field("framebuffer", &self.framebuffer.color)
+            .finish_non_exhaustive()
+    }
+}
+
+// egui gets the window events through egui-winit and its meshes are drawn on the cpu
+// as well, over the srgb pixels like egui expects them to be blended
+pub struct SoftwareGui {
+    window: Arc<winit::window::Window>,
+    state: egui_winit::State,
+    textures: HashMap<egui::TextureId, GuiTexture>,
+    // freed once the frame that still used them is drawn
+    textures_to_free: Vec<egui::TextureId>,
+    // what the last build produced, in points
+    primitives: Vec<egui::ClippedPrimitive>,
+    pixels_per_point: f32,
+}
+
+impl std::fmt::Debug for SoftwareGui {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SoftwareGui")
+            .field("textures", &self.textures.len())
+            .field("primitives", &self.primitives.len())
+            .finish_non_exhaustive()
+    }
+}
+
+// premultiplied srgb texels like egui uploads them
+struct GuiTexture {
+    size: [usize; 2],
+    pixels: Vec<egui::Color32>,
+}
+
+impl RenderBackend for SoftwareBackend {
+    type Error = GraphicsError;
+
+    fn new_headless() -> Result<Self, Self::Error> {
+        Ok(SoftwareBackend {})
+    }
+
+    fn render_image(
+        &mut self,
+        scene: &crate::graphics::scene::Scene,
+        camera: &crate::graphics::camera::Camera,
+        width: u32,
+        height: u32,
+    ) -> Result<crate::graphics::rendered_image::RenderedImage, GraphicsError> {
+        let mut framebuffer = Framebuffer::new(width.max(1), height.max(1));
+        draw_scene(&mut framebuffer, scene, camera);
+        Ok(framebuffer.color)
+    }
+}
+
+impl WindowBackend for SoftwareBackend {
+    type Context = SoftwareContext;
+
+    fn new(_event_loop: &winit::event_loop::EventLoop<()>) -> Result<Self, Self::Error> {
+        Ok(SoftwareBackend {})
+    }
+
+    fn create_window_context(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        window: Arc<winit::window::Window>,
+    ) -> Result<Self::Context, GraphicsError> {
+        let display = softbuffer::Context::new(window.clone()).map_err(|e| {
+            GraphicsError::RenderingError(format!("Failed to connect to the display: {}", e))
+        })?;
+        let surface = softbuffer::Surface::new(&display, window.clone()).map_err(|e| {
+            GraphicsError::RenderingError(format!("Failed to create surface: {}", e))
+        })?;
+        let size = window.inner_size();
+
+        let mut context = SoftwareContext {
+            window: window.clone(),
+            _display: display,
+            surface,
+            framebuffer: Framebuffer::new(size.width.max(1), size.height.max(1)),
+            gui: SoftwareGui::new(event_loop, window),
+        };
+        context.resize()?;
+        Ok(context)
+    }
+
+    fn draw_frame(
+        &mut self,
+        context: &mut Self::Context,
+        scene: &crate::graphics::scene::Scene,
+    ) -> Result<crate::graphics::frustum::CullingStats, GraphicsError> {
+        let camera = scene.main_camera().ok_or(GraphicsError::NoCameraFound)?;
+
+        context.framebuffer.clear();
+        let culling = draw_scene(&mut context.framebuffer, scene, camera);
+        context.present()?;
+        Ok(culling)
+    }
+}
+
+impl SoftwareContext {
+    // copies the framebuffer to the window in srgb with the gui on top
+    fn present(&mut self) -> Result<(), GraphicsError> {
+        let to_error = |e: softbuffer::SoftBufferError| {
+            GraphicsError::RenderingError(format!("Failed to present framebuffer: {}", e))
+        };
+        let width = self.framebuffer.width() as usize;
+        let height = self.framebuffer.height() as usize;
+
+        let mut buffer = self.surface.buffer_mut().map_err(to_error)?;
+        let pixels = self.framebuffer.color.to_rgba8();
+        for (target, pixel) in buffer.iter_mut().zip(pixels.pixels()) {
+            let [r, g, b, _] = pixel.0;
+            *target = u32::from_be_bytes([0, r, g, b]);
+        }
+        self.gui.paint(&mut buffer, width, height);
+
+        buffer.present().map_err(to_error)
+    }
+}
+
+impl RenderContext for SoftwareContext {
+    type Gui = SoftwareGui;
+
+    fn window(&self) -> Arc<winit::window::Window> {
+        self.window.clone()
+    }
+
+    fn resize(&mut self) -> Result<(), GraphicsError> {
+        let size = self.window.inner_size();
+        let (width, height) = (size.width.max(1), size.height.max(1));
+        self.framebuffer.resize(width, height);
+        self.surface
+            .resize(
+                NonZeroU32::new(width).unwrap_or(NonZeroU32::MIN),
+                NonZeroU32::new(height).unwrap_or(NonZeroU32::MIN),
+            )
+            .map_err(|e| GraphicsError::RenderingError(format!("Failed to resize surface: {}", e)))
+    }
+
+    fn gui(&mut self) -> &mut Self::Gui {
+        &mut self.gui
+    }
+}
+
+impl SoftwareGui {
+    fn new(
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        window: Arc<winit::window::Window>,
+    ) -> Self {
+        let state = egui_winit::State::new(
+            egui::Context::default(),
+            egui::ViewportId::ROOT,
+            event_loop,
+            Some(window.scale_factor() as f32),
+            window.theme(),
+            None,
+        );
+        SoftwareGui {
+            window,
+            state,
+            textures: HashMap::new(),
+            textures_to_free: Vec::new(),
+            primitives: Vec::new(),
+            pixels_per_point: 1.0,
+        }
+    }
+
+    fn set_texture(&mut self, id: egui::TextureId, delta: &egui::epaint::ImageDelta) {
+        let [width, height] = delta.image.size();
+        let pixels = match &delta.image {
+            egui::ImageData::Color(image) => image.pixels.clone(),
+            egui::ImageData::Font(image) => image.srgba_pixels(None).collect(),
+        };
+
+        match delta.pos {
+            None => {
+                self.textures.insert(
+                    id,
+                    GuiTexture {
+                        size: [width, height],
+                        pixels,
+                    },
+                );
+            }
+            // a patch of a texture that is already there, like new glyphs of the font
+            Some([x, y]) => {
+                let Some(texture) = self.textures.get_mut(&id) else {
+                    return;
+                };
+                for row in 0..height {
+                    let start = (y + row) * texture.size[0] + x;
+                    texture.pixels[start..start + width]
+                        .copy_from_slice(&pixels[row * width..(row + 1) * width]);
+                }
+            }
+        }
+    }
+
+    // blends the meshes of the last build over the srgb pixels, premultiplied like the
+    // egui shaders do it
+    fn paint(&mut self, buffer: &mut [u32], width: usize, height: usize) {
+        for primitive in &self.primitives {
+            // callbacks draw with a specific graphics api
+            let egui::epaint::Primitive::Mesh(mesh) = &primitive.primitive else {
+                continue;
+            };
+            let Some(texture) = self.textures.get(&mesh.texture_id) else {
+                continue;
+            };
+            let clip = primitive.clip_rect * self.pixels_per_point;
+            let clip = [
+                clip.min.x.round().clamp(0.0, width as f32) as usize,
+                clip.min.y.round().clamp(0.0, height as f32) as usize,
+                clip.max.x.round().clamp(0.0, width as f32) as usize,
+                clip.max.y.round().clamp(0.0, height as f32) as usize,
+            ];
+
+            for triangle in mesh.indices.chunks_exact(3) {
+                let vertices = [
+                    &mesh.vertices[triangle[0] as usize],
+                    &mesh.vertices[triangle[1] as usize],
+                    &mesh.vertices[triangle[2] as usize],
+                ];
+                paint_triangle(
+                    buffer,
+                    width,
+                    clip,
+                    vertices,
+                    texture,
+                    self.pixels_per_point,
+                );
+            }
+        }
+
+        for id in self.textures_to_free.drain(..) {
+            self.textures.remove(&id);
+        }
+    }
+}
+
+impl GuiIntegration for SoftwareGui {
+    fn handle_event(&mut self, event: &winit::event::WindowEvent) -> bool {
+        self.state.on_window_event(&self.window, event).consumed
+    }
+
+    fn build(&mut self, ui: impl FnOnce(&egui::Context)) {
+        let input = self.state.take_egui_input(&self.window);
+        let context = self.state.egui_ctx().clone();
+        context.begin_pass(input);
+        ui(&context);
+        let output = context.end_pass();
+
+        self.state
+            .handle_platform_output(&self.window, output.platform_output);
+        for (id, delta) in &output.textures_delta.set {
+            self.set_texture(*id, delta);
+        }
+        self.textures_to_free
+            .extend(output.textures_delta.free.iter().copied());
+        self.primitives = context.tessellate(output.shapes, output.pixels_per_point);
+        self.pixels_per_point = output.pixels_per_point;
+    }
+}
+
+// fills the pixel centers inside the triangle and the clip rectangle, with the vertex
+// colors interpolated and the nearest texel
+fn paint_triangle(
+    buffer: &mut [u32],
+    width: usize,
+    [min_x, min_y, max_x, max_y]: [usize; 4],
+    vertices: [&egui::epaint::Vertex; 3],
+    texture: &GuiTexture,
+    pixels_per_point: f32,
+) {
+    let edge = |a: egui::Vec2, b: egui::Vec2, p: egui::Vec2| {
+        (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+    };
+    // egui uses both windings, turned the same way the edges shared by two triangles
+    // run in opposite directions, and only one of the two owns the pixels on them
+    let mut vertices = vertices;
+    let mut positions = vertices.map(|vertex| vertex.pos.to_vec2() * pixels_per_point);
+    let mut area = edge(positions[0], positions[1], positions[2]);
+    if area < 0.0 {
+        vertices.swap(1, 2);
+        positions.swap(1, 2);
+        area = -area;
+    }
+    if area == 0.0 {
+        return;
+    }
+    let owns = |a: egui::Vec2, b: egui::Vec2| a.y > b.y || (a.y == b.y && a.x < b.x);
+    let edges = [(1, 2), (2, 0), (0, 1)].map(|(a, b)| (positions[a], positions[b]));
+
+    let bounds_min = positions[0].min(positions[1]).min(positions[2]);
+    let bounds_max = positions[0].max(positions[1]).max(positions[2]);
+    let x_range = (bounds_min.x.floor().max(min_x as f32) as usize)
+        ..(bounds_max.x.ceil().min(max_x as f32) as usize);
+    let y_range = (bounds_min.y.floor().max(min_y as f32) as usize)
+        ..(bounds_max.y.ceil().min(max_y as f32) as usize);
+
+    for y in y_range {
+        for x in x_range.clone() {
+            let p = egui::vec2(x as f32 + 0.5, y as f32 + 0.5);
+            let distances = edges.map(|(a, b)| edge(a, b, p));
+            if edges
+                .iter()
+                .zip(distances)
+                .any(|(&(a, b), distance)| distance < 0.0 || (distance == 0.0 && !owns(a, b)))
+            {
+                continue;
+            }
+            let weights = distances.map(|distance| distance / area);
+
+            let uv = vertices
+                .iter()
+                .zip(weights)
+                .fold(egui::Vec2::ZERO, |uv, (vertex, weight)| {
+                    uv + vertex.uv.to_vec2() * weight
+                });
+            let texel_x = ((uv.x * texture.size[0] as f32) as usize).min(texture.size[0] - 1);
+            let texel_y = ((uv.y * texture.size[1] as f32) as usize).min(texture.size[1] - 1);
+            let texel = texture.pixels[texel_y * texture.size[0] + texel_x].to_array();
+
+            let mut source = [0.0; 4];
+            for (vertex, weight) in vertices.iter().zip(weights) {
+                for (channel, value) in source.iter_mut().zip(vertex.color.to_array()) {
+                    *channel += value as f32 * weight;
+                }
+            }
+            let source = std::array::from_fn::<f32, 4, _>(|channel| {
+                source[channel] * texel[channel] as f32 / (255.0 * 255.0)
+            });
+
+            let target = &mut buffer[y * width + x];
+            let [_, r, g, b] = target.to_be_bytes();
+            let coverage = source[3].min(1.0);
+            let blend = |source: f32, target: u8| {
+                ((source + target as f32 / 255.0 * (1.0 - coverage)) * 255.0)
+                    .round()
+                    .clamp(0.0, 255.0) as u8
+            };
+            *target = u32::from_be_bytes([
+                0,
+                blend(source[0], r),
+                blend(source[1], g),
+                blend(source[2], b),
+            ]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(x: f32, y: f32, color: egui::Color32) -> egui::epaint::Vertex {
+        egui::epaint::Vertex {
+            pos: egui::pos2(x, y),
+            uv: egui::epaint::WHITE_UV,
+            color,
+        }
+    }
+
+    #[test]
+    fn gui_triangles_blend_over_the_pixels_inside_the_clip() {
+        let texture = GuiTexture {
+            size: [1, 1],
+            pixels: vec![egui::Color32::WHITE],
+        };
+        // half transparent red, premultiplied, over gray
+        let color = egui::Color32::from_rgba_premultiplied(128, 0, 0, 128);
+        let [a, b, c, d] =
+            [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)].map(|(x, y)| vertex(x, y, color));
+        let mut buffer = vec![u32::from_be_bytes([0, 100, 100, 100]); 4 * 4];
+        // points are two pixels wide, the clip cuts off the last column
+        for triangle in [[&a, &b, &c], [&a, &c, &d]] {
+            paint_triangle(&mut buffer, 4, [0, 0, 3, 4], triangle, &texture, 2.0);
+        }
+
+        let blended = u32::from_be_bytes([0, 178, 50, 50]);
+        let gray = u32::from_be_bytes([0, 100, 100, 100]);
+        for y in 0..4 {
+            assert_eq!(buffer[y * 4..y * 4 + 4], [blended, blended, blended, gray]);
+        }
+    }
+}
@@ -0,0 +1,713 @@
+use glam::{Mat3, Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
+
+use crate::graphics::camera::Camera;
+use crate::graphics::frustum::CullingStats;
+use crate::graphics::light::{Light, SpotLight};
+use crate::graphics::material::GpuMaterials;
+use crate::graphics::mesh::SubMesh;
+use crate::graphics::rendered_image::RenderedImage;
+use crate::graphics::scene::Scene;
+use crate::graphics::shadow::{Cascade, CubeShadow, ShadowSettings};
+
+// the ambient light fragment.glsl adds to every material
+const AMBIENT_LIGHT: Vec3 = Vec3::splat(0.1);
+
+// the color and depth attachments the rasterizer draws into
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    pub color: RenderedImage,
+    // depth from 0 at the near plane to 1 at the far plane, row by row like the colors
+    pub depth: Vec<f32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut framebuffer = Framebuffer {
+            color: RenderedImage::new(width, height),
+            depth: Vec::new(),
+        };
+        framebuffer.clear();
+        framebuffer
+    }
+
+    pub fn width(&self) -> u32 {
+        self.color.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.color.height
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width != self.width() || height != self.height() {
+            *self = Framebuffer::new(width, height);
+        }
+    }
+
+    // the same clear values as the vulkan render pass
+    pub fn clear(&mut self) {
+        self.color.pixels.fill([0.0, 0.0, 0.0, 1.0]);
+        self.depth.clear();
+        self.depth.resize(self.color.pixels.len(), 1.0);
+    }
+}
+
+// a vertex after the vertex stage, positions and normals in view space like vertex.glsl
+#[derive(Debug, Clone, Copy)]
+struct ClipVertex {
+    clip: Vec4,
+    position: Vec3,
+    normal: Vec3,
+}
+
+impl ClipVertex {
+    fn lerp(&self, other: &ClipVertex, t: f32) -> ClipVertex {
+        ClipVertex {
+            clip: self.clip.lerp(other.clip, t),
+            position: self.position.lerp(other.position, t),
+            normal: self.normal.lerp(other.normal, t),
+        }
+    }
+}
+
+// a light moved into view space the way fragment.glsl reads it
+#[derive(Debug, Clone)]
+enum ViewLight {
+    Point {
+        position: Vec3,
+        color: Vec3,
+        intensity: f32,
+        // its cube in the shadow maps
+        shadow: Option<usize>,
+    },
+    Directional {
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        // the first of its cascades in the shadow maps
+        shadow: Option<usize>,
+    },
+    // position and direction in view space
+    Spot(SpotLight),
+}
+
+// the depth of one cascade or cube face as seen from its light, drawn like the shadow
+// passes of the vulkan pipeline
+#[derive(Debug, Clone)]
+struct ShadowMap {
+    // view space to the clip space of the map
+    from_view: Mat4,
+    resolution: u32,
+    depth: Vec<f32>,
+}
+
+// what lights the fragments, the cascades of a light follow each other in `cascades`
+#[derive(Debug, Clone)]
+struct Lighting {
+    lights: Vec<ViewLight>,
+    cascades: Vec<(Cascade, ShadowMap)>,
+    // the six faces of every cube, in the order of `CubeShadow::faces`
+    cubes: Vec<(CubeShadow, Vec<ShadowMap>)>,
+    // view space directions back to world space, the cube faces are aligned with it
+    inverse_view_rotation: Mat3,
+    settings: ShadowSettings,
+}
+
+// draws the scene as seen from the camera into the framebuffer, with the culling,
+// levels of detail, rasterizer state and lighting of the vulkan pipeline so both
+// produce comparable images, returns what the frustum culling kept and skipped
+pub fn draw_scene(framebuffer: &mut Framebuffer, scene: &Scene, camera: &Camera) -> CullingStats {
+    let aspect_ratio = framebuffer.width() as f32 / framebuffer.height() as f32;
+    let view = camera.view_matrix();
+    let projection = camera.projection_matrix(aspect_ratio);
+    let frustum = camera.frustum(aspect_ratio);
+
+    // the same lights get shadow maps as in the vulkan pipeline
+    let settings = scene.shadows;
+    let scene_bounds = scene.bounds();
+    let mut cascades = Vec::new();
+    let mut cubes = Vec::new();
+
+    let lights = scene
+        .lights
+        .values()
+        .map(|light| match light {
+            Light::Point(point) => {
+                let shadowed = point.cast_shadows && cubes.len() < settings.max_point_shadows();
+                let shadow = shadowed.then(|| {
+                    let cube = settings.cube_shadow(point.position, &scene_bounds);
+                    let faces = cube
+                        .faces
+                        .iter()
+                        .map(|face| draw_shadow_map(scene, face, &view, settings.point_resolution))
+                        .collect();
+                    cubes.push((cube, faces));
+                    cubes.len() - 1
+                });
+                ViewLight::Point {
+                    position: view.transform_point3(point.position),
+                    color: point.color,
+                    intensity: point.intensity,
+                    shadow,
+                }
+            }
+            Light::Spot(spot) => ViewLight::Spot(SpotLight {
+                position: view.transform_point3(spot.position),
+                direction: view.transform_vector3(spot.direction),
+                ..spot.clone()
+            }),
+            // lit like a point light in the center, as the vulkan pipeline does
+            Light::Area(area) => ViewLight::Point {
+                position: view.transform_point3(area.position),
+                color: area.color,
+                intensity: area.intensity,
+                shadow: None,
+            },
+            Light::Directional(directional) => {
+                let shadowed = directional.cast_shadows
+                    && cascades.len()
+                        < settings.max_shadowed_lights() * settings.cascade_count() as usize;
+                let shadow = shadowed.then(|| {
+                    let first = cascades.len();
+                    for cascade in settings.cascades(
+                        camera,
+                        aspect_ratio,
+                        directional.direction,
+                        &scene_bounds,
+                    ) {
+                        let map = draw_shadow_map(
+                            scene,
+                            &cascade.view_projection,
+                            &view,
+                            settings.resolution,
+                        );
+                        cascades.push((cascade, map));
+                    }
+                    first
+                });
+                ViewLight::Directional {
+                    direction: view.transform_vector3(directional.direction),
+                    color: directional.color,
+                    intensity: directional.intensity,
+                    shadow,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    let lighting = Lighting {
+        lights,
+        cascades,
+        cubes,
+        inverse_view_rotation: Mat3::from_mat4(view).transpose(),
+        settings,
+    };
+
+    let mut culling = CullingStats::default();
+    for object in scene.objects.values() {
+        let world_bounds = object.world_bounds();
+        if !frustum.intersects_sphere(&world_bounds.sphere)
+            || !frustum.intersects_aabb(&world_bounds.aabb)
+        {
+            culling.culled_objects += 1;
+            culling.culled_submeshes += object.submeshes.len();
+            continue;
+        }
+        culling.visible_objects += 1;
+
+        let screen_size = crate::graphics::mesh::simplify::projected_screen_size(
+            world_bounds.sphere.center,
+            world_bounds.sphere.radius,
+            camera,
+        );
+        let model_view = view * object.world_transform;
+        // only the upper 3x3 like vertex.glsl, the inverse transpose moves the translation
+        // into the bottom row
+        let normal_matrix =
+            Mat3::from_mat4(view) * Mat3::from_mat4(object.world_transform.inverse().transpose());
+
+        for submesh in &object.submeshes {
+            if submesh.vertices.is_empty() || submesh.indices.is_empty() {
+                continue;
+            }
+            let aabb = submesh.bounds().aabb.transform(&object.world_transform);
+            if !frustum.intersects_aabb(&aabb) {
+                culling.culled_submeshes += 1;
+                continue;
+            }
+            culling.visible_submeshes += 1;
+
+            let material = submesh
+                .material
+                .and_then(|material| scene.materials.get(material))
+                .map_or_else(GpuMaterials::default, |material| {
+                    material.properties.clone()
+                });
+
+            draw_submesh(
+                framebuffer,
+                submesh,
+                submesh.select_lod(screen_size),
+                &model_view,
+                &normal_matrix,
+                &projection,
+                &material,
+                &lighting,
+            );
+        }
+    }
+
+    culling
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_submesh(
+    framebuffer: &mut Framebuffer,
+    submesh: &SubMesh,
+    indices: &[u32],
+    model_view: &Mat4,
+    normal_matrix: &Mat3,
+    projection: &Mat4,
+    material: &GpuMaterials,
+    lighting: &Lighting,
+) {
+    let vertices = submesh
+        .vertices
+        .iter()
+        .map(|vertex| {
+            let position = model_view.transform_point3(vertex.position);
+            ClipVertex {
+                clip: *projection * position.extend(1.0),
+                position,
+                normal: normal_matrix.mul_vec3(vertex.normal).normalize_or_zero(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    for triangle in indices.chunks_exact(3) {
+        let corners = [0, 1, 2].map(|corner| vertices[triangle[corner] as usize]);
+        let polygon = clip_near(&corners);
+        // the clipped polygon is convex, so it is drawn as a fan
+        for index in 1..polygon.len().saturating_sub(1) {
+            draw_triangle(
+                framebuffer,
+                [polygon[0], polygon[index], polygon[index + 1]],
+                material,
+                lighting,
+            );
+        }
+    }
+}
+
+// cuts off the part of the triangle in front of the near plane, where the depth would
+// fall below 0, the other planes are handled per pixel
+fn clip_near(triangle: &[ClipVertex; 3]) -> Vec<ClipVertex> {
+    let mut polygon = Vec::with_capacity(4);
+    for index in 0..3 {
+        let current = triangle[index];
+        let next = triangle[(index + 1) % 3];
+        let current_inside = current.clip.z >= 0.0;
+        let next_inside = next.clip.z >= 0.0;
+
+        if current_inside {
+            polygon.push(current);
+        }
+        if current_inside != next_inside {
+            let t = current.clip.z / (current.clip.z - next.clip.z);
+            polygon.push(current.lerp(&next, t));
+        }
+    }
+    polygon
+}
+
+fn draw_triangle(
+    framebuffer: &mut Framebuffer,
+    triangle: [ClipVertex; 3],
+    material: &GpuMaterials,
+    lighting: &Lighting,
+) {
+    let width = framebuffer.width();
+    let height = framebuffer.height();
+
+    // the viewport transform, the top row of the framebuffer is at -1 like in vulkan
+    let inverse_w = triangle.map(|vertex| 1.0 / vertex.clip.w);
+    let screen = [0, 1, 2].map(|corner| {
+        let ndc = triangle[corner].clip.xyz() * inverse_w[corner];
+        Vec3::new(
+            (ndc.x + 1.0) * 0.5 * width as f32,
+            (ndc.y + 1.0) * 0.5 * height as f32,
+            ndc.z,
+        )
+    });
+
+    // the pipeline culls back faces with clockwise front faces, with y pointing down a
+    // clockwise triangle has a positive area
+    let area = edge(
+        screen[0].truncate(),
+        screen[1].truncate(),
+        screen[2].truncate(),
+    );
+    if area <= 0.0 {
+        return;
+    }
+
+    let min = screen[0].min(screen[1]).min(screen[2]);
+    let max = screen[0].max(screen[1]).max(screen[2]);
+    let min_x = min.x.floor().max(0.0) as u32;
+    let min_y = min.y.floor().max(0.0) as u32;
+    let max_x = (max.x.ceil() as i64).clamp(0, width as i64) as u32;
+    let max_y = (max.y.ceil() as i64).clamp(0, height as i64) as u32;
+
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let sample = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+            let weights = [
+                edge(screen[1].truncate(), screen[2].truncate(), sample),
+                edge(screen[2].truncate(), screen[0].truncate(), sample),
+                edge(screen[0].truncate(), screen[1].truncate(), sample),
+            ];
+            if weights.iter().any(|&weight| weight < 0.0) {
+                continue;
+            }
+            let weights = weights.map(|weight| weight / area);
+
+            // depth is interpolated in screen space, the far plane is clipped here
+            let depth =
+                weights[0] * screen[0].z + weights[1] * screen[1].z + weights[2] * screen[2].z;
+            let pixel = y as usize * width as usize + x as usize;
+            if !(0.0..=1.0).contains(&depth) || depth >= framebuffer.depth[pixel] {
+                continue;
+            }
+
+            // everything else is interpolated perspective correct
+            let perspective = [0, 1, 2].map(|corner| weights[corner] * inverse_w[corner]);
+            let total = perspective[0] + perspective[1] + perspective[2];
+            let interpolate = |attribute: fn(&ClipVertex) -> Vec3| {
+                (attribute(&triangle[0]) * perspective[0]
+                    + attribute(&triangle[1]) * perspective[1]
+                    + attribute(&triangle[2]) * perspective[2])
+                    / total
+            };
+            let position = interpolate(|vertex| vertex.position);
+            let normal = interpolate(|vertex| vertex.normal);
+
+            framebuffer.depth[pixel] = depth;
+            framebuffer.color.pixels[pixel] = shade(material, lighting, position, normal)
+                .extend(1.0)
+                .to_array();
+        }
+    }
+}
+
+// twice the signed area of the triangle a, b, p
+fn edge(a: Vec2, b: Vec2, p: Vec2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+// the blinn-phong lighting of fragment.glsl, the interpolated normal is not normalized
+// again there either
+fn shade(material: &GpuMaterials, lighting: &Lighting, position: Vec3, normal: Vec3) -> Vec3 {
+    let view_direction = (-position).normalize_or_zero();
+
+    let mut total = Vec3::ZERO;
+    for light in &lighting.lights {
+        let (light_direction, color, intensity) = match light {
+            &ViewLight::Point {
+                position: light_position,
+                color,
+                intensity,
+                shadow,
+            } => (
+                (light_position - position).normalize_or_zero(),
+                color,
+                intensity
+                    * attenuation(light_position.distance(position))
+                    * shadow.map_or(1.0, |cube| {
+                        point_shadow(lighting, cube, light_position, position, normal)
+                    }),
+            ),
+            &ViewLight::Directional {
+                direction,
+                color,
+                intensity,
+                shadow,
+            } => (
+                -direction.normalize_or_zero(),
+                color,
+                intensity
+                    * shadow.map_or(1.0, |first| {
+                        directional_shadow(lighting, first, position, normal)
+                    }),
+            ),
+            ViewLight::Spot(spot) => {
+                let distance = spot.position.distance(position);
+                let light_direction = (spot.position - position).normalize_or_zero();
+                (
+                    light_direction,
+                    spot.color,
+                    spot.intensity
+                        * attenuation(distance)
+                        * spot.cone_falloff(-light_direction)
+                        * spot.range_falloff(distance),
+                )
+            }
+        };
+
+        let diffuse = color * material.diffuse_color * normal.dot(light_direction).max(0.0);
+        let half_vector = (light_direction + view_direction).normalize_or_zero();
+        let specular = color
+            * material.specular_color
+            * normal
+                .dot(half_vector)
+                .max(0.0)
+                .powf(material.specular_exponent);
+
+        total += (diffuse + specular) * intensity;
+    }
+
+    total + AMBIENT_LIGHT * material.ambient_color
+}
+
+// draws the depth of every caster the map reaches, both sides of the triangles
+fn draw_shadow_map(
+    scene: &Scene,
+    view_projection: &Mat4,
+    view: &Mat4,
+    resolution: u32,
+) -> ShadowMap {
+    let resolution = resolution.max(1);
+    let size = resolution as f32;
+    let mut depth = vec![1.0; resolution as usize * resolution as usize];
+    let frustum = crate::graphics::frustum::Frustum::from_matrix(view_projection);
+
+    for object in scene.objects.values() {
+        let transform = *view_projection * object.world_transform;
+        for submesh in &object.submeshes {
+            if !frustum.intersects_aabb(&submesh.bounds().aabb.transform(&object.world_transform)) {
+                continue;
+            }
+            let vertices = submesh
+                .vertices
+                .iter()
+                .map(|vertex| ClipVertex {
+                    clip: transform * vertex.position.extend(1.0),
+                    position: Vec3::ZERO,
+                    normal: Vec3::ZERO,
+                })
+                .collect::<Vec<_>>();
+
+            for triangle in submesh.indices.chunks_exact(3) {
+                let corners = [0, 1, 2].map(|corner| vertices[triangle[corner] as usize]);
+                // the faces around point lights are perspective, their casters can reach
+                // behind the light
+                let polygon = clip_near(&corners)
+                    .iter()
+                    .map(|vertex| {
+                        let ndc = vertex.clip.xyz() / vertex.clip.w;
+                        Vec3::new(
+                            (ndc.x + 1.0) * 0.5 * size,
+                            (ndc.y + 1.0) * 0.5 * size,
+                            ndc.z,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                for index in 1..polygon.len().saturating_sub(1) {
+                    let [a, b, c] = [polygon[0], polygon[index], polygon[index + 1]];
+                    let area = edge(a.truncate(), b.truncate(), c.truncate());
+                    if area == 0.0 {
+                        continue;
+                    }
+
+                    let min = a.min(b).min(c);
+                    let max = a.max(b).max(c);
+                    let min_x = min.x.floor().max(0.0) as u32;
+                    let min_y = min.y.floor().max(0.0) as u32;
+                    let max_x = (max.x.ceil() as i64).clamp(0, resolution as i64) as u32;
+                    let max_y = (max.y.ceil() as i64).clamp(0, resolution as i64) as u32;
+                    for y in min_y..max_y {
+                        for x in min_x..max_x {
+                            let sample = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                            let weights = [
+                                edge(b.truncate(), c.truncate(), sample) / area,
+                                edge(c.truncate(), a.truncate(), sample) / area,
+                                edge(a.truncate(), b.truncate(), sample) / area,
+                            ];
+                            if weights.iter().any(|&weight| weight < 0.0) {
+                                continue;
+                            }
+                            let z = weights[0] * a.z + weights[1] * b.z + weights[2] * c.z;
+                            let texel = &mut depth[y as usize * resolution as usize + x as usize];
+                            if (0.0..=1.0).contains(&z) && z < *texel {
+                                *texel = z;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    ShadowMap {
+        from_view: *view_projection * view.inverse(),
+        resolution,
+        depth,
+    }
+}
+
+// the shadow lookups of fragment.glsl, from 0 in full shadow to 1
+fn directional_shadow(lighting: &Lighting, first: usize, position: Vec3, normal: Vec3) -> f32 {
+    let settings = &lighting.settings;
+    let cascades = lighting
+        .cascades
+        .get(first..first + settings.cascade_count() as usize)
+        .unwrap_or_default();
+    // nothing is shadowed beyond the last cascade
+    let Some((cascade, map)) = cascades
+        .iter()
+        .find(|(cascade, _)| -position.z <= cascade.far)
+    else {
+        return 1.0;
+    };
+
+    let position =
+        position + normal.normalize_or_zero() * settings.normal_bias * cascade.texel_size;
+    filtered_shadow(map, settings, position)
+}
+
+fn point_shadow(
+    lighting: &Lighting,
+    cube: usize,
+    light_position: Vec3,
+    position: Vec3,
+    normal: Vec3,
+) -> f32 {
+    let Some((cube, faces)) = lighting.cubes.get(cube) else {
+        return 1.0;
+    };
+    let settings = &lighting.settings;
+    let from_light = lighting.inverse_view_rotation * (position - light_position);
+    let Some(map) = faces.get(CubeShadow::face(from_light)) else {
+        return 1.0;
+    };
+
+    // the texels grow with the distance from the light
+    let texel_size = cube.texel_scale * from_light.length();
+    let position = position + normal.normalize_or_zero() * settings.normal_bias * texel_size;
+    filtered_shadow(map, settings, position)
+}
+
+// percentage closer filtering around the view space position
+fn filtered_shadow(map: &ShadowMap, settings: &ShadowSettings, position: Vec3) -> f32 {
+    let ndc = map.from_view.project_point3(position);
+    let texel = (ndc.truncate() * 0.5 + 0.5) * map.resolution as f32;
+    // nothing past the far plane of the map casts a shadow there
+    let reference = (ndc.z - settings.depth_bias).min(1.0);
+
+    let radius = settings.pcf_radius as i32;
+    let mut lit = 0.0;
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            lit += compare_bilinear(map, texel + Vec2::new(x as f32, y as f32), reference);
+        }
+    }
+    lit / ((2 * radius + 1) * (2 * radius + 1)) as f32
+}
+
+// the comparison of the four texels around the point, blended like a linear comparing
+// sampler does, everything outside the map is lit
+fn compare_bilinear(map: &ShadowMap, texel: Vec2, reference: f32) -> f32 {
+    let corner = texel - 0.5;
+    let base = corner.floor();
+    let fraction = corner - base;
+
+    let compare = |x: f32, y: f32| {
+        let resolution = map.resolution as f32;
+        if x < 0.0 || y < 0.0 || x >= resolution || y >= resolution {
+            return 1.0;
+        }
+        let depth = map.depth[y as usize * map.resolution as usize + x as usize];
+        if reference <= depth { 1.0 } else { 0.0 }
+    };
+    let top =
+        compare(base.x, base.y) * (1.0 - fraction.x) + compare(base.x + 1.0, base.y) * fraction.x;
+    let bottom = compare(base.x, base.y + 1.0) * (1.0 - fraction.x)
+        + compare(base.x + 1.0, base.y + 1.0) * fraction.x;
+    top * (1.0 - fraction.y) + bottom * fraction.y
+}
+
+// the distance falloff of point and spot lights in fragment.glsl
+fn attenuation(distance: f32) -> f32 {
+    1.0 / (1.0 + 0.09 * distance + 0.032 * distance * distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the default scene lit from the camera, with a cube behind the camera that the
+    // culling has to skip
+    #[test]
+    fn draws_the_default_scene() {
+        let mut scene = Scene::default();
+        scene.lights.insert(Light::Directional(
+            crate::graphics::light::DirectionalLight {
+                direction: Vec3::NEG_Z,
+                color: Vec3::ONE,
+                intensity: 0.4,
+                cast_shadows: false,
+            },
+        ));
+        let mut behind = crate::graphics::primitives::create_cube(Vec3::ONE);
+        behind.world_transform = Mat4::from_translation(Vec3::new(0.0, 0.0, 10.0));
+        scene.add_object("Behind", behind, None).unwrap();
+
+        let mut framebuffer = Framebuffer::new(64, 48);
+        let culling = draw_scene(&mut framebuffer, &scene, &Camera::default());
+        assert_eq!(culling.visible_objects, 1);
+        assert_eq!(culling.culled_objects, 1);
+        assert_eq!(culling.visible_submeshes, 1);
+
+        // the clear values around the sphere
+        assert_eq!(framebuffer.color.pixels[0], [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(framebuffer.depth[0], 1.0);
+
+        // the sphere in the middle is drawn in front of the far plane
+        let center = 24 * 64 + 32;
+        assert_ne!(framebuffer.color.pixels[center], [0.0, 0.0, 0.0, 1.0]);
+        assert!(framebuffer.depth[center] > 0.0 && framebuffer.depth[center] < 1.0);
+    }
+
+    // the reference the vulkan render is compared with as well, set ATOM_UPDATE_GOLDEN to
+    // write the render as the new reference instead
+    #[test]
+    fn default_scene_matches_the_golden_image() {
+        // the default scene lit from the upper right, for the specular highlight
+        let mut scene = Scene::default();
+        scene.lights.insert(Light::Directional(
+            crate::graphics::light::DirectionalLight {
+                direction: Vec3::new(-1.0, -1.0, -1.0),
+                color: Vec3::ONE,
+                intensity: 0.8,
+                cast_shadows: false,
+            },
+        ));
+        let mut framebuffer = Framebuffer::new(160, 90);
+        draw_scene(&mut framebuffer, &scene, &Camera::default());
+
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/golden/default_scene.png"
+        );
+        if std::env::var_os("ATOM_UPDATE_GOLDEN").is_some() {
+            framebuffer.color.save(path).unwrap();
+            return;
+        }
+        let reference = RenderedImage::load(path).unwrap();
+        // only the 8 bits of the png apart
+        let difference = framebuffer
+            .color
+            .difference(&reference, 1.0 / 255.0)
+            .unwrap();
+        assert_eq!(difference.outliers, 0.0, "{}", difference);
+    }
+}
@@ -6,14 +6,20 @@ use vulkano::pipeline::graphics::vertex_input::VertexDefinition;
 use vulkano::sync::GpuFuture;
 
 use crate::graphics::backend::error::VulkanError;
-use crate::graphics::backend::{RenderBackend, RenderContext};
-use crate::graphics::light::GpuLight;
+use crate::graphics::backend::vulkan_scene::{VulkanFrame, VulkanScene};
+use crate::graphics::backend::{GuiIntegration, RenderBackend, RenderContext, WindowBackend};
 
 #[cfg(all(debug_assertions))]
 const ENABLE_VALIDATION_LAYERS: bool = true;
 #[cfg(not(debug_assertions))]
 const ENABLE_VALIDATION_LAYERS: bool = false;
 
+// frames the cpu may record ahead of the gpu, each has its own buffers and fence
+pub const FRAMES_IN_FLIGHT: usize = 2;
+
+// shared so the next frame can chain to it while it is still waited on
+pub type FrameFence = Arc<vulkano::sync::future::FenceSignalFuture<Box<dyn GpuFuture>>>;
+
 pub mod vs {
     vulkano_shaders::shader! {
         ty: "vertex",
@@ -21,6 +27,20 @@ pub mod vs {
     }
 }
 
+pub mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/graphics/shaders/cull.glsl",
+    }
+}
+
+pub mod shadow_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/graphics/shaders/shadow.glsl",
+    }
+}
+
 pub mod fs {
     vulkano_shaders::shader! {
             ty: "fragment",
@@ -46,285 +66,97 @@ pub struct VulkanContext {
     pub swapchain: Arc<vulkano::swapchain::Swapchain>,
     pub pipeline: Arc<vulkano::pipeline::GraphicsPipeline>,
     pub viewport: vulkano::pipeline::graphics::viewport::Viewport,
-    pub previous_frame_end: Option<Box<dyn vulkano::sync::GpuFuture>>,
+    // signalled once the gpu is done with the frame in flight of the same index
+    pub frame_fences: Vec<Option<FrameFence>>,
+    // the frame in flight recorded next
+    pub frame_index: usize,
+    // the scene as uploaded to the gpu for this window
+    pub scene_buffers: VulkanScene,
     pub image_views: Vec<Arc<vulkano::image::view::ImageView>>,
     pub need_recreate_swapchain: bool,
-    pub depth_buffer: Arc<vulkano::image::view::ImageView>,
+    // one per frame in flight, so a frame never clears the depth another one still tests
+    pub depth_buffers: Vec<Arc<vulkano::image::view::ImageView>>,
     pub gui: egui_winit_vulkano::Gui,
 }
-pub struct VulkanScene {
-    pub vertex_buffer: vulkano::buffer::Subbuffer<[crate::graphics::vertex::Vertex]>,
-    pub index_buffer: vulkano::buffer::Subbuffer<[u32]>,
-    pub uniform_buffer: vulkano::buffer::Subbuffer<vs::CameraUbo>,
-    pub matrix_buffer: vulkano::buffer::Subbuffer<[glam::Mat4]>,
-    pub normal_buffer: vulkano::buffer::Subbuffer<[glam::Mat4]>,
-    pub indirect_buffer:
-        vulkano::buffer::Subbuffer<[vulkano::command_buffer::DrawIndexedIndirectCommand]>,
-    pub material_buffer: vulkano::buffer::Subbuffer<[crate::graphics::material::GpuMaterials]>,
-    pub light_buffer: vulkano::buffer::Subbuffer<[crate::graphics::light::GpuLight]>,
-}
+impl VulkanBackend {
+    // a backend without a window system, it can only render into offscreen targets
+    pub fn new_headless() -> Result<Self, VulkanError> {
+        let instance = create_instance(None)?;
 
-impl VulkanScene {
-    pub fn from_scene(
-        scene: &crate::graphics::scene::Scene,
-        memory_allocator: Arc<vulkano::memory::allocator::StandardMemoryAllocator>,
-        aspect_ratio: f32,
-    ) -> Result<Self, crate::graphics::error::GraphicsError> {
-        // create two vec to hold all vertices and indices
-        // we need to merge all meshes in the scene into one vertex buffer and one index buffer
-        let mut all_vertices = Vec::new();
-        let mut all_indices = Vec::new();
-        let mut indirect_commands = Vec::new();
-        let mut instance_id = 0;
-        let mut model_matrices = Vec::new();
-        let mut normal_matrices = Vec::new();
-        let mut materials = Vec::new();
-        let mut lights = Vec::<GpuLight>::new();
-
-        let view_matrix = scene
-            .cameras
-            .get(scene.main_camera_index)
-            .ok_or_else(|| crate::graphics::error::GraphicsError::NoCameraFound)?
-            .view_matrix();
-
-        for light in &scene.lights {
-            lights.push(light.into());
-        }
+        let (virtual_device, queues) = create_virtual_device(Arc::clone(&instance), None)?;
 
-        for mesh in &scene.objects {
-            let model_matrix = mesh.world_transform;
-            let normal_matrix = (view_matrix * mesh.world_transform).inverse().transpose();
+        Ok(Self::from_device(instance, virtual_device, queues))
+    }
 
-            for submesh in &mesh.submeshes {
-                if submesh.vertices.is_empty() || submesh.indices.is_empty() {
-                    continue; // Skip empty submeshes
-                }
+    fn from_device(
+        instance: Arc<vulkano::instance::Instance>,
+        virtual_device: Arc<vulkano::device::Device>,
+        mut queues: impl ExactSizeIterator<Item = Arc<vulkano::device::Queue>>,
+    ) -> Self {
+        let memory_allocator = Arc::new(
+            vulkano::memory::allocator::StandardMemoryAllocator::new_default(
+                virtual_device.clone(),
+            ),
+        );
 
-                model_matrices.push(model_matrix);
-                normal_matrices.push(normal_matrix);
-
-                let first_index = all_indices.len() as u32;
-                let vertex_offset = all_vertices.len() as u32;
-                let index_count = submesh.indices.len() as u32;
-
-                all_vertices.extend_from_slice(&submesh.vertices);
-                all_indices.extend_from_slice(&submesh.indices);
-
-                let command = vulkano::command_buffer::DrawIndexedIndirectCommand {
-                    index_count,
-                    instance_count: 1,
-                    first_index,
-                    vertex_offset,
-                    first_instance: instance_id,
-                };
-                indirect_commands.push(command);
-                materials.push(submesh.material.properties.clone());
-                instance_id += 1;
-            }
-        }
-        let vertex_buffer = vulkano::buffer::Buffer::from_iter(
-            memory_allocator.clone(),
-            vulkano::buffer::BufferCreateInfo {
-                usage: vulkano::buffer::BufferUsage::VERTEX_BUFFER,
-                ..Default::default()
-            },
-            vulkano::memory::allocator::AllocationCreateInfo {
-                memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_DEVICE
-                    | vulkano::memory::allocator::MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            all_vertices.into_iter(),
-        )
-        .map_err(|e| {
-            crate::graphics::backend::error::VulkanError::BufferCreationError(format!(
-                "Failed to create vertex buffer: {}",
-                e
-            ))
-        })?;
+        let command_buffer_allocator = Arc::new(
+            vulkano::command_buffer::allocator::StandardCommandBufferAllocator::new(
+                Arc::clone(&virtual_device),
+                Default::default(),
+            ),
+        );
 
-        let index_buffer = vulkano::buffer::Buffer::from_iter(
-            memory_allocator.clone(),
-            vulkano::buffer::BufferCreateInfo {
-                usage: vulkano::buffer::BufferUsage::INDEX_BUFFER,
-                ..Default::default()
-            },
-            vulkano::memory::allocator::AllocationCreateInfo {
-                memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_DEVICE
-                    | vulkano::memory::allocator::MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            all_indices.into_iter(),
-        )
-        .map_err(|e| {
-            crate::graphics::error::GraphicsError::from(
-                crate::graphics::backend::error::VulkanError::BufferCreationError(format!(
-                    "Failed to create index buffer: {}",
-                    e
-                )),
-            )
-        })?;
+        let descriptor_set_allocator = Arc::new(
+            vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator::new(
+                Arc::clone(&virtual_device),
+                Default::default(),
+            ),
+        );
 
-        let indirect_buffer = vulkano::buffer::Buffer::from_iter(
-            memory_allocator.clone(),
-            vulkano::buffer::BufferCreateInfo {
-                usage: vulkano::buffer::BufferUsage::INDIRECT_BUFFER,
-                ..Default::default()
-            },
-            vulkano::memory::allocator::AllocationCreateInfo {
-                memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_DEVICE
-                    | vulkano::memory::allocator::MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            indirect_commands.into_iter(),
-        )
-        .map_err(|e| {
-            crate::graphics::error::GraphicsError::from(
-                crate::graphics::backend::error::VulkanError::BufferCreationError(format!(
-                    "Failed to create indirect buffer: {}",
-                    e
-                )),
-            )
-        })?;
+        let graphic_queue = queues.next().expect("No queues available");
+        // a family with a single queue does its transfers on the graphics queue
+        let transfer_queue = queues.next().unwrap_or_else(|| graphic_queue.clone());
+        Self {
+            instance,
+            device: virtual_device,
+            graphic_queue,
+            transfer_queue,
+            descriptor_set_allocator,
+            command_buffer_allocator,
+            memory_allocator,
+        }
+    }
 
-        let projection_matrix = scene
-            .cameras
-            .get(scene.main_camera_index)
-            .ok_or_else(|| crate::graphics::error::GraphicsError::NoCameraFound)?
-            .projection_matrix(aspect_ratio);
-
-        let view_matrix = scene
-            .cameras
-            .get(scene.main_camera_index)
-            .ok_or_else(|| crate::graphics::error::GraphicsError::NoCameraFound)?
-            .view_matrix();
-
-        let ubo_data = vs::CameraUbo {
-            proj: projection_matrix.to_cols_array_2d(),
-            view: view_matrix.to_cols_array_2d(),
+    // the scene buffers of one render target, culled on the gpu when the device can
+    pub(crate) fn create_scene_buffers(
+        &self,
+        frames_in_flight: usize,
+    ) -> Result<VulkanScene, VulkanError> {
+        // the groups of the cull shader are only read by indirect draws, which need a first
+        // instance to find their slots, the cpu culls instead
+        let cull_pipeline = if self.device.enabled_features().draw_indirect_first_instance {
+            Some(create_cull_pipeline(self.device.clone())?)
+        } else {
+            println!("Draw indirect first instance not supported, culling on the cpu");
+            None
         };
+        if cull_pipeline.is_some() && !self.device.enabled_features().draw_indirect_count {
+            println!("Draw indirect count not supported, drawing every culled group");
+        }
 
-        let uniform_buffer = vulkano::buffer::Buffer::from_data(
-            memory_allocator.clone(),
-            vulkano::buffer::BufferCreateInfo {
-                usage: vulkano::buffer::BufferUsage::UNIFORM_BUFFER,
-                ..Default::default()
-            },
-            vulkano::memory::allocator::AllocationCreateInfo {
-                memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_DEVICE
-                    | vulkano::memory::allocator::MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            ubo_data,
-        )
-        .map_err(|e| {
-            crate::graphics::error::GraphicsError::from(
-                crate::graphics::backend::error::VulkanError::BufferCreationError(format!(
-                    "Failed to create uniform buffer: {}",
-                    e
-                )),
-            )
-        })?;
-
-        let matrix_buffer = vulkano::buffer::Buffer::from_iter(
-            memory_allocator.clone(),
-            vulkano::buffer::BufferCreateInfo {
-                usage: vulkano::buffer::BufferUsage::STORAGE_BUFFER,
-                ..Default::default()
-            },
-            vulkano::memory::allocator::AllocationCreateInfo {
-                memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_DEVICE
-                    | vulkano::memory::allocator::MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            model_matrices.into_iter(),
-        )
-        .map_err(|e| {
-            crate::graphics::error::GraphicsError::from(
-                crate::graphics::backend::error::VulkanError::BufferCreationError(format!(
-                    "Failed to create matrix buffer: {}",
-                    e
-                )),
-            )
-        })?;
-
-        let material_buffer = vulkano::buffer::Buffer::from_iter(
-            memory_allocator.clone(),
-            vulkano::buffer::BufferCreateInfo {
-                usage: vulkano::buffer::BufferUsage::STORAGE_BUFFER,
-                ..Default::default()
-            },
-            vulkano::memory::allocator::AllocationCreateInfo {
-                memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_DEVICE
-                    | vulkano::memory::allocator::MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            materials.into_iter(),
-        )
-        .map_err(|e| {
-            crate::graphics::error::GraphicsError::from(
-                crate::graphics::backend::error::VulkanError::BufferCreationError(format!(
-                    "Failed to create material buffer: {}",
-                    e
-                )),
-            )
-        })?;
-
-        let normal_buffer = vulkano::buffer::Buffer::from_iter(
-            memory_allocator.clone(),
-            vulkano::buffer::BufferCreateInfo {
-                usage: vulkano::buffer::BufferUsage::STORAGE_BUFFER,
-                ..Default::default()
-            },
-            vulkano::memory::allocator::AllocationCreateInfo {
-                memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_DEVICE
-                    | vulkano::memory::allocator::MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            normal_matrices.into_iter(),
-        )
-        .map_err(|e| {
-            crate::graphics::error::GraphicsError::from(
-                crate::graphics::backend::error::VulkanError::BufferCreationError(format!(
-                    "Failed to create normal buffer: {}",
-                    e
-                )),
-            )
-        })?;
-
-        let light_buffer = vulkano::buffer::Buffer::from_iter(
-            memory_allocator.clone(),
-            vulkano::buffer::BufferCreateInfo {
-                usage: vulkano::buffer::BufferUsage::STORAGE_BUFFER,
-                ..Default::default()
-            },
-            vulkano::memory::allocator::AllocationCreateInfo {
-                memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_DEVICE
-                    | vulkano::memory::allocator::MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            lights.into_iter(),
-        )
-        .map_err(|e| {
-            crate::graphics::error::GraphicsError::from(
-                crate::graphics::backend::error::VulkanError::BufferCreationError(format!(
-                    "Failed to create light buffer: {}",
-                    e
-                )),
-            )
-        })?;
-        Ok(Self {
-            vertex_buffer,
-            index_buffer,
-            uniform_buffer,
-            indirect_buffer,
-            matrix_buffer,
-            material_buffer,
-            normal_buffer,
-            light_buffer,
-        })
+        Ok(VulkanScene::new(
+            self.memory_allocator.clone(),
+            self.command_buffer_allocator.clone(),
+            self.descriptor_set_allocator.clone(),
+            &self.graphic_queue,
+            self.transfer_queue.clone(),
+            cull_pipeline,
+            create_shadow_pipeline(self.device.clone())?,
+            create_shadow_sampler(self.device.clone())?,
+            frames_in_flight,
+        ))
     }
-}
-impl VulkanBackend {
+
     pub fn recreate_swapchain(
         &self,
         render_context: &mut VulkanContext,
@@ -361,52 +193,50 @@ impl VulkanBackend {
 
         render_context.viewport.extent = new_window_size.into();
 
-        render_context.depth_buffer = create_depth_buffer(
-            self.memory_allocator.clone(),
-            [new_window_size.width, new_window_size.height],
-        )?;
+        render_context.depth_buffers = (0..FRAMES_IN_FLIGHT)
+            .map(|_| {
+                create_depth_buffer(
+                    self.memory_allocator.clone(),
+                    [new_window_size.width, new_window_size.height],
+                )
+            })
+            .collect::<Result<_, _>>()?;
         Ok(())
     }
 }
 
 impl RenderBackend for VulkanBackend {
-    type Context = VulkanContext;
     type Error = crate::graphics::backend::error::VulkanError;
-    fn new(event_loop: &winit::event_loop::EventLoop<()>) -> Result<Self, Self::Error> {
-        let instance = create_instance(event_loop)?;
 
-        let (virtual_device, mut queues) =
-            create_virtual_device(Arc::clone(&instance), event_loop)?;
+    fn new_headless() -> Result<Self, Self::Error> {
+        VulkanBackend::new_headless()
+    }
 
-        let memory_allocator = Arc::new(
-            vulkano::memory::allocator::StandardMemoryAllocator::new_default(
-                virtual_device.clone(),
-            ),
-        );
+    // a new target every time, renders that repeat keep an `OffscreenTarget` and call
+    // `render_offscreen` instead
+    fn render_image(
+        &mut self,
+        scene: &crate::graphics::scene::Scene,
+        camera: &crate::graphics::camera::Camera,
+        width: u32,
+        height: u32,
+    ) -> Result<crate::graphics::rendered_image::RenderedImage, crate::graphics::error::GraphicsError>
+    {
+        let mut target = self.create_offscreen_target(width, height)?;
+        self.render_offscreen(&mut target, scene, camera)
+    }
+}
 
-        let command_buffer_allocator = Arc::new(
-            vulkano::command_buffer::allocator::StandardCommandBufferAllocator::new(
-                Arc::clone(&virtual_device),
-                Default::default(),
-            ),
-        );
+impl WindowBackend for VulkanBackend {
+    type Context = VulkanContext;
 
-        let descriptor_set_allocator = Arc::new(
-            vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator::new(
-                Arc::clone(&virtual_device),
-                Default::default(),
-            ),
-        );
+    fn new(event_loop: &winit::event_loop::EventLoop<()>) -> Result<Self, Self::Error> {
+        let instance = create_instance(Some(event_loop))?;
 
-        Ok(Self {
-            instance,
-            device: virtual_device,
-            graphic_queue: queues.next().expect("No queues available"),
-            transfer_queue: queues.next().expect("No queues available"),
-            descriptor_set_allocator,
-            command_buffer_allocator,
-            memory_allocator,
-        })
+        let (virtual_device, queues) =
+            create_virtual_device(Arc::clone(&instance), Some(event_loop))?;
+
+        Ok(Self::from_device(instance, virtual_device, queues))
     }
 
     fn create_window_context(
@@ -495,133 +325,7 @@ impl RenderBackend for VulkanBackend {
             })
             .collect::<Vec<_>>();
 
-        let pipeline = {
-            let vs = vs::load(self.device.clone())
-                .map_err(|e| {
-                    crate::graphics::backend::error::VulkanError::ShaderCompilationError(format!(
-                        "Failed to load vertex shader: {}",
-                        e
-                    ))
-                })?
-                .entry_point("main")
-                .ok_or_else(|| {
-                    crate::graphics::backend::error::VulkanError::ShaderCompilationError(
-                        "Vertex shader entry point 'main' not found".to_string(),
-                    )
-                })?;
-
-            let fs = fs::load(self.device.clone())
-                .map_err(|e| {
-                    crate::graphics::backend::error::VulkanError::ShaderCompilationError(format!(
-                        "Failed to load fragment shader: {}",
-                        e
-                    ))
-                })?
-                .entry_point("main")
-                .ok_or_else(|| {
-                    crate::graphics::backend::error::VulkanError::ShaderCompilationError(
-                        "Fragment shader entry point 'main' not found".to_string(),
-                    )
-                })?;
-
-            let vertex_input_state = crate::graphics::vertex::Vertex::per_vertex()
-                .definition(&vs)
-                .map_err(|e| {
-                    crate::graphics::backend::error::VulkanError::ShaderCompilationError(format!(
-                        "Failed to define vertex input: {}",
-                        e
-                    ))
-                })?;
-
-            let stages = [
-                vulkano::pipeline::PipelineShaderStageCreateInfo::new(vs),
-                vulkano::pipeline::PipelineShaderStageCreateInfo::new(fs),
-            ];
-
-            let pipeline_layout_create_info =
-                vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo::from_stages(
-                    &stages,
-                )
-                .into_pipeline_layout_create_info(self.device.clone())
-                .map_err(|e| {
-                    crate::graphics::backend::error::VulkanError::PipelineLayoutError(format!(
-                        "Failed to create pipeline layout: {}",
-                        e
-                    ))
-                })?;
-
-            println!(
-                "Pipeline layout create info: {:?}",
-                pipeline_layout_create_info
-            );
-            let layout = vulkano::pipeline::layout::PipelineLayout::new(
-                self.device.clone(),
-                pipeline_layout_create_info,
-            )
-            .map_err(|e| {
-                crate::graphics::backend::error::VulkanError::PipelineLayoutError(format!(
-                    "Failed to create pipeline layout: {}",
-                    e
-                ))
-            })?;
-            let subpass = vulkano::pipeline::graphics::subpass::PipelineRenderingCreateInfo {
-                color_attachment_formats: [Some(swapchain.image_format())].to_vec(),
-                depth_attachment_format: Some(vulkano::format::Format::D32_SFLOAT),
-                ..Default::default()
-            };
-
-            vulkano::pipeline::graphics::GraphicsPipeline::new(
-                self.device.clone(),
-                None,
-                vulkano::pipeline::graphics::GraphicsPipelineCreateInfo {
-                    stages: stages.to_vec().into(),
-                    vertex_input_state: Some(vertex_input_state),
-                    input_assembly_state: Some(
-                        vulkano::pipeline::graphics::input_assembly::InputAssemblyState::default(),
-                    ),
-                    viewport_state: Some(
-                        vulkano::pipeline::graphics::viewport::ViewportState::default(),
-                    ),
-                    rasterization_state: Some(
-                        vulkano::pipeline::graphics::rasterization::RasterizationState {
-                            cull_mode: vulkano::pipeline::graphics::rasterization::CullMode::Back,
-                            front_face:
-                                vulkano::pipeline::graphics::rasterization::FrontFace::Clockwise,
-                            ..Default::default()
-                        },
-                    ),
-                    multisample_state: Some(
-                        vulkano::pipeline::graphics::multisample::MultisampleState::default(),
-                    ),
-                    color_blend_state: Some(
-                        vulkano::pipeline::graphics::color_blend::ColorBlendState {
-                            attachments: [vulkano::pipeline::graphics::color_blend::ColorBlendAttachmentState::default()].to_vec(),
-                            ..Default::default()
-                        },
-                    ),
-
-                    depth_stencil_state: Some(
-                        vulkano::pipeline::graphics::depth_stencil::DepthStencilState {
-                            depth: Some(
-                                vulkano::pipeline::graphics::depth_stencil::DepthState::simple(),
-                            ),
-                            ..Default::default()
-                        },
-                    ),
-
-                    dynamic_state: [vulkano::pipeline::DynamicState::Viewport].into_iter().collect(),
-                    subpass: Some(subpass.into()),
-
-                    ..vulkano::pipeline::graphics::GraphicsPipelineCreateInfo::layout(layout)
-                },
-            )
-            .map_err(|e| {
-                crate::graphics::backend::error::VulkanError::PipelineCreationError(format!(
-                    "Failed to create graphics pipeline: {}",
-                    e
-                ))
-            })
-        }?;
+        let pipeline = create_graphics_pipeline(self.device.clone(), swapchain.image_format())?;
 
         let viewport = vulkano::pipeline::graphics::viewport::Viewport {
             offset: [0.0, 0.0],
@@ -629,14 +333,16 @@ impl RenderBackend for VulkanBackend {
             ..Default::default()
         };
 
-        let previous_frame_end = Some(vulkano::sync::GpuFuture::boxed(vulkano::sync::now(
-            self.device.clone(),
-        )));
+        let scene_buffers = self.create_scene_buffers(FRAMES_IN_FLIGHT)?;
 
-        let depth_buffer = create_depth_buffer(
-            self.memory_allocator.clone(),
-            [window_size.width, window_size.height],
-        )?;
+        let depth_buffers = (0..FRAMES_IN_FLIGHT)
+            .map(|_| {
+                create_depth_buffer(
+                    self.memory_allocator.clone(),
+                    [window_size.width, window_size.height],
+                )
+            })
+            .collect::<Result<_, _>>()?;
 
         let gui = egui_winit_vulkano::Gui::new(
             event_loop,
@@ -659,10 +365,12 @@ impl RenderBackend for VulkanBackend {
             window: window.clone(),
             viewport,
             swapchain,
-            depth_buffer,
+            depth_buffers,
             pipeline,
             need_recreate_swapchain: false,
-            previous_frame_end,
+            frame_fences: (0..FRAMES_IN_FLIGHT).map(|_| None).collect(),
+            frame_index: 0,
+            scene_buffers,
             image_views,
             gui,
         })
@@ -671,9 +379,8 @@ impl RenderBackend for VulkanBackend {
     fn draw_frame(
         &mut self,
         context: &mut Self::Context,
-        gui_callback: impl FnOnce(&mut egui_winit_vulkano::egui::Context),
         scene: &crate::graphics::scene::Scene,
-    ) -> Result<(), crate::graphics::error::GraphicsError> {
+    ) -> Result<crate::graphics::frustum::CullingStats, crate::graphics::error::GraphicsError> {
         if context.need_recreate_swapchain {
             self.recreate_swapchain(context).map_err(|e| {
                 crate::graphics::backend::error::VulkanError::SwapchainError(format!(
@@ -690,7 +397,7 @@ impl RenderBackend for VulkanBackend {
                 Ok(result) => result,
                 Err(vulkano::VulkanError::OutOfDate) => {
                     context.need_recreate_swapchain = true;
-                    return Ok(());
+                    return Ok(crate::graphics::frustum::CullingStats::default());
                 }
                 Err(e) => {
                     return Err(
@@ -712,59 +419,17 @@ impl RenderBackend for VulkanBackend {
             extent.width as f32 / extent.height as f32
         };
 
-        let renderable_scene =
-            VulkanScene::from_scene(scene, self.memory_allocator.clone(), aspect_ratio).map_err(
-                |e| {
-                    crate::graphics::backend::error::VulkanError::SceneError(format!(
-                        "Failed to create renderable scene: {}",
-                        e
-                    ))
-                },
-            )?;
+        // the buffers of this frame in flight are free again once its last use is done
+        let frame_index = context.frame_index;
+        if let Some(fence) = context.frame_fences[frame_index].take() {
+            fence.wait(None).map_err(|e| {
+                crate::graphics::backend::error::VulkanError::SynchronizationError(format!(
+                    "Failed to wait for frame in flight: {}",
+                    e
+                ))
+            })?;
+        }
 
-        let descriptor_set = vulkano::descriptor_set::DescriptorSet::new(
-            self.descriptor_set_allocator.clone(),
-            context
-                .pipeline
-                .layout()
-                .set_layouts()
-                .get(0)
-                .ok_or_else(|| {
-                    crate::graphics::backend::error::VulkanError::PipelineLayoutError(
-                        "No descriptor set layout found in pipeline".to_string(),
-                    )
-                })?
-                .clone(),
-            [
-                vulkano::descriptor_set::WriteDescriptorSet::buffer(
-                    0,
-                    renderable_scene.uniform_buffer.clone(),
-                ),
-                vulkano::descriptor_set::WriteDescriptorSet::buffer(
-                    1,
-                    renderable_scene.matrix_buffer.clone(),
-                ),
-                vulkano::descriptor_set::WriteDescriptorSet::buffer(
-                    2,
-                    renderable_scene.material_buffer.clone(),
-                ),
-                vulkano::descriptor_set::WriteDescriptorSet::buffer(
-                    3,
-                    renderable_scene.normal_buffer.clone(),
-                ),
-                vulkano::descriptor_set::WriteDescriptorSet::buffer(
-                    4,
-                    renderable_scene.light_buffer.clone(),
-                ),
-            ],
-            [],
-        )
-        .map_err(|e| {
-            crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
-                "Failed to create descriptor set: {}",
-                e
-            ))
-        })?;
         let mut builder = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
             self.command_buffer_allocator.clone(),
             self.graphic_queue.queue_family_index(),
@@ -777,94 +442,46 @@ impl RenderBackend for VulkanBackend {
             ))
         })?;
 
-        builder
-            .begin_rendering(vulkano::command_buffer::RenderingInfo {
-                color_attachments: vec![Some(vulkano::command_buffer::RenderingAttachmentInfo {
-                    load_op: vulkano::render_pass::AttachmentLoadOp::Clear,
-                    store_op: vulkano::render_pass::AttachmentStoreOp::Store,
-                    clear_value: Some([0.0, 0.0, 0.0, 1.0].into()),
-                    ..vulkano::command_buffer::RenderingAttachmentInfo::image_view(
-                        context.image_views[image_index as usize].clone(),
-                    )
-                })],
-                depth_attachment: Some(vulkano::command_buffer::RenderingAttachmentInfo {
-                    load_op: vulkano::render_pass::AttachmentLoadOp::Clear,
-                    store_op: vulkano::render_pass::AttachmentStoreOp::Store,
-                    clear_value: Some(1.0f32.into()),
-                    ..vulkano::command_buffer::RenderingAttachmentInfo::image_view(
-                        context.depth_buffer.clone(),
-                    )
-                }),
-                ..Default::default()
-            })
-            .map_err(|e| {
-                crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
-                    "Failed to begin rendering: {}",
-                    e
-                ))
-            })?
-            .set_viewport(0, vec![context.viewport.clone()].into_iter().collect())
-            .map_err(|e| {
-                crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
-                    "Failed to set viewport: {}",
-                    e
-                ))
-            })?
-            .bind_pipeline_graphics(context.pipeline.clone())
-            .map_err(|e| {
-                crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
-                    "Failed to bind graphics pipeline: {}",
-                    e
-                ))
-            })?
-            .bind_descriptor_sets(
-                vulkano::pipeline::PipelineBindPoint::Graphics,
-                context.pipeline.layout().clone(),
-                0,
-                descriptor_set,
+        // records the patches of the frame's buffers ahead of the rendering
+        let frame = context
+            .scene_buffers
+            .update(
+                scene,
+                scene
+                    .main_camera()
+                    .ok_or(crate::graphics::error::GraphicsError::NoCameraFound)?,
+                &mut builder,
+                aspect_ratio,
+                frame_index,
+                context
+                    .pipeline
+                    .layout()
+                    .set_layouts()
+                    .first()
+                    .ok_or_else(|| {
+                        crate::graphics::backend::error::VulkanError::PipelineLayoutError(
+                            "No descriptor set layout found in pipeline".to_string(),
+                        )
+                    })?
+                    .clone(),
             )
             .map_err(|e| {
-                crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
-                    "Failed to bind descriptor sets: {}",
-                    e
-                ))
-            })?
-            .bind_vertex_buffers(0, renderable_scene.vertex_buffer)
-            .map_err(|e| {
-                crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
-                    "Failed to bind vertex buffers: {}",
-                    e
-                ))
-            })?
-            .bind_index_buffer(renderable_scene.index_buffer)
-            .map_err(|e| {
-                crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
-                    "Failed to bind index buffer: {}",
+                crate::graphics::backend::error::VulkanError::SceneError(format!(
+                    "Failed to update renderable scene: {}",
                     e
                 ))
             })?;
 
-        unsafe {
-            builder
-                .draw_indexed_indirect(renderable_scene.indirect_buffer)
-                .map_err(|e| {
-                    crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
-                        "Failed to draw indexed indirect: {}",
-                        e
-                    ))
-                })?;
-        }
-
-        context.gui.immediate_ui(|gui| {
-            gui_callback(&mut gui.context());
-        });
-
-        builder.end_rendering().map_err(|e| {
-            crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
-                "Failed to end rendering: {}",
-                e
-            ))
-        })?;
+        let culling = frame.culling;
+        record_scene(
+            &mut builder,
+            &context.pipeline,
+            context.viewport.clone(),
+            context.image_views[image_index as usize].clone(),
+            context.depth_buffers[frame_index].clone(),
+            &context.scene_buffers,
+            frame,
+        )?;
 
         let command_buffer = builder.build().map_err(|e| {
             crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
@@ -873,10 +490,13 @@ impl RenderBackend for VulkanBackend {
             ))
         })?;
 
-        let scene_future = context
-            .previous_frame_end
-            .take()
-            .unwrap_or_else(|| vulkano::sync::now(self.graphic_queue.device().clone()).boxed())
+        // chained to the frame recorded before, which may still be on the gpu
+        let previous_frame = (frame_index + FRAMES_IN_FLIGHT - 1) % FRAMES_IN_FLIGHT;
+        let previous_future = match context.frame_fences[previous_frame].clone() {
+            Some(fence) => fence.boxed(),
+            None => vulkano::sync::now(self.graphic_queue.device().clone()).boxed(),
+        };
+        let scene_future = previous_future
             .join(acquire_future)
             .then_execute(self.graphic_queue.clone(), command_buffer)
             .map_err(|e| {
@@ -891,7 +511,7 @@ impl RenderBackend for VulkanBackend {
             context.image_views[image_index as usize].clone(),
         );
 
-        let _ = gui_future
+        let frame_end = gui_future
             .then_swapchain_present(
                 self.graphic_queue.clone(),
                 vulkano::swapchain::SwapchainPresentInfo::swapchain_image_index(
@@ -899,20 +519,38 @@ impl RenderBackend for VulkanBackend {
                     image_index,
                 ),
             )
+            .boxed()
             .then_signal_fence_and_flush()
-            .map_err(|e| {
-                crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
-                    "Failed to flush command buffer: {}",
-                    e
-                ))
-            })?
-            .cleanup_finished();
+            .map_err(vulkano::Validated::unwrap);
+        // the cpu goes on with the next frame without waiting for this one, the fence
+        // never leaves the render thread, the arc only lets vulkano chain frames to it
+        #[allow(clippy::arc_with_non_send_sync)]
+        let frame_fence = match frame_end {
+            Ok(fence) => Some(Arc::new(fence)),
+            Err(vulkano::VulkanError::OutOfDate) => {
+                context.need_recreate_swapchain = true;
+                None
+            }
+            Err(e) => {
+                return Err(
+                    crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
+                        "Failed to flush command buffer: {}",
+                        e
+                    ))
+                    .into(),
+                );
+            }
+        };
+        context.frame_fences[frame_index] = frame_fence;
+        context.frame_index = (frame_index + 1) % FRAMES_IN_FLIGHT;
 
-        Ok(())
+        Ok(culling)
     }
 }
 
 impl RenderContext for VulkanContext {
+    type Gui = egui_winit_vulkano::Gui;
+
     fn resize(&mut self) -> Result<(), crate::graphics::error::GraphicsError> {
         let size = self.window.inner_size();
         self.need_recreate_swapchain = true;
@@ -921,17 +559,433 @@ impl RenderContext for VulkanContext {
         Ok(())
     }
 
-    fn gui_update(&mut self, winit_event: &winit::event::WindowEvent) -> bool {
-        self.gui.update(&winit_event)
-    }
     fn window(&self) -> Arc<winit::window::Window> {
         self.window.clone()
     }
+
+    fn gui(&mut self) -> &mut Self::Gui {
+        &mut self.gui
+    }
 }
 
 impl VulkanContext {}
 
-fn create_depth_buffer(
+impl GuiIntegration for egui_winit_vulkano::Gui {
+    fn handle_event(&mut self, event: &winit::event::WindowEvent) -> bool {
+        self.update(event)
+    }
+
+    fn build(&mut self, ui: impl FnOnce(&egui::Context)) {
+        self.immediate_ui(|gui| ui(&gui.context()));
+    }
+}
+
+// records the drawing of the scene into the attachments, between the scene buffer
+// patches and whatever is drawn on top
+pub(crate) fn record_scene(
+    builder: &mut vulkano::command_buffer::AutoCommandBufferBuilder<
+        vulkano::command_buffer::PrimaryAutoCommandBuffer,
+    >,
+    pipeline: &Arc<vulkano::pipeline::GraphicsPipeline>,
+    viewport: vulkano::pipeline::graphics::viewport::Viewport,
+    color_attachment: Arc<vulkano::image::view::ImageView>,
+    depth_attachment: Arc<vulkano::image::view::ImageView>,
+    scene_buffers: &VulkanScene,
+    frame: VulkanFrame,
+) -> Result<(), VulkanError> {
+    scene_buffers.record_shadows(builder, &frame)?;
+
+    builder
+        .begin_rendering(vulkano::command_buffer::RenderingInfo {
+            color_attachments: vec![Some(vulkano::command_buffer::RenderingAttachmentInfo {
+                load_op: vulkano::render_pass::AttachmentLoadOp::Clear,
+                store_op: vulkano::render_pass::AttachmentStoreOp::Store,
+                clear_value: Some([0.0, 0.0, 0.0, 1.0].into()),
+                ..vulkano::command_buffer::RenderingAttachmentInfo::image_view(color_attachment)
+            })],
+            depth_attachment: Some(vulkano::command_buffer::RenderingAttachmentInfo {
+                load_op: vulkano::render_pass::AttachmentLoadOp::Clear,
+                store_op: vulkano::render_pass::AttachmentStoreOp::Store,
+                clear_value: Some(1.0f32.into()),
+                ..vulkano::command_buffer::RenderingAttachmentInfo::image_view(depth_attachment)
+            }),
+            ..Default::default()
+        })
+        .map_err(|e| {
+            crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
+                "Failed to begin rendering: {}",
+                e
+            ))
+        })?
+        .set_viewport(0, vec![viewport].into_iter().collect())
+        .map_err(|e| {
+            crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
+                "Failed to set viewport: {}",
+                e
+            ))
+        })?
+        .bind_pipeline_graphics(pipeline.clone())
+        .map_err(|e| {
+            crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
+                "Failed to bind graphics pipeline: {}",
+                e
+            ))
+        })?
+        .bind_descriptor_sets(
+            vulkano::pipeline::PipelineBindPoint::Graphics,
+            pipeline.layout().clone(),
+            0,
+            frame.descriptor_set,
+        )
+        .map_err(|e| {
+            crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
+                "Failed to bind descriptor sets: {}",
+                e
+            ))
+        })?;
+
+    // nothing to draw leaves the buffers unset
+    if let (Some(draws), Some(vertex_buffer), Some(index_buffer)) = (
+        frame.draws,
+        scene_buffers.vertex_buffer.clone(),
+        scene_buffers.index_buffer.clone(),
+    ) {
+        builder
+            .bind_vertex_buffers(0, vertex_buffer)
+            .map_err(|e| {
+                crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
+                    "Failed to bind vertex buffers: {}",
+                    e
+                ))
+            })?
+            .bind_index_buffer(index_buffer)
+            .map_err(|e| {
+                crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
+                    "Failed to bind index buffer: {}",
+                    e
+                ))
+            })?;
+
+        scene_buffers.draw_instanced(builder, &draws)?;
+    }
+
+    builder.end_rendering().map_err(|e| {
+        crate::graphics::backend::error::VulkanError::CommandBufferError(format!(
+            "Failed to end rendering: {}",
+            e
+        ))
+    })?;
+    Ok(())
+}
+
+// draws the scene into color attachments of the given format
+pub(crate) fn create_graphics_pipeline(
+    device: Arc<vulkano::device::Device>,
+    image_format: vulkano::format::Format,
+) -> Result<Arc<vulkano::pipeline::GraphicsPipeline>, VulkanError> {
+    let vs = vs::load(device.clone())
+        .map_err(|e| {
+            crate::graphics::backend::error::VulkanError::ShaderCompilationError(format!(
+                "Failed to load vertex shader: {}",
+                e
+            ))
+        })?
+        .entry_point("main")
+        .ok_or_else(|| {
+            crate::graphics::backend::error::VulkanError::ShaderCompilationError(
+                "Vertex shader entry point 'main' not found".to_string(),
+            )
+        })?;
+
+    let fs = fs::load(device.clone())
+        .map_err(|e| {
+            crate::graphics::backend::error::VulkanError::ShaderCompilationError(format!(
+                "Failed to load fragment shader: {}",
+                e
+            ))
+        })?
+        .entry_point("main")
+        .ok_or_else(|| {
+            crate::graphics::backend::error::VulkanError::ShaderCompilationError(
+                "Fragment shader entry point 'main' not found".to_string(),
+            )
+        })?;
+
+    let vertex_input_state = crate::graphics::vertex::Vertex::per_vertex()
+        .definition(&vs)
+        .map_err(|e| {
+            crate::graphics::backend::error::VulkanError::ShaderCompilationError(format!(
+                "Failed to define vertex input: {}",
+                e
+            ))
+        })?;
+
+    let stages = [
+        vulkano::pipeline::PipelineShaderStageCreateInfo::new(vs),
+        vulkano::pipeline::PipelineShaderStageCreateInfo::new(fs),
+    ];
+
+    let pipeline_layout_create_info =
+        vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
+            .into_pipeline_layout_create_info(device.clone())
+            .map_err(|e| {
+                crate::graphics::backend::error::VulkanError::PipelineLayoutError(format!(
+                    "Failed to create pipeline layout: {}",
+                    e
+                ))
+            })?;
+
+    println!(
+        "Pipeline layout create info: {:?}",
+        pipeline_layout_create_info
+    );
+    let layout =
+        vulkano::pipeline::layout::PipelineLayout::new(device.clone(), pipeline_layout_create_info)
+            .map_err(|e| {
+                crate::graphics::backend::error::VulkanError::PipelineLayoutError(format!(
+                    "Failed to create pipeline layout: {}",
+                    e
+                ))
+            })?;
+    let subpass = vulkano::pipeline::graphics::subpass::PipelineRenderingCreateInfo {
+        color_attachment_formats: [Some(image_format)].to_vec(),
+        depth_attachment_format: Some(vulkano::format::Format::D32_SFLOAT),
+        ..Default::default()
+    };
+
+    vulkano::pipeline::graphics::GraphicsPipeline::new(
+        device.clone(),
+        None,
+        vulkano::pipeline::graphics::GraphicsPipelineCreateInfo {
+            stages: stages.to_vec().into(),
+            vertex_input_state: Some(vertex_input_state),
+            input_assembly_state: Some(
+                vulkano::pipeline::graphics::input_assembly::InputAssemblyState::default(),
+            ),
+            viewport_state: Some(vulkano::pipeline::graphics::viewport::ViewportState::default()),
+            rasterization_state: Some(
+                vulkano::pipeline::graphics::rasterization::RasterizationState {
+                    cull_mode: vulkano::pipeline::graphics::rasterization::CullMode::Back,
+                    front_face: vulkano::pipeline::graphics::rasterization::FrontFace::Clockwise,
+                    ..Default::default()
+                },
+            ),
+            multisample_state: Some(
+                vulkano::pipeline::graphics::multisample::MultisampleState::default(),
+            ),
+            color_blend_state: Some(vulkano::pipeline::graphics::color_blend::ColorBlendState {
+                attachments: [
+                    vulkano::pipeline::graphics::color_blend::ColorBlendAttachmentState::default(),
+                ]
+                .to_vec(),
+                ..Default::default()
+            }),
+
+            depth_stencil_state: Some(
+                vulkano::pipeline::graphics::depth_stencil::DepthStencilState {
+                    depth: Some(vulkano::pipeline::graphics::depth_stencil::DepthState::simple()),
+                    ..Default::default()
+                },
+            ),
+
+            dynamic_state: [vulkano::pipeline::DynamicState::Viewport]
+                .into_iter()
+                .collect(),
+            subpass: Some(subpass.into()),
+
+            ..vulkano::pipeline::graphics::GraphicsPipelineCreateInfo::layout(layout)
+        },
+    )
+    .map_err(|e| {
+        crate::graphics::backend::error::VulkanError::PipelineCreationError(format!(
+            "Failed to create graphics pipeline: {}",
+            e
+        ))
+    })
+}
+
+fn create_cull_pipeline(
+    device: Arc<vulkano::device::Device>,
+) -> Result<Arc<vulkano::pipeline::ComputePipeline>, crate::graphics::backend::error::VulkanError> {
+    let cs = cs::load(device.clone())
+        .map_err(|e| {
+            crate::graphics::backend::error::VulkanError::ShaderCompilationError(format!(
+                "Failed to load cull shader: {}",
+                e
+            ))
+        })?
+        .entry_point("main")
+        .ok_or_else(|| {
+            crate::graphics::backend::error::VulkanError::ShaderCompilationError(
+                "Cull shader entry point 'main' not found".to_string(),
+            )
+        })?;
+    let stage = vulkano::pipeline::PipelineShaderStageCreateInfo::new(cs);
+
+    let layout = vulkano::pipeline::layout::PipelineLayout::new(
+        device.clone(),
+        vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+            .into_pipeline_layout_create_info(device.clone())
+            .map_err(|e| {
+                crate::graphics::backend::error::VulkanError::PipelineLayoutError(format!(
+                    "Failed to create cull pipeline layout: {}",
+                    e
+                ))
+            })?,
+    )
+    .map_err(|e| {
+        crate::graphics::backend::error::VulkanError::PipelineLayoutError(format!(
+            "Failed to create cull pipeline layout: {}",
+            e
+        ))
+    })?;
+
+    vulkano::pipeline::ComputePipeline::new(
+        device,
+        None,
+        vulkano::pipeline::compute::ComputePipelineCreateInfo::stage_layout(stage, layout),
+    )
+    .map_err(|e| {
+        crate::graphics::backend::error::VulkanError::PipelineCreationError(format!(
+            "Failed to create cull pipeline: {}",
+            e
+        ))
+    })
+}
+
+// draws only the depth of the shadow casters, as seen from a light
+fn create_shadow_pipeline(
+    device: Arc<vulkano::device::Device>,
+) -> Result<Arc<vulkano::pipeline::GraphicsPipeline>, crate::graphics::backend::error::VulkanError>
+{
+    let vs = shadow_vs::load(device.clone())
+        .map_err(|e| {
+            crate::graphics::backend::error::VulkanError::ShaderCompilationError(format!(
+                "Failed to load shadow shader: {}",
+                e
+            ))
+        })?
+        .entry_point("main")
+        .ok_or_else(|| {
+            crate::graphics::backend::error::VulkanError::ShaderCompilationError(
+                "Shadow shader entry point 'main' not found".to_string(),
+            )
+        })?;
+
+    let vertex_input_state = crate::graphics::vertex::Vertex::per_vertex()
+        .definition(&vs)
+        .map_err(|e| {
+            crate::graphics::backend::error::VulkanError::ShaderCompilationError(format!(
+                "Failed to define shadow vertex input: {}",
+                e
+            ))
+        })?;
+    let stage = vulkano::pipeline::PipelineShaderStageCreateInfo::new(vs);
+
+    let layout = vulkano::pipeline::layout::PipelineLayout::new(
+        device.clone(),
+        vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+            .into_pipeline_layout_create_info(device.clone())
+            .map_err(|e| {
+                crate::graphics::backend::error::VulkanError::PipelineLayoutError(format!(
+                    "Failed to create shadow pipeline layout: {}",
+                    e
+                ))
+            })?,
+    )
+    .map_err(|e| {
+        crate::graphics::backend::error::VulkanError::PipelineLayoutError(format!(
+            "Failed to create shadow pipeline layout: {}",
+            e
+        ))
+    })?;
+    let subpass = vulkano::pipeline::graphics::subpass::PipelineRenderingCreateInfo {
+        depth_attachment_format: Some(vulkano::format::Format::D32_SFLOAT),
+        ..Default::default()
+    };
+
+    vulkano::pipeline::graphics::GraphicsPipeline::new(
+        device.clone(),
+        None,
+        vulkano::pipeline::graphics::GraphicsPipelineCreateInfo {
+            stages: [stage].into_iter().collect(),
+            vertex_input_state: Some(vertex_input_state),
+            input_assembly_state: Some(
+                vulkano::pipeline::graphics::input_assembly::InputAssemblyState::default(),
+            ),
+            viewport_state: Some(vulkano::pipeline::graphics::viewport::ViewportState::default()),
+            // both sides cast shadows, open meshes would let the light through otherwise
+            rasterization_state: Some(
+                vulkano::pipeline::graphics::rasterization::RasterizationState::default(),
+            ),
+            multisample_state: Some(
+                vulkano::pipeline::graphics::multisample::MultisampleState::default(),
+            ),
+            depth_stencil_state: Some(
+                vulkano::pipeline::graphics::depth_stencil::DepthStencilState {
+                    depth: Some(vulkano::pipeline::graphics::depth_stencil::DepthState::simple()),
+                    ..Default::default()
+                },
+            ),
+            dynamic_state: [vulkano::pipeline::DynamicState::Viewport]
+                .into_iter()
+                .collect(),
+            subpass: Some(subpass.into()),
+
+            ..vulkano::pipeline::graphics::GraphicsPipelineCreateInfo::layout(layout)
+        },
+    )
+    .map_err(|e| {
+        crate::graphics::backend::error::VulkanError::PipelineCreationError(format!(
+            "Failed to create shadow pipeline: {}",
+            e
+        ))
+    })
+}
+
+// compares with the shadow maps instead of returning their depth, outside of them
+// everything is lit
+fn create_shadow_sampler(
+    device: Arc<vulkano::device::Device>,
+) -> Result<Arc<vulkano::image::sampler::Sampler>, crate::graphics::backend::error::VulkanError> {
+    // blending the four nearest comparisons smooths the edges, when the device can
+    let filter = match device
+        .physical_device()
+        .format_properties(vulkano::format::Format::D32_SFLOAT)
+    {
+        Ok(properties)
+            if properties
+                .optimal_tiling_features
+                .intersects(vulkano::format::FormatFeatures::SAMPLED_IMAGE_FILTER_LINEAR) =>
+        {
+            vulkano::image::sampler::Filter::Linear
+        }
+        _ => {
+            println!("Linear filtering of depth not supported, shadow edges are sampled nearest");
+            vulkano::image::sampler::Filter::Nearest
+        }
+    };
+
+    vulkano::image::sampler::Sampler::new(
+        device,
+        vulkano::image::sampler::SamplerCreateInfo {
+            mag_filter: filter,
+            min_filter: filter,
+            address_mode: [vulkano::image::sampler::SamplerAddressMode::ClampToBorder; 3],
+            border_color: vulkano::image::sampler::BorderColor::FloatOpaqueWhite,
+            compare: Some(vulkano::pipeline::graphics::depth_stencil::CompareOp::LessOrEqual),
+            ..Default::default()
+        },
+    )
+    .map_err(|e| {
+        crate::graphics::backend::error::VulkanError::PipelineCreationError(format!(
+            "Failed to create shadow sampler: {}",
+            e
+        ))
+    })
+}
+
+pub(crate) fn create_depth_buffer(
     memory_allocator: Arc<vulkano::memory::allocator::StandardMemoryAllocator>,
     extent: [u32; 2],
 ) -> Result<Arc<vulkano::image::view::ImageView>, super::error::VulkanError> {
@@ -961,8 +1015,9 @@ fn create_depth_buffer(
     })
 }
 
+// without an event loop the instance has no surface extensions, for rendering offscreen
 fn create_instance(
-    event_loop: &impl winit::raw_window_handle::HasDisplayHandle,
+    event_loop: Option<&winit::event_loop::EventLoop<()>>,
 ) -> Result<Arc<vulkano::instance::Instance>, crate::graphics::backend::error::VulkanError> {
     let library = vulkano::library::VulkanLibrary::new().map_err(|e| {
         crate::graphics::backend::error::VulkanError::InstanceCreationError(format!(
@@ -970,8 +1025,11 @@ fn create_instance(
             e
         ))
     })?;
-    let mut required_extensions = vulkano::swapchain::Surface::required_extensions(&event_loop)
-        .expect("Failed to get required extensions");
+    let mut required_extensions = match event_loop {
+        Some(event_loop) => vulkano::swapchain::Surface::required_extensions(event_loop)
+            .expect("Failed to get required extensions"),
+        None => vulkano::instance::InstanceExtensions::empty(),
+    };
 
     required_extensions.khr_portability_enumeration = true;
 
@@ -1003,7 +1061,7 @@ fn create_instance(
 }
 fn create_virtual_device(
     instance: Arc<vulkano::instance::Instance>,
-    event_loop: &impl winit::raw_window_handle::HasDisplayHandle,
+    event_loop: Option<&winit::event_loop::EventLoop<()>>,
 ) -> Result<
     (
         Arc<vulkano::device::Device>,
@@ -1012,14 +1070,19 @@ fn create_virtual_device(
     crate::graphics::backend::error::VulkanError,
 > {
     let mut device_extensions = vulkano::device::DeviceExtensions {
-        khr_swapchain: true,
+        // nothing is presented without an event loop
+        khr_swapchain: event_loop.is_some(),
         // khr_dynamic_rendering: true,
         ..vulkano::device::DeviceExtensions::empty()
     };
 
-    let devices = instance
-        .enumerate_physical_devices()
-        .expect("could not enumerate devices");
+    // machines without a device get an error, so headless callers can skip rendering
+    let devices = instance.enumerate_physical_devices().map_err(|e| {
+        crate::graphics::backend::error::VulkanError::DeviceCreationError(format!(
+            "Failed to enumerate devices: {}",
+            e
+        ))
+    })?;
 
     let required_features = vulkano::device::DeviceFeatures {
         dynamic_rendering: true,
@@ -1028,12 +1091,21 @@ fn create_virtual_device(
         image_view_format_swizzle: true,
         ..vulkano::device::DeviceFeatures::empty()
     };
+    // enabled where supported, the scene buffers fall back without them
+    let optional_features = vulkano::device::DeviceFeatures {
+        // the instanced draws start at their slot lists through the first instance
+        draw_indirect_first_instance: true,
+        // the culled draws are packed behind their count
+        draw_indirect_count: true,
+        ..vulkano::device::DeviceFeatures::empty()
+    };
     let mut graphics_queue_family_index: Option<u32> = None;
     let mut transfer_queue_family_index: Option<u32> = None;
 
     let (suitable_device, graphics_queue_family_index, transfer_queue_family_index) = devices
         .into_iter()
         .filter(|device| device.supported_extensions().contains(&device_extensions))
+        .filter(|device| device.supported_features().contains(&required_features))
         .map(|device| {
             device
                 .queue_family_properties()
@@ -1048,9 +1120,11 @@ fn create_virtual_device(
                         .queue_flags
                         .contains(vulkano::device::QueueFlags::GRAPHICS)
                         && graphics_queue_family_index.is_none()
-                        && device
-                            .presentation_support(i as u32, &event_loop)
-                            .map_or(false, |support| support)
+                        && event_loop.is_none_or(|event_loop| {
+                            device
+                                .presentation_support(i as u32, event_loop)
+                                .map_or(false, |support| support)
+                        })
                     {
                         graphics_queue_family_index = Some(i as u32);
                     }
@@ -1082,16 +1156,22 @@ fn create_virtual_device(
             vulkano::device::physical::PhysicalDeviceType::Other => 4,
             _ => 5,
         })
-        .expect("No suitable device found");
+        .ok_or_else(|| {
+            crate::graphics::backend::error::VulkanError::DeviceCreationError(
+                "No suitable device found".to_string(),
+            )
+        })?;
 
     println!("Using device: {}", suitable_device.properties().device_name);
 
     if suitable_device.api_version() < vulkano::Version::V1_3 {
         device_extensions.khr_dynamic_rendering = true;
     }
-    let enabled_features = suitable_device
-        .supported_features()
-        .intersection(&required_features);
+    let enabled_features = required_features.union(
+        &suitable_device
+            .supported_features()
+            .intersection(&optional_features),
+    );
 
     println!("Enabled features: {:?}", enabled_features);
 
@@ -1105,20 +1185,35 @@ fn create_virtual_device(
         transfer_queue_family_index
     );
 
+    // a family can only be named once, devices with a single family like lavapipe get
+    // two queues of it when it has them
+    let queue_create_infos = if transfer_queue_family_index == graphics_queue_family_index {
+        let queue_count = suitable_device.queue_family_properties()
+            [graphics_queue_family_index as usize]
+            .queue_count;
+        vec![vulkano::device::QueueCreateInfo {
+            queue_family_index: graphics_queue_family_index,
+            queues: vec![0.5; queue_count.clamp(1, 2) as usize],
+            ..Default::default()
+        }]
+    } else {
+        vec![
+            vulkano::device::QueueCreateInfo {
+                queue_family_index: graphics_queue_family_index,
+                ..Default::default()
+            },
+            vulkano::device::QueueCreateInfo {
+                queue_family_index: transfer_queue_family_index,
+                ..Default::default()
+            },
+        ]
+    };
+
     let (device, queues) = vulkano::device::Device::new(
         suitable_device,
         vulkano::device::DeviceCreateInfo {
             enabled_extensions: device_extensions,
-            queue_create_infos: vec![
-                vulkano::device::QueueCreateInfo {
-                    queue_family_index: graphics_queue_family_index,
-                    ..Default::default()
-                },
-                vulkano::device::QueueCreateInfo {
-                    queue_family_index: transfer_queue_family_index,
-                    ..Default::default()
-                },
-            ],
+            queue_create_infos,
             enabled_features,
             ..Default::default()
         },
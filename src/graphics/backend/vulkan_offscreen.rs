@@ -0,0 +1,253 @@
+use std::sync::Arc;
+
+use vulkano::pipeline::Pipeline;
+use vulkano::sync::GpuFuture;
+
+use crate::graphics::backend::error::VulkanError;
+use crate::graphics::backend::vulkan_backend::{
+    VulkanBackend, create_depth_buffer, create_graphics_pipeline, record_scene,
+};
+use crate::graphics::backend::vulkan_scene::VulkanScene;
+use crate::graphics::camera::Camera;
+use crate::graphics::rendered_image::RenderedImage;
+use crate::graphics::scene::Scene;
+
+// linear floating point, so exr files keep the full range
+const COLOR_FORMAT: vulkano::format::Format = vulkano::format::Format::R32G32B32A32_SFLOAT;
+
+// an image the scene is rendered into instead of a swapchain image, kept between renders
+// so the scene buffers are only patched like they are for a window
+#[derive(Debug)]
+pub struct OffscreenTarget {
+    pub width: u32,
+    pub height: u32,
+    pipeline: Arc<vulkano::pipeline::GraphicsPipeline>,
+    color_image: Arc<vulkano::image::Image>,
+    color_view: Arc<vulkano::image::view::ImageView>,
+    depth_buffer: Arc<vulkano::image::view::ImageView>,
+    // the pixels copied back from the color image, four floats each
+    readback: vulkano::buffer::Subbuffer<[f32]>,
+    scene_buffers: VulkanScene,
+}
+
+impl VulkanBackend {
+    pub fn create_offscreen_target(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<OffscreenTarget, VulkanError> {
+        let pipeline = create_graphics_pipeline(self.device.clone(), COLOR_FORMAT)?;
+
+        let color_image = vulkano::image::Image::new(
+            self.memory_allocator.clone(),
+            vulkano::image::ImageCreateInfo {
+                image_type: vulkano::image::ImageType::Dim2d,
+                format: COLOR_FORMAT,
+                extent: [width, height, 1],
+                usage: vulkano::image::ImageUsage::COLOR_ATTACHMENT
+                    | vulkano::image::ImageUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            vulkano::memory::allocator::AllocationCreateInfo::default(),
+        )
+        .map_err(|e| {
+            VulkanError::ImageCreationError(format!("Failed to create offscreen image: {}", e))
+        })?;
+        let color_view = vulkano::image::view::ImageView::new_default(color_image.clone())
+            .map_err(|e| {
+                VulkanError::ImageViewCreationError(format!(
+                    "Failed to create offscreen image view: {}",
+                    e
+                ))
+            })?;
+
+        let readback = vulkano::buffer::Buffer::new_slice(
+            self.memory_allocator.clone(),
+            vulkano::buffer::BufferCreateInfo {
+                usage: vulkano::buffer::BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            vulkano::memory::allocator::AllocationCreateInfo {
+                memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_HOST
+                    | vulkano::memory::allocator::MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            width as u64 * height as u64 * 4,
+        )
+        .map_err(|e| {
+            VulkanError::BufferCreationError(format!("Failed to create readback buffer: {}", e))
+        })?;
+
+        Ok(OffscreenTarget {
+            width,
+            height,
+            pipeline,
+            color_image,
+            color_view,
+            depth_buffer: create_depth_buffer(self.memory_allocator.clone(), [width, height])?,
+            readback,
+            // every render waits for the gpu, so one set of frame buffers is enough
+            scene_buffers: self.create_scene_buffers(1)?,
+        })
+    }
+
+    // renders the scene as seen from the camera into the target, waits for the gpu and
+    // reads the pixels back
+    pub fn render_offscreen(
+        &mut self,
+        target: &mut OffscreenTarget,
+        scene: &Scene,
+        camera: &Camera,
+    ) -> Result<RenderedImage, crate::graphics::error::GraphicsError> {
+        let mut builder = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
+            self.command_buffer_allocator.clone(),
+            self.graphic_queue.queue_family_index(),
+            vulkano::command_buffer::CommandBufferUsage::OneTimeSubmit,
+        )
+        .map_err(|e| {
+            VulkanError::CommandBufferError(format!(
+                "Failed to create command buffer builder: {}",
+                e
+            ))
+        })?;
+
+        let layout = target
+            .pipeline
+            .layout()
+            .set_layouts()
+            .first()
+            .ok_or_else(|| {
+                VulkanError::PipelineLayoutError(
+                    "No descriptor set layout found in pipeline".to_string(),
+                )
+            })?
+            .clone();
+        let frame = target
+            .scene_buffers
+            .update(
+                scene,
+                camera,
+                &mut builder,
+                target.width as f32 / target.height as f32,
+                0,
+                layout,
+            )
+            .map_err(|e| {
+                VulkanError::SceneError(format!("Failed to update renderable scene: {}", e))
+            })?;
+
+        let viewport = vulkano::pipeline::graphics::viewport::Viewport {
+            offset: [0.0, 0.0],
+            extent: [target.width as f32, target.height as f32],
+            ..Default::default()
+        };
+        record_scene(
+            &mut builder,
+            &target.pipeline,
+            viewport,
+            target.color_view.clone(),
+            target.depth_buffer.clone(),
+            &target.scene_buffers,
+            frame,
+        )?;
+
+        builder
+            .copy_image_to_buffer(
+                vulkano::command_buffer::CopyImageToBufferInfo::image_buffer(
+                    target.color_image.clone(),
+                    target.readback.clone(),
+                ),
+            )
+            .map_err(|e| {
+                VulkanError::CommandBufferError(format!("Failed to copy offscreen image: {}", e))
+            })?;
+
+        let command_buffer = builder.build().map_err(|e| {
+            VulkanError::CommandBufferError(format!("Failed to build command buffer: {}", e))
+        })?;
+
+        vulkano::sync::now(self.device.clone())
+            .then_execute(self.graphic_queue.clone(), command_buffer)
+            .map_err(|e| {
+                VulkanError::CommandBufferError(format!("Failed to execute command buffer: {}", e))
+            })?
+            .then_signal_fence_and_flush()
+            .map_err(|e| {
+                VulkanError::SynchronizationError(format!(
+                    "Failed to flush offscreen render: {}",
+                    e
+                ))
+            })?
+            .wait(None)
+            .map_err(|e| {
+                VulkanError::SynchronizationError(format!(
+                    "Failed to wait for offscreen render: {}",
+                    e
+                ))
+            })?;
+
+        let pixels = target
+            .readback
+            .read()
+            .map_err(|e| {
+                VulkanError::BufferCreationError(format!("Failed to read back pixels: {}", e))
+            })?
+            .chunks_exact(4)
+            .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+            .collect();
+
+        Ok(RenderedImage {
+            width: target.width,
+            height: target.height,
+            pixels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // runs wherever there is a device, lavapipe on ci, set ATOM_UPDATE_GOLDEN to write the
+    // render as the new reference instead, the edges of triangles may be rasterized a
+    // pixel apart on other drivers, so a few pixels are allowed to differ
+    #[test]
+    fn default_scene_matches_the_golden_image() {
+        let Ok(mut backend) = VulkanBackend::new_headless() else {
+            println!("No vulkan device, skipping the golden image");
+            return;
+        };
+        // the scene of the software rasterizer test, which wrote the reference
+        let mut scene = Scene::default();
+        scene
+            .lights
+            .insert(crate::graphics::light::Light::Directional(
+                crate::graphics::light::DirectionalLight {
+                    direction: glam::Vec3::new(-1.0, -1.0, -1.0),
+                    color: glam::Vec3::ONE,
+                    intensity: 0.8,
+                    cast_shadows: false,
+                },
+            ));
+        let mut target = backend.create_offscreen_target(160, 90).unwrap();
+        let image = backend
+            .render_offscreen(&mut target, &scene, &Camera::default())
+            .unwrap();
+
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/golden/default_scene.png"
+        );
+        if std::env::var_os("ATOM_UPDATE_GOLDEN").is_some() {
+            image.save(path).unwrap();
+            return;
+        }
+        let reference = RenderedImage::load(path).unwrap();
+        let difference = image.difference(&reference, 0.05).unwrap();
+        assert!(
+            difference.mean < 0.01 && difference.outliers < 0.01,
+            "{}",
+            difference
+        );
+    }
+}
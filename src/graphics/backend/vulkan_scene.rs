@@ -0,0 +1,2049 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use vulkano::device::DeviceOwned;
+use vulkano::pipeline::Pipeline;
+use vulkano::sync::GpuFuture;
+
+use crate::graphics::arena::Handle;
+use crate::graphics::backend::error::VulkanError;
+use crate::graphics::backend::vulkan_backend::{cs, fs, shadow_vs, vs};
+use crate::graphics::bounds::{Aabb, BoundingSphere};
+use crate::graphics::camera::Camera;
+use crate::graphics::frustum::{CullingStats, Frustum};
+use crate::graphics::light::{GpuLight, Light};
+use crate::graphics::material::GpuMaterials;
+use crate::graphics::mesh::{Geometry, Mesh, SubMesh};
+use crate::graphics::scene::{Scene, SceneRevision};
+use crate::graphics::shadow::{
+    Cascade, CubeShadow, MAX_CASCADES, MAX_POINT_SHADOWS, ShadowSettings,
+};
+use crate::graphics::vertex::Vertex;
+
+// where the vertices and index lists of one geometry revision live in the shared buffers
+#[derive(Debug, Clone)]
+struct GpuGeometry {
+    vertex_offset: u32,
+    // (first index, index count) of the full detail indices followed by every lod
+    index_ranges: Vec<(u32, u32)>,
+}
+
+// one submesh of an object, its slot in the instance buffers is its position in the list
+#[derive(Debug, Clone)]
+struct GpuInstance {
+    object: Handle<Mesh>,
+    submesh_index: usize,
+    model_matrix: glam::Mat4,
+    material: GpuMaterials,
+}
+
+type IndirectBuffer =
+    vulkano::buffer::Subbuffer<[vulkano::command_buffer::DrawIndexedIndirectCommand]>;
+
+// the instanced draws of a pass, see `VulkanScene::draw_instanced`
+#[derive(Debug, Clone)]
+pub enum DrawCommands {
+    // written on the host, which keeps them to draw one by one on devices that can not
+    // start an indirect draw at a first instance
+    Host {
+        buffer: IndirectBuffer,
+        commands: Vec<vulkano::command_buffer::DrawIndexedIndirectCommand>,
+    },
+    // written by the cull shader, the groups that kept instances are packed at the front
+    // and drawn up to `count` when the device can take the count from a buffer, otherwise
+    // every group is drawn and the empty ones draw nothing
+    Culled {
+        buffer: IndirectBuffer,
+        count: Option<vulkano::buffer::Subbuffer<u32>>,
+    },
+}
+
+// a submesh instance that passed the culling done on the cpu
+struct VisibleSubmesh<'a> {
+    submesh: &'a SubMesh,
+    level: Option<usize>,
+    slot: u32,
+    sphere: BoundingSphere,
+    aabb: Aabb,
+}
+
+// what a frame draws, grouped into instanced draws on the cpu, either of the culled
+// instances or of every candidate, which the cull shader adds to its group if it survives
+enum DrawList {
+    Instanced {
+        instance_slots: Vec<u32>,
+        commands: Vec<vulkano::command_buffer::DrawIndexedIndirectCommand>,
+    },
+    Culled {
+        candidates: Vec<cs::Candidate>,
+        // one per group, without instances, the slots of a group start at first_instance
+        commands: Vec<vulkano::command_buffer::DrawIndexedIndirectCommand>,
+        frustum: Frustum,
+    },
+}
+
+// the casters of every cascade and cube face of the shadowed lights, drawn from one
+// instanced list
+struct ShadowDraws {
+    cascades: Vec<Cascade>,
+    cubes: Vec<CubeShadow>,
+    // the range of `commands` every cascade draws, followed by those of the cube faces
+    ranges: Vec<std::ops::Range<u64>>,
+    instance_slots: Vec<u32>,
+    commands: Vec<vulkano::command_buffer::DrawIndexedIndirectCommand>,
+    view: glam::Mat4,
+}
+
+// the depth of every cascade or cube face in one layer of an array image
+#[derive(Debug)]
+struct ShadowMaps {
+    resolution: u32,
+    // sampled by the fragment shader
+    array_view: Arc<vulkano::image::view::ImageView>,
+    // drawn into by the shadow passes
+    layer_views: Vec<Arc<vulkano::image::view::ImageView>>,
+}
+
+// what a frame in flight still has to copy before it draws, changes are recorded for
+// every frame and applied once the frame comes around again
+#[derive(Debug, Default)]
+struct PendingChanges {
+    instances: bool,
+    transforms: BTreeSet<usize>,
+    materials: BTreeSet<usize>,
+    lights: bool,
+}
+
+// the buffers only one frame in flight reads, they are written once its fence has
+// signalled, so the next frame is recorded while the gpu still draws the previous one
+#[derive(Debug, Default)]
+struct FrameResources {
+    matrix_buffer: Option<vulkano::buffer::Subbuffer<[glam::Mat4]>>,
+    normal_buffer: Option<vulkano::buffer::Subbuffer<[glam::Mat4]>>,
+    material_buffer: Option<vulkano::buffer::Subbuffer<[GpuMaterials]>>,
+    light_buffer: Option<vulkano::buffer::Subbuffer<[GpuLight]>>,
+    uniform_buffer: Option<vulkano::buffer::Subbuffer<vs::CameraUbo>>,
+    // the instance slot of every drawn instance, indexed by gl_InstanceIndex
+    instance_buffer: Option<vulkano::buffer::Subbuffer<[u32]>>,
+    indirect_buffer:
+        Option<vulkano::buffer::Subbuffer<[vulkano::command_buffer::DrawIndexedIndirectCommand]>>,
+    // rebuilt whenever one of the buffers it points to is replaced
+    descriptor_set: Option<Arc<vulkano::descriptor_set::DescriptorSet>>,
+    pending: PendingChanges,
+
+    // the shadow maps of the frame and what draws them
+    shadow_maps: Option<ShadowMaps>,
+    shadow_uniform_buffer: Option<vulkano::buffer::Subbuffer<fs::ShadowUbo>>,
+    shadow_instance_buffer: Option<vulkano::buffer::Subbuffer<[u32]>>,
+    shadow_indirect_buffer: Option<IndirectBuffer>,
+    shadow_descriptor_set: Option<Arc<vulkano::descriptor_set::DescriptorSet>>,
+    point_shadow_maps: Option<ShadowMaps>,
+    point_shadow_uniform_buffer: Option<vulkano::buffer::Subbuffer<fs::PointShadowUbo>>,
+
+    // the inputs and outputs of the cull shader, unused without it, the groups are in
+    // `indirect_buffer` and the packed draws in `draw_buffer`
+    candidate_buffer: Option<vulkano::buffer::Subbuffer<[cs::Candidate]>>,
+    draw_buffer: Option<IndirectBuffer>,
+    // the surviving candidates followed by the packed draws
+    count_buffer: Option<vulkano::buffer::Subbuffer<[u32]>>,
+    count_readback: Option<vulkano::buffer::Subbuffer<[u32]>>,
+    cull_descriptor_set: Option<Arc<vulkano::descriptor_set::DescriptorSet>>,
+    // the stats of the cpu side and the candidate count of the frame last recorded
+    culled: Option<(CullingStats, usize)>,
+}
+
+// the scene kept on the gpu between frames, geometry is uploaded once per revision through
+// the transfer queue and shared by all frames, transforms, materials and lights are copied
+// into every frame in flight and only patched when the scene revision says they changed
+#[derive(Debug)]
+pub struct VulkanScene {
+    memory_allocator: Arc<vulkano::memory::allocator::StandardMemoryAllocator>,
+    command_buffer_allocator:
+        Arc<vulkano::command_buffer::allocator::StandardCommandBufferAllocator>,
+    descriptor_set_allocator:
+        Arc<vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator>,
+    transfer_queue: Arc<vulkano::device::Queue>,
+    // culls the submeshes on the gpu, `None` when indirect draws can not have a first instance
+    cull_pipeline: Option<Arc<vulkano::pipeline::ComputePipeline>>,
+    // draws the depth of the shadow casters, sampled with the comparing sampler
+    shadow_pipeline: Arc<vulkano::pipeline::GraphicsPipeline>,
+    shadow_sampler: Arc<vulkano::image::sampler::Sampler>,
+    // both queue families use the device local buffers when they differ
+    queue_family_indices: Vec<u32>,
+    // staging data of the patches, its arenas are reused once a frame is done
+    upload_allocator: vulkano::buffer::allocator::SubbufferAllocator,
+
+    // the used front of the geometry buffers, what the draws bind
+    pub vertex_buffer: Option<vulkano::buffer::Subbuffer<[Vertex]>>,
+    pub index_buffer: Option<vulkano::buffer::Subbuffer<[u32]>>,
+    // the whole geometry buffers, new geometry is appended behind the used front
+    vertex_storage: Option<vulkano::buffer::Subbuffer<[Vertex]>>,
+    index_storage: Option<vulkano::buffer::Subbuffer<[u32]>>,
+    geometry: HashMap<u64, GpuGeometry>,
+
+    instances: Vec<GpuInstance>,
+    lights: Vec<GpuLight>,
+    // the direction of every directional light with shadow maps, in the order of their
+    // `shadow_index`
+    shadowed_lights: Vec<glam::Vec3>,
+    // the position of every point light with a cube of shadow maps, in the same order
+    shadowed_point_lights: Vec<glam::Vec3>,
+    // the settings the lights were uploaded with
+    shadow_settings: Option<ShadowSettings>,
+    frames: Vec<FrameResources>,
+
+    // what was uploaded last, `None` before the first frame
+    revision: Option<SceneRevision>,
+}
+
+// what one frame binds, the descriptor set points to the buffers of its frame in flight
+pub struct VulkanFrame {
+    pub culling: CullingStats,
+    pub descriptor_set: Arc<vulkano::descriptor_set::DescriptorSet>,
+    // `None` when nothing is drawn
+    pub draws: Option<DrawCommands>,
+    // drawn before the scene, which samples them
+    pub shadow_passes: Vec<ShadowPass>,
+    pub shadow_descriptor_set: Arc<vulkano::descriptor_set::DescriptorSet>,
+}
+
+// one cascade drawn into its layer of the shadow maps
+pub struct ShadowPass {
+    depth_attachment: Arc<vulkano::image::view::ImageView>,
+    resolution: u32,
+    view_projection: glam::Mat4,
+    // `None` when no caster reaches the cascade, the layer is only cleared
+    draws: Option<DrawCommands>,
+}
+
+impl VulkanScene {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        memory_allocator: Arc<vulkano::memory::allocator::StandardMemoryAllocator>,
+        command_buffer_allocator: Arc<
+            vulkano::command_buffer::allocator::StandardCommandBufferAllocator,
+        >,
+        descriptor_set_allocator: Arc<
+            vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator,
+        >,
+        graphic_queue: &Arc<vulkano::device::Queue>,
+        transfer_queue: Arc<vulkano::device::Queue>,
+        cull_pipeline: Option<Arc<vulkano::pipeline::ComputePipeline>>,
+        shadow_pipeline: Arc<vulkano::pipeline::GraphicsPipeline>,
+        shadow_sampler: Arc<vulkano::image::sampler::Sampler>,
+        frames_in_flight: usize,
+    ) -> Self {
+        let mut queue_family_indices = vec![graphic_queue.queue_family_index()];
+        if transfer_queue.queue_family_index() != graphic_queue.queue_family_index() {
+            queue_family_indices.push(transfer_queue.queue_family_index());
+        }
+
+        let upload_allocator = vulkano::buffer::allocator::SubbufferAllocator::new(
+            memory_allocator.clone(),
+            vulkano::buffer::allocator::SubbufferAllocatorCreateInfo {
+                buffer_usage: vulkano::buffer::BufferUsage::TRANSFER_SRC,
+                memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_HOST
+                    | vulkano::memory::allocator::MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+        );
+
+        VulkanScene {
+            memory_allocator,
+            command_buffer_allocator,
+            descriptor_set_allocator,
+            transfer_queue,
+            cull_pipeline,
+            shadow_pipeline,
+            shadow_sampler,
+            queue_family_indices,
+            upload_allocator,
+            vertex_buffer: None,
+            index_buffer: None,
+            vertex_storage: None,
+            index_storage: None,
+            geometry: HashMap::new(),
+            instances: Vec::new(),
+            lights: Vec::new(),
+            shadowed_lights: Vec::new(),
+            shadowed_point_lights: Vec::new(),
+            shadow_settings: None,
+            frames: (0..frames_in_flight.max(1))
+                .map(|_| FrameResources::default())
+                .collect(),
+            revision: None,
+        }
+    }
+
+    // brings the buffers of the frame in flight up to date with the scene as seen from the
+    // camera, patches are recorded into `builder`, which has to run before the frame draws,
+    // the fence of the frame must have signalled since it was last drawn
+    pub fn update(
+        &mut self,
+        scene: &Scene,
+        camera: &Camera,
+        builder: &mut vulkano::command_buffer::AutoCommandBufferBuilder<
+            vulkano::command_buffer::PrimaryAutoCommandBuffer,
+        >,
+        aspect_ratio: f32,
+        frame_index: usize,
+        layout: Arc<vulkano::descriptor_set::layout::DescriptorSetLayout>,
+    ) -> Result<VulkanFrame, crate::graphics::error::GraphicsError> {
+        // only kept once the scene is on the gpu, a failed upload is retried next frame
+        let revision = scene.revision();
+        let previous = self.revision;
+        let changed = |field: fn(&SceneRevision) -> u64| {
+            previous.is_none_or(|previous| field(&previous) != field(&revision))
+        };
+
+        if changed(|revision| revision.objects) {
+            self.rebuild_instances(scene);
+            // every object is kept on the gpu, so culling never uploads anything
+            self.upload_scene_geometry(scene)?;
+            for frame in &mut self.frames {
+                frame.pending.instances = true;
+            }
+        } else {
+            if changed(|revision| revision.transforms) {
+                let slots = self.update_transforms(scene);
+                for frame in &mut self.frames {
+                    frame.pending.transforms.extend(slots.iter().copied());
+                }
+            }
+            if changed(|revision| revision.materials) {
+                let slots = self.update_materials(scene);
+                for frame in &mut self.frames {
+                    frame.pending.materials.extend(slots.iter().copied());
+                }
+            }
+        }
+        // the shadow maps of the lights are numbered with the cascade count, those of the
+        // point lights in sixes
+        let settings = scene.shadows;
+        if changed(|revision| revision.lights) || self.shadow_settings != Some(settings) {
+            self.shadow_settings = Some(settings);
+            self.shadowed_lights.clear();
+            self.shadowed_point_lights.clear();
+            self.lights = scene
+                .lights
+                .values()
+                .map(|light| {
+                    let mut gpu_light = GpuLight::from(light);
+                    match light {
+                        Light::Directional(directional)
+                            if directional.cast_shadows
+                                && self.shadowed_lights.len() < settings.max_shadowed_lights() =>
+                        {
+                            gpu_light.shadow_index = (self.shadowed_lights.len() as u32
+                                * settings.cascade_count())
+                                as i32;
+                            self.shadowed_lights.push(directional.direction);
+                        }
+                        Light::Point(point)
+                            if point.cast_shadows
+                                && self.shadowed_point_lights.len()
+                                    < settings.max_point_shadows() =>
+                        {
+                            gpu_light.shadow_index = (self.shadowed_point_lights.len() * 6) as i32;
+                            self.shadowed_point_lights.push(point.position);
+                        }
+                        _ => {}
+                    }
+                    gpu_light
+                })
+                .collect();
+            // a storage buffer can not be empty, a black light adds nothing
+            if self.lights.is_empty() {
+                self.lights.push(GpuLight::default());
+            }
+            for frame in &mut self.frames {
+                frame.pending.lights = true;
+            }
+        }
+        self.revision = Some(revision);
+
+        // objects outside the view are skipped whole, the submeshes of the visible ones are
+        // tested against their own bounds, by the cull shader when there is one
+        let frustum = camera.frustum(aspect_ratio);
+        let gpu_culling = self.cull_pipeline.is_some();
+        let mut culling = CullingStats::default();
+        // the screen size of every visible object, `None` for culled ones
+        let mut objects = HashMap::<Handle<Mesh>, Option<f32>>::new();
+
+        let mut visible = Vec::<VisibleSubmesh>::new();
+        for (slot, instance) in self.instances.iter().enumerate() {
+            let Some(object) = scene.objects.get(instance.object) else {
+                continue;
+            };
+            let Some(submesh) = object.submeshes.get(instance.submesh_index) else {
+                continue;
+            };
+            if submesh.vertices.is_empty() || submesh.indices.is_empty() {
+                continue; // Skip empty submeshes
+            }
+
+            let screen_size = *objects.entry(instance.object).or_insert_with(|| {
+                let world_bounds = object.world_bounds();
+                if !frustum.intersects_sphere(&world_bounds.sphere)
+                    || !frustum.intersects_aabb(&world_bounds.aabb)
+                {
+                    culling.culled_objects += 1;
+                    return None;
+                }
+                culling.visible_objects += 1;
+
+                // pick the level of detail from how large the object appears on screen
+                Some(crate::graphics::mesh::simplify::projected_screen_size(
+                    world_bounds.sphere.center,
+                    world_bounds.sphere.radius,
+                    camera,
+                ))
+            });
+            let Some(screen_size) = screen_size else {
+                culling.culled_submeshes += 1;
+                continue;
+            };
+            let aabb = submesh.bounds().aabb.transform(&object.world_transform);
+            if !gpu_culling {
+                if !frustum.intersects_aabb(&aabb) {
+                    culling.culled_submeshes += 1;
+                    continue;
+                }
+                culling.visible_submeshes += 1;
+            }
+
+            visible.push(VisibleSubmesh {
+                submesh,
+                level: submesh.select_lod_level(screen_size),
+                slot: slot as u32,
+                sphere: submesh.bounds().sphere.transform(&object.world_transform),
+                aabb,
+            });
+        }
+
+        // the shadow maps reach outside the view, their casters are culled against the
+        // cascades instead and drawn at full detail
+        let scene_bounds = scene.bounds();
+        let cascades = self
+            .shadowed_lights
+            .iter()
+            .flat_map(|&direction| {
+                settings.cascades(camera, aspect_ratio, direction, &scene_bounds)
+            })
+            .collect::<Vec<_>>();
+        let cubes = self
+            .shadowed_point_lights
+            .iter()
+            .map(|&position| settings.cube_shadow(position, &scene_bounds))
+            .collect::<Vec<_>>();
+        let casters = cascades
+            .iter()
+            .map(|cascade| cascade.view_projection)
+            .chain(cubes.iter().flat_map(|cube| cube.faces))
+            .map(|view_projection| {
+                let frustum = Frustum::from_matrix(&view_projection);
+                self.instances
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(slot, instance)| {
+                        let object = scene.objects.get(instance.object)?;
+                        let submesh = object.submeshes.get(instance.submesh_index)?;
+                        let aabb = submesh.bounds().aabb.transform(&object.world_transform);
+                        (!submesh.vertices.is_empty()
+                            && !submesh.indices.is_empty()
+                            && frustum.intersects_aabb(&aabb))
+                        .then_some((submesh, None, slot as u32))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // geometry replaced without the objects being marked as changed
+        if visible
+            .iter()
+            .map(|visible| visible.submesh)
+            .chain(casters.iter().flatten().map(|&(submesh, _, _)| submesh))
+            .any(|submesh| !self.geometry.contains_key(&submesh.geometry.revision()))
+        {
+            self.upload_scene_geometry(scene)?;
+        }
+
+        let draws = if gpu_culling {
+            // grouped like the instanced draws, the group sizes are counted in first_instance
+            // until the groups are laid out
+            let mut commands = Vec::<vulkano::command_buffer::DrawIndexedIndirectCommand>::new();
+            let mut group_indices = HashMap::<(u64, Option<usize>), usize>::new();
+            let mut candidates = Vec::with_capacity(visible.len());
+            for visible in &visible {
+                let group = *group_indices
+                    .entry((visible.submesh.geometry.revision(), visible.level))
+                    .or_insert_with(|| {
+                        let (vertex_offset, first_index, index_count) =
+                            self.index_range(visible.submesh, visible.level);
+                        commands.push(vulkano::command_buffer::DrawIndexedIndirectCommand {
+                            index_count,
+                            instance_count: 0,
+                            first_index,
+                            vertex_offset,
+                            first_instance: 0,
+                        });
+                        commands.len() - 1
+                    });
+                commands[group].first_instance += 1;
+                candidates.push(cs::Candidate {
+                    sphere: visible.sphere.center.extend(visible.sphere.radius).into(),
+                    aabb_min: visible.aabb.min.extend(0.0).into(),
+                    aabb_max: visible.aabb.max.extend(0.0).into(),
+                    group: group as u32,
+                    slot: visible.slot,
+                });
+            }
+            let mut first_instance = 0;
+            for command in &mut commands {
+                let size = command.first_instance;
+                command.first_instance = first_instance;
+                first_instance += size;
+            }
+            DrawList::Culled {
+                candidates,
+                commands,
+                frustum,
+            }
+        } else {
+            let mut instance_slots = Vec::new();
+            let commands = self.instanced_commands(
+                visible
+                    .iter()
+                    .map(|visible| (visible.submesh, visible.level, visible.slot)),
+                &mut instance_slots,
+            );
+            // a storage buffer can not be empty, the slot is never read without draws
+            if instance_slots.is_empty() {
+                instance_slots.push(0);
+            }
+            DrawList::Instanced {
+                instance_slots,
+                commands,
+            }
+        };
+
+        let mut shadows = ShadowDraws {
+            cascades,
+            cubes,
+            ranges: Vec::new(),
+            instance_slots: Vec::new(),
+            commands: Vec::new(),
+            view: camera.view_matrix(),
+        };
+        for casters in casters {
+            let commands = self.instanced_commands(casters, &mut shadows.instance_slots);
+            let first = shadows.commands.len() as u64;
+            shadows.ranges.push(first..first + commands.len() as u64);
+            shadows.commands.extend(commands);
+        }
+
+        let ubo_data = vs::CameraUbo {
+            proj: camera.projection_matrix(aspect_ratio).to_cols_array_2d(),
+            view: camera.view_matrix().to_cols_array_2d(),
+        };
+
+        // taken out of the list while it is written, so the helpers can borrow the scene
+        let mut frame = std::mem::take(&mut self.frames[frame_index]);
+        let result = self.write_frame(
+            &mut frame, builder, ubo_data, draws, shadows, culling, layout,
+        );
+        self.frames[frame_index] = frame;
+        Ok(result?)
+    }
+
+    // (vertex offset, first index, index count) of the level of detail of the uploaded submesh
+    fn index_range(&self, submesh: &SubMesh, level: Option<usize>) -> (u32, u32, u32) {
+        let geometry = &self.geometry[&submesh.geometry.revision()];
+        let (first_index, index_count) = geometry.index_ranges[level.map_or(0, |level| level + 1)];
+        (geometry.vertex_offset, first_index, index_count)
+    }
+
+    // instances sharing their geometry revision and level of detail are drawn together,
+    // their slots are appended to `instance_slots`, which the commands index into
+    fn instanced_commands<'a>(
+        &self,
+        draws: impl IntoIterator<Item = (&'a SubMesh, Option<usize>, u32)>,
+        instance_slots: &mut Vec<u32>,
+    ) -> Vec<vulkano::command_buffer::DrawIndexedIndirectCommand> {
+        let mut groups = Vec::<(&SubMesh, Option<usize>, Vec<u32>)>::new();
+        let mut group_indices = HashMap::<(u64, Option<usize>), usize>::new();
+        for (submesh, level, slot) in draws {
+            let group = *group_indices
+                .entry((submesh.geometry.revision(), level))
+                .or_insert_with(|| {
+                    groups.push((submesh, level, Vec::new()));
+                    groups.len() - 1
+                });
+            groups[group].2.push(slot);
+        }
+
+        let mut commands = Vec::with_capacity(groups.len());
+        for (submesh, level, slots) in groups {
+            let (vertex_offset, first_index, index_count) = self.index_range(submesh, level);
+
+            // gl_InstanceIndex starts at first_instance, so it indexes the slot list directly
+            commands.push(vulkano::command_buffer::DrawIndexedIndirectCommand {
+                index_count,
+                instance_count: slots.len() as u32,
+                first_index,
+                vertex_offset,
+                first_instance: instance_slots.len() as u32,
+            });
+            instance_slots.extend(slots);
+        }
+        commands
+    }
+
+    // a new slot for every submesh, every frame uploads all instance data again
+    fn rebuild_instances(&mut self, scene: &Scene) {
+        self.instances = scene
+            .objects
+            .iter()
+            .flat_map(|(handle, object)| {
+                (0..object.submeshes.len()).map(move |submesh_index| GpuInstance {
+                    object: handle,
+                    submesh_index,
+                    model_matrix: object.world_transform,
+                    material: submesh_material(scene, &object.submeshes[submesh_index]),
+                })
+            })
+            .collect();
+    }
+
+    // the slots whose transform changed
+    fn update_transforms(&mut self, scene: &Scene) -> Vec<usize> {
+        let mut changed = Vec::new();
+        for (slot, instance) in self.instances.iter_mut().enumerate() {
+            if let Some(object) = scene.objects.get(instance.object)
+                && object.world_transform != instance.model_matrix
+            {
+                instance.model_matrix = object.world_transform;
+                changed.push(slot);
+            }
+        }
+        changed
+    }
+
+    // the slots whose material changed
+    fn update_materials(&mut self, scene: &Scene) -> Vec<usize> {
+        let mut changed = Vec::new();
+        for (slot, instance) in self.instances.iter_mut().enumerate() {
+            let Some(submesh) = scene
+                .objects
+                .get(instance.object)
+                .and_then(|object| object.submeshes.get(instance.submesh_index))
+            else {
+                continue;
+            };
+            let material = submesh_material(scene, submesh);
+            if material != instance.material {
+                instance.material = material;
+                changed.push(slot);
+            }
+        }
+        changed
+    }
+
+    // applies the pending changes of the frame and writes what it draws
+    #[allow(clippy::too_many_arguments)]
+    fn write_frame(
+        &self,
+        frame: &mut FrameResources,
+        builder: &mut vulkano::command_buffer::AutoCommandBufferBuilder<
+            vulkano::command_buffer::PrimaryAutoCommandBuffer,
+        >,
+        ubo_data: vs::CameraUbo,
+        draws: DrawList,
+        shadows: ShadowDraws,
+        culling: CullingStats,
+        layout: Arc<vulkano::descriptor_set::layout::DescriptorSetLayout>,
+    ) -> Result<VulkanFrame, VulkanError> {
+        let pending = std::mem::take(&mut frame.pending);
+        if pending.instances {
+            let len = self.instances.len().max(1) as u64;
+            let usage = vulkano::buffer::BufferUsage::STORAGE_BUFFER;
+            frame.matrix_buffer = Some(self.reserve(frame.matrix_buffer.take(), usage, len)?);
+            frame.normal_buffer = Some(self.reserve(frame.normal_buffer.take(), usage, len)?);
+            frame.material_buffer = Some(self.reserve(frame.material_buffer.take(), usage, len)?);
+            frame.descriptor_set = None;
+            frame.shadow_descriptor_set = None;
+
+            let slots = (0..self.instances.len()).collect::<Vec<_>>();
+            self.patch_instances(builder, frame, &slots, true, true)?;
+        } else {
+            let transforms = pending.transforms.into_iter().collect::<Vec<_>>();
+            self.patch_instances(builder, frame, &transforms, true, false)?;
+            let materials = pending.materials.into_iter().collect::<Vec<_>>();
+            self.patch_instances(builder, frame, &materials, false, true)?;
+        }
+
+        // the shader loops over the whole buffer, so it is sized to the lights exactly
+        if pending.lights {
+            let len = self.lights.len() as u64;
+            if frame
+                .light_buffer
+                .as_ref()
+                .is_none_or(|buffer| buffer.len() != len)
+            {
+                frame.light_buffer = Some(
+                    self.create_device_buffer(vulkano::buffer::BufferUsage::STORAGE_BUFFER, len)?,
+                );
+                frame.descriptor_set = None;
+            }
+            let lights = self
+                .lights
+                .iter()
+                .enumerate()
+                .map(|(index, light)| (index as u64, light.clone()))
+                .collect::<Vec<_>>();
+            self.patch(builder, frame.light_buffer.clone(), &lights)?;
+        }
+
+        let uniform_buffer = match frame.uniform_buffer.clone() {
+            Some(buffer) => buffer,
+            None => {
+                let buffer = vulkano::buffer::Buffer::new_sized(
+                    self.memory_allocator.clone(),
+                    vulkano::buffer::BufferCreateInfo {
+                        usage: vulkano::buffer::BufferUsage::UNIFORM_BUFFER,
+                        ..Default::default()
+                    },
+                    host_allocation_info(),
+                )
+                .map_err(|e| {
+                    VulkanError::BufferCreationError(format!(
+                        "Failed to create uniform buffer: {}",
+                        e
+                    ))
+                })?;
+                frame.uniform_buffer = Some(buffer.clone());
+                frame.descriptor_set = None;
+                buffer
+            }
+        };
+        *uniform_buffer.write().map_err(|e| {
+            VulkanError::BufferCreationError(format!("Failed to write uniform buffer: {}", e))
+        })? = ubo_data;
+
+        let (culling, draws) = match draws {
+            DrawList::Instanced {
+                instance_slots,
+                commands,
+            } => {
+                if self.write_host_buffer(
+                    &mut frame.instance_buffer,
+                    vulkano::buffer::BufferUsage::STORAGE_BUFFER,
+                    &instance_slots,
+                )? {
+                    frame.descriptor_set = None;
+                }
+                // the buffer keeps its capacity, only the commands of this frame are drawn
+                let draws = if commands.is_empty() {
+                    None
+                } else {
+                    self.write_host_buffer(
+                        &mut frame.indirect_buffer,
+                        vulkano::buffer::BufferUsage::INDIRECT_BUFFER,
+                        &commands,
+                    )?;
+                    frame
+                        .indirect_buffer
+                        .clone()
+                        .map(|buffer| DrawCommands::Host {
+                            buffer: buffer.slice(0..commands.len() as u64),
+                            commands,
+                        })
+                };
+                (culling, draws)
+            }
+            DrawList::Culled {
+                candidates,
+                commands,
+                frustum,
+            } => {
+                // the gpu counts are only known once the frame is done, so the stats are
+                // those of the frame recorded into these buffers before
+                let previous = frame.culled.replace((culling, candidates.len()));
+                let culling = previous
+                    .and_then(|(culling, candidates)| {
+                        // nothing was dispatched, the readback still holds an older count
+                        let count = if candidates == 0 {
+                            0
+                        } else {
+                            *frame.count_readback.as_ref()?.read().ok()?.first()?
+                        };
+                        Some(CullingStats {
+                            visible_submeshes: count as usize,
+                            culled_submeshes: culling.culled_submeshes
+                                + candidates.saturating_sub(count as usize),
+                            ..culling
+                        })
+                    })
+                    .unwrap_or(culling);
+
+                let draws = self.cull(builder, frame, candidates, commands, &frustum)?;
+                (culling, draws)
+            }
+        };
+
+        let shadow_passes = self.write_shadows(frame, shadows)?;
+
+        let descriptor_set = match frame.descriptor_set.clone() {
+            Some(descriptor_set) => descriptor_set,
+            None => {
+                let descriptor_set = self.create_descriptor_set(frame, layout)?;
+                frame.descriptor_set = Some(descriptor_set.clone());
+                descriptor_set
+            }
+        };
+        let shadow_descriptor_set = match frame.shadow_descriptor_set.clone() {
+            Some(descriptor_set) => descriptor_set,
+            None => {
+                let descriptor_set = self.create_shadow_descriptor_set(frame)?;
+                frame.shadow_descriptor_set = Some(descriptor_set.clone());
+                descriptor_set
+            }
+        };
+
+        Ok(VulkanFrame {
+            culling,
+            descriptor_set,
+            draws,
+            shadow_passes,
+            shadow_descriptor_set,
+        })
+    }
+
+    // writes the cascades, the cubes and the casters of the frame, the shadow maps are
+    // recreated when their size changed, every cascade and face gets a pass even without
+    // casters to clear its layer
+    fn write_shadows(
+        &self,
+        frame: &mut FrameResources,
+        shadows: ShadowDraws,
+    ) -> Result<Vec<ShadowPass>, VulkanError> {
+        let settings = self.shadow_settings.unwrap_or_default();
+        // a descriptor needs an image, without shadowed lights it is a single texel
+        let resolution = if shadows.cascades.is_empty() {
+            1
+        } else {
+            settings.resolution.max(1)
+        };
+        let layers = shadows.cascades.len().max(1) as u32;
+        if self.resize_shadow_maps(&mut frame.shadow_maps, resolution, layers)? {
+            frame.descriptor_set = None;
+        }
+        let point_resolution = if shadows.cubes.is_empty() {
+            1
+        } else {
+            settings.point_resolution.max(1)
+        };
+        let point_layers = (shadows.cubes.len() * 6).max(1) as u32;
+        if self.resize_shadow_maps(&mut frame.point_shadow_maps, point_resolution, point_layers)? {
+            frame.descriptor_set = None;
+        }
+
+        let mut ubo_data = fs::ShadowUbo {
+            cascade_matrices: [glam::Mat4::IDENTITY.to_cols_array_2d(); MAX_CASCADES],
+            cascade_splits: [[0.0; 4]; MAX_CASCADES],
+            cascade_count: settings.cascade_count(),
+            pcf_radius: settings.pcf_radius as i32,
+            depth_bias: settings.depth_bias,
+            normal_bias: settings.normal_bias,
+        };
+        for (index, cascade) in shadows.cascades.iter().enumerate().take(MAX_CASCADES) {
+            ubo_data.cascade_matrices[index] =
+                cascade.from_view_space(&shadows.view).to_cols_array_2d();
+            ubo_data.cascade_splits[index] = [cascade.far, cascade.texel_size, 0.0, 0.0];
+        }
+        if self.write_uniform_buffer(&mut frame.shadow_uniform_buffer, ubo_data)? {
+            frame.descriptor_set = None;
+        }
+
+        let mut point_ubo_data = fs::PointShadowUbo {
+            face_matrices: [glam::Mat4::IDENTITY.to_cols_array_2d();
+                MAX_POINT_SHADOWS as usize * 6],
+            texel_scale: shadows.cubes.first().map_or(0.0, |cube| cube.texel_scale),
+        };
+        let inverse_view = shadows.view.inverse();
+        for (index, face) in shadows
+            .cubes
+            .iter()
+            .flat_map(|cube| cube.faces)
+            .enumerate()
+            .take(MAX_POINT_SHADOWS as usize * 6)
+        {
+            point_ubo_data.face_matrices[index] = (face * inverse_view).to_cols_array_2d();
+        }
+        if self.write_uniform_buffer(&mut frame.point_shadow_uniform_buffer, point_ubo_data)? {
+            frame.descriptor_set = None;
+        }
+
+        // a storage buffer can not be empty, the slot is never read without draws
+        let mut instance_slots = shadows.instance_slots;
+        if instance_slots.is_empty() {
+            instance_slots.push(0);
+        }
+        if self.write_host_buffer(
+            &mut frame.shadow_instance_buffer,
+            vulkano::buffer::BufferUsage::STORAGE_BUFFER,
+            &instance_slots,
+        )? {
+            frame.shadow_descriptor_set = None;
+        }
+        if !shadows.commands.is_empty() {
+            self.write_host_buffer(
+                &mut frame.shadow_indirect_buffer,
+                vulkano::buffer::BufferUsage::INDIRECT_BUFFER,
+                &shadows.commands,
+            )?;
+        }
+
+        let (Some(maps), Some(point_maps)) = (&frame.shadow_maps, &frame.point_shadow_maps) else {
+            return Ok(Vec::new());
+        };
+        let cascade_passes = shadows
+            .cascades
+            .iter()
+            .map(|cascade| cascade.view_projection)
+            .zip(&maps.layer_views)
+            .map(|(view_projection, layer_view)| (view_projection, layer_view, resolution));
+        let face_passes = shadows
+            .cubes
+            .iter()
+            .flat_map(|cube| cube.faces)
+            .zip(&point_maps.layer_views)
+            .map(|(view_projection, layer_view)| (view_projection, layer_view, point_resolution));
+        Ok(cascade_passes
+            .chain(face_passes)
+            .zip(shadows.ranges)
+            .map(
+                |((view_projection, layer_view, resolution), range)| ShadowPass {
+                    depth_attachment: layer_view.clone(),
+                    resolution,
+                    view_projection,
+                    draws: if range.is_empty() {
+                        None
+                    } else {
+                        frame
+                            .shadow_indirect_buffer
+                            .clone()
+                            .map(|buffer| DrawCommands::Host {
+                                buffer: buffer.slice(range.clone()),
+                                commands: shadows.commands
+                                    [range.start as usize..range.end as usize]
+                                    .to_vec(),
+                            })
+                    },
+                },
+            )
+            .collect())
+    }
+
+    // recreates the maps when their size changed, returns whether it did
+    fn resize_shadow_maps(
+        &self,
+        maps: &mut Option<ShadowMaps>,
+        resolution: u32,
+        layers: u32,
+    ) -> Result<bool, VulkanError> {
+        let resized = maps.as_ref().is_none_or(|maps| {
+            maps.resolution != resolution || maps.layer_views.len() as u32 != layers
+        });
+        if resized {
+            *maps = Some(self.create_shadow_maps(resolution, layers)?);
+        }
+        Ok(resized)
+    }
+
+    // creates the buffer the first time, returns whether it did
+    fn write_uniform_buffer<T: vulkano::buffer::BufferContents>(
+        &self,
+        buffer: &mut Option<vulkano::buffer::Subbuffer<T>>,
+        data: T,
+    ) -> Result<bool, VulkanError> {
+        let created = buffer.is_none();
+        let buffer = match buffer {
+            Some(buffer) => buffer,
+            None => buffer.insert(
+                vulkano::buffer::Buffer::new_sized(
+                    self.memory_allocator.clone(),
+                    vulkano::buffer::BufferCreateInfo {
+                        usage: vulkano::buffer::BufferUsage::UNIFORM_BUFFER,
+                        ..Default::default()
+                    },
+                    host_allocation_info(),
+                )
+                .map_err(|e| {
+                    VulkanError::BufferCreationError(format!(
+                        "Failed to create shadow uniform buffer: {}",
+                        e
+                    ))
+                })?,
+            ),
+        };
+        *buffer.write().map_err(|e| {
+            VulkanError::BufferCreationError(format!(
+                "Failed to write shadow uniform buffer: {}",
+                e
+            ))
+        })? = data;
+        Ok(created)
+    }
+
+    fn create_shadow_maps(&self, resolution: u32, layers: u32) -> Result<ShadowMaps, VulkanError> {
+        let image = vulkano::image::Image::new(
+            self.memory_allocator.clone(),
+            vulkano::image::ImageCreateInfo {
+                image_type: vulkano::image::ImageType::Dim2d,
+                format: vulkano::format::Format::D32_SFLOAT,
+                extent: [resolution, resolution, 1],
+                array_layers: layers,
+                usage: vulkano::image::ImageUsage::DEPTH_STENCIL_ATTACHMENT
+                    | vulkano::image::ImageUsage::SAMPLED,
+                ..Default::default()
+            },
+            vulkano::memory::allocator::AllocationCreateInfo::default(),
+        )
+        .map_err(|e| {
+            VulkanError::ImageCreationError(format!("Failed to create shadow maps: {}", e))
+        })?;
+
+        let view = |view_type, array_layers| {
+            let mut create_info = vulkano::image::view::ImageViewCreateInfo::from_image(&image);
+            create_info.view_type = view_type;
+            create_info.subresource_range.array_layers = array_layers;
+            vulkano::image::view::ImageView::new(image.clone(), create_info).map_err(|e| {
+                VulkanError::ImageViewCreationError(format!(
+                    "Failed to create shadow map view: {}",
+                    e
+                ))
+            })
+        };
+
+        Ok(ShadowMaps {
+            resolution,
+            array_view: view(vulkano::image::view::ImageViewType::Dim2dArray, 0..layers)?,
+            layer_views: (0..layers)
+                .map(|layer| view(vulkano::image::view::ImageViewType::Dim2d, layer..layer + 1))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn create_shadow_descriptor_set(
+        &self,
+        frame: &FrameResources,
+    ) -> Result<Arc<vulkano::descriptor_set::DescriptorSet>, VulkanError> {
+        let (Some(matrix_buffer), Some(instance_buffer)) = (
+            frame.matrix_buffer.clone(),
+            frame.shadow_instance_buffer.clone(),
+        ) else {
+            return Err(VulkanError::SceneError(
+                "Shadow caster buffers were never uploaded".to_string(),
+            ));
+        };
+        let layout = self
+            .shadow_pipeline
+            .layout()
+            .set_layouts()
+            .first()
+            .ok_or_else(|| {
+                VulkanError::PipelineLayoutError(
+                    "No descriptor set layout found in shadow pipeline".to_string(),
+                )
+            })?
+            .clone();
+
+        vulkano::descriptor_set::DescriptorSet::new(
+            self.descriptor_set_allocator.clone(),
+            layout,
+            [
+                vulkano::descriptor_set::WriteDescriptorSet::buffer(0, matrix_buffer),
+                vulkano::descriptor_set::WriteDescriptorSet::buffer(1, instance_buffer),
+            ],
+            [],
+        )
+        .map_err(|e| {
+            VulkanError::CommandBufferError(format!(
+                "Failed to create shadow descriptor set: {}",
+                e
+            ))
+        })
+    }
+
+    // draws the casters of every cascade into its layer of the shadow maps, before the
+    // scene that samples them
+    pub(crate) fn record_shadows(
+        &self,
+        builder: &mut vulkano::command_buffer::AutoCommandBufferBuilder<
+            vulkano::command_buffer::PrimaryAutoCommandBuffer,
+        >,
+        frame: &VulkanFrame,
+    ) -> Result<(), VulkanError> {
+        for pass in &frame.shadow_passes {
+            builder
+                .begin_rendering(vulkano::command_buffer::RenderingInfo {
+                    depth_attachment: Some(vulkano::command_buffer::RenderingAttachmentInfo {
+                        load_op: vulkano::render_pass::AttachmentLoadOp::Clear,
+                        store_op: vulkano::render_pass::AttachmentStoreOp::Store,
+                        clear_value: Some(1.0f32.into()),
+                        ..vulkano::command_buffer::RenderingAttachmentInfo::image_view(
+                            pass.depth_attachment.clone(),
+                        )
+                    }),
+                    ..Default::default()
+                })
+                .map_err(|e| {
+                    VulkanError::CommandBufferError(format!("Failed to begin shadow pass: {}", e))
+                })?;
+
+            if let (Some(draws), Some(vertex_buffer), Some(index_buffer)) = (
+                &pass.draws,
+                self.vertex_buffer.clone(),
+                self.index_buffer.clone(),
+            ) {
+                let viewport = vulkano::pipeline::graphics::viewport::Viewport {
+                    offset: [0.0, 0.0],
+                    extent: [pass.resolution as f32, pass.resolution as f32],
+                    ..Default::default()
+                };
+                builder
+                    .set_viewport(0, vec![viewport].into_iter().collect())
+                    .map_err(|e| {
+                        VulkanError::CommandBufferError(format!(
+                            "Failed to set shadow viewport: {}",
+                            e
+                        ))
+                    })?
+                    .bind_pipeline_graphics(self.shadow_pipeline.clone())
+                    .map_err(|e| {
+                        VulkanError::CommandBufferError(format!(
+                            "Failed to bind shadow pipeline: {}",
+                            e
+                        ))
+                    })?
+                    .bind_descriptor_sets(
+                        vulkano::pipeline::PipelineBindPoint::Graphics,
+                        self.shadow_pipeline.layout().clone(),
+                        0,
+                        frame.shadow_descriptor_set.clone(),
+                    )
+                    .map_err(|e| {
+                        VulkanError::CommandBufferError(format!(
+                            "Failed to bind shadow descriptor sets: {}",
+                            e
+                        ))
+                    })?
+                    .push_constants(
+                        self.shadow_pipeline.layout().clone(),
+                        0,
+                        shadow_vs::ShadowPass {
+                            view_projection: pass.view_projection.to_cols_array_2d(),
+                        },
+                    )
+                    .map_err(|e| {
+                        VulkanError::CommandBufferError(format!(
+                            "Failed to push shadow constants: {}",
+                            e
+                        ))
+                    })?
+                    .bind_vertex_buffers(0, vertex_buffer)
+                    .map_err(|e| {
+                        VulkanError::CommandBufferError(format!(
+                            "Failed to bind vertex buffers: {}",
+                            e
+                        ))
+                    })?
+                    .bind_index_buffer(index_buffer)
+                    .map_err(|e| {
+                        VulkanError::CommandBufferError(format!(
+                            "Failed to bind index buffer: {}",
+                            e
+                        ))
+                    })?;
+                self.draw_instanced(builder, draws)?;
+            }
+
+            builder.end_rendering().map_err(|e| {
+                VulkanError::CommandBufferError(format!("Failed to end shadow pass: {}", e))
+            })?;
+        }
+        Ok(())
+    }
+
+    // records the draws of a pass, the commands written on the host are drawn one by one
+    // when the device can not start indirect draws at a first instance other than 0, which
+    // the slot lists depend on
+    pub(crate) fn draw_instanced(
+        &self,
+        builder: &mut vulkano::command_buffer::AutoCommandBufferBuilder<
+            vulkano::command_buffer::PrimaryAutoCommandBuffer,
+        >,
+        draws: &DrawCommands,
+    ) -> Result<(), VulkanError> {
+        let map_err =
+            |e| VulkanError::CommandBufferError(format!("Failed to draw indexed indirect: {}", e));
+        let first_instance = self
+            .transfer_queue
+            .device()
+            .enabled_features()
+            .draw_indirect_first_instance;
+        unsafe {
+            match draws {
+                DrawCommands::Host { buffer, .. } if first_instance => {
+                    builder
+                        .draw_indexed_indirect(buffer.clone())
+                        .map_err(map_err)?;
+                }
+                DrawCommands::Host { commands, .. } => {
+                    for command in commands {
+                        builder
+                            .draw_indexed(
+                                command.index_count,
+                                command.instance_count,
+                                command.first_index,
+                                command.vertex_offset as i32,
+                                command.first_instance,
+                            )
+                            .map_err(map_err)?;
+                    }
+                }
+                DrawCommands::Culled {
+                    buffer,
+                    count: Some(count),
+                } => {
+                    builder
+                        .draw_indexed_indirect_count(
+                            buffer.clone(),
+                            count.clone(),
+                            buffer.len() as u32,
+                        )
+                        .map_err(map_err)?;
+                }
+                DrawCommands::Culled {
+                    buffer,
+                    count: None,
+                } => {
+                    builder
+                        .draw_indexed_indirect(buffer.clone())
+                        .map_err(map_err)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // records the cull shader, which adds the candidates inside the frustum to the instances
+    // of their groups and counts them, then packs the groups that kept any instance at the
+    // front of the draw buffer behind their count when the device can draw from a count
+    fn cull(
+        &self,
+        builder: &mut vulkano::command_buffer::AutoCommandBufferBuilder<
+            vulkano::command_buffer::PrimaryAutoCommandBuffer,
+        >,
+        frame: &mut FrameResources,
+        candidates: Vec<cs::Candidate>,
+        commands: Vec<vulkano::command_buffer::DrawIndexedIndirectCommand>,
+        frustum: &Frustum,
+    ) -> Result<Option<DrawCommands>, VulkanError> {
+        let Some(pipeline) = self.cull_pipeline.clone() else {
+            return Ok(None);
+        };
+        let pack = self
+            .transfer_queue
+            .device()
+            .enabled_features()
+            .draw_indirect_count;
+
+        // every candidate may survive, so the outputs are sized to all of them, there are
+        // never more groups than candidates
+        let capacity = candidates.len().max(1) as u64;
+        if frame
+            .instance_buffer
+            .as_ref()
+            .is_none_or(|buffer| buffer.len() < capacity)
+        {
+            let capacity = capacity.next_power_of_two();
+            let usage = vulkano::buffer::BufferUsage::STORAGE_BUFFER
+                | vulkano::buffer::BufferUsage::INDIRECT_BUFFER;
+            frame.instance_buffer = Some(
+                self.create_device_buffer(vulkano::buffer::BufferUsage::STORAGE_BUFFER, capacity)?,
+            );
+            frame.indirect_buffer = Some(self.create_device_buffer(usage, capacity)?);
+            frame.draw_buffer = Some(self.create_device_buffer(usage, capacity)?);
+            frame.descriptor_set = None;
+            frame.cull_descriptor_set = None;
+        }
+        if frame.count_buffer.is_none() {
+            frame.count_buffer = Some(self.create_device_buffer(
+                vulkano::buffer::BufferUsage::STORAGE_BUFFER
+                    | vulkano::buffer::BufferUsage::INDIRECT_BUFFER
+                    | vulkano::buffer::BufferUsage::TRANSFER_SRC,
+                2,
+            )?);
+            frame.count_readback = Some(
+                vulkano::buffer::Buffer::new_slice(
+                    self.memory_allocator.clone(),
+                    vulkano::buffer::BufferCreateInfo {
+                        usage: vulkano::buffer::BufferUsage::TRANSFER_DST,
+                        ..Default::default()
+                    },
+                    vulkano::memory::allocator::AllocationCreateInfo {
+                        memory_type_filter:
+                            vulkano::memory::allocator::MemoryTypeFilter::PREFER_HOST
+                                | vulkano::memory::allocator::MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                        ..Default::default()
+                    },
+                    2,
+                )
+                .map_err(|e| {
+                    VulkanError::BufferCreationError(format!(
+                        "Failed to create count readback: {}",
+                        e
+                    ))
+                })?,
+            );
+            frame.cull_descriptor_set = None;
+        }
+        if candidates.is_empty() {
+            return Ok(None);
+        }
+        if self.write_host_buffer(
+            &mut frame.candidate_buffer,
+            vulkano::buffer::BufferUsage::STORAGE_BUFFER,
+            &candidates,
+        )? {
+            frame.cull_descriptor_set = None;
+        }
+
+        let (
+            Some(candidate_buffer),
+            Some(group_buffer),
+            Some(instance_buffer),
+            Some(draw_buffer),
+            Some(count_buffer),
+            Some(count_readback),
+        ) = (
+            frame.candidate_buffer.clone(),
+            frame.indirect_buffer.clone(),
+            frame.instance_buffer.clone(),
+            frame.draw_buffer.clone(),
+            frame.count_buffer.clone(),
+            frame.count_readback.clone(),
+        )
+        else {
+            return Ok(None);
+        };
+
+        let descriptor_set = match frame.cull_descriptor_set.clone() {
+            Some(descriptor_set) => descriptor_set,
+            None => {
+                let descriptor_set = vulkano::descriptor_set::DescriptorSet::new(
+                    self.descriptor_set_allocator.clone(),
+                    pipeline
+                        .layout()
+                        .set_layouts()
+                        .first()
+                        .ok_or_else(|| {
+                            VulkanError::PipelineLayoutError(
+                                "No descriptor set layout found in cull pipeline".to_string(),
+                            )
+                        })?
+                        .clone(),
+                    [
+                        vulkano::descriptor_set::WriteDescriptorSet::buffer(0, candidate_buffer),
+                        vulkano::descriptor_set::WriteDescriptorSet::buffer(
+                            1,
+                            group_buffer.clone(),
+                        ),
+                        vulkano::descriptor_set::WriteDescriptorSet::buffer(2, instance_buffer),
+                        vulkano::descriptor_set::WriteDescriptorSet::buffer(
+                            3,
+                            count_buffer.clone(),
+                        ),
+                        vulkano::descriptor_set::WriteDescriptorSet::buffer(4, draw_buffer.clone()),
+                    ],
+                    [],
+                )
+                .map_err(|e| {
+                    VulkanError::CommandBufferError(format!(
+                        "Failed to create cull descriptor set: {}",
+                        e
+                    ))
+                })?;
+                frame.cull_descriptor_set = Some(descriptor_set.clone());
+                descriptor_set
+            }
+        };
+
+        // the groups start without instances, the shader counts them up
+        let group_count = commands.len() as u32;
+        let groups = commands
+            .into_iter()
+            .enumerate()
+            .map(|(index, command)| (index as u64, command))
+            .collect::<Vec<_>>();
+        self.patch(builder, Some(group_buffer.clone()), &groups)?;
+
+        builder
+            .fill_buffer(count_buffer.clone(), 0)
+            .map_err(|e| {
+                VulkanError::CommandBufferError(format!("Failed to reset cull counts: {}", e))
+            })?
+            .bind_pipeline_compute(pipeline.clone())
+            .map_err(|e| {
+                VulkanError::CommandBufferError(format!("Failed to bind cull pipeline: {}", e))
+            })?
+            .bind_descriptor_sets(
+                vulkano::pipeline::PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                descriptor_set,
+            )
+            .map_err(|e| {
+                VulkanError::CommandBufferError(format!(
+                    "Failed to bind cull descriptor set: {}",
+                    e
+                ))
+            })?;
+
+        // the groups are only packed once every candidate was counted, the second dispatch
+        // waits for the first
+        let local_size = 64;
+        let passes = [(0, candidates.len() as u32), (1, group_count)];
+        for (pass, count) in passes.into_iter().take(if pack { 2 } else { 1 }) {
+            builder
+                .push_constants(
+                    pipeline.layout().clone(),
+                    0,
+                    cs::Cull {
+                        planes: frustum.planes.map(|plane| plane.to_array()),
+                        candidate_count: candidates.len() as u32,
+                        group_count,
+                        pack: pass,
+                    },
+                )
+                .map_err(|e| {
+                    VulkanError::CommandBufferError(format!("Failed to push cull constants: {}", e))
+                })?;
+            unsafe {
+                builder
+                    .dispatch([count.div_ceil(local_size), 1, 1])
+                    .map_err(|e| {
+                        VulkanError::CommandBufferError(format!(
+                            "Failed to dispatch culling: {}",
+                            e
+                        ))
+                    })?;
+            }
+        }
+        // read back once the frame is done, for the stats
+        builder
+            .copy_buffer(vulkano::command_buffer::CopyBufferInfo::buffers(
+                count_buffer.clone(),
+                count_readback,
+            ))
+            .map_err(|e| {
+                VulkanError::CommandBufferError(format!("Failed to read back cull counts: {}", e))
+            })?;
+
+        let groups = 0..group_count as u64;
+        Ok(Some(if pack {
+            DrawCommands::Culled {
+                buffer: draw_buffer.slice(groups),
+                count: Some(count_buffer.index(1)),
+            }
+        } else {
+            DrawCommands::Culled {
+                buffer: group_buffer.slice(groups),
+                count: None,
+            }
+        }))
+    }
+
+    fn create_descriptor_set(
+        &self,
+        frame: &FrameResources,
+        layout: Arc<vulkano::descriptor_set::layout::DescriptorSetLayout>,
+    ) -> Result<Arc<vulkano::descriptor_set::DescriptorSet>, VulkanError> {
+        let buffer = |binding, buffer: Option<vulkano::buffer::Subbuffer<[u8]>>| {
+            buffer
+                .map(|buffer| vulkano::descriptor_set::WriteDescriptorSet::buffer(binding, buffer))
+                .ok_or_else(|| {
+                    VulkanError::SceneError(format!("Scene buffer {} was never uploaded", binding))
+                })
+        };
+
+        vulkano::descriptor_set::DescriptorSet::new(
+            self.descriptor_set_allocator.clone(),
+            layout,
+            [
+                buffer(
+                    0,
+                    frame
+                        .uniform_buffer
+                        .clone()
+                        .map(|buffer| buffer.into_bytes()),
+                )?,
+                buffer(
+                    1,
+                    frame
+                        .matrix_buffer
+                        .clone()
+                        .map(|buffer| buffer.into_bytes()),
+                )?,
+                buffer(
+                    2,
+                    frame
+                        .material_buffer
+                        .clone()
+                        .map(|buffer| buffer.into_bytes()),
+                )?,
+                buffer(
+                    3,
+                    frame
+                        .normal_buffer
+                        .clone()
+                        .map(|buffer| buffer.into_bytes()),
+                )?,
+                buffer(
+                    4,
+                    frame.light_buffer.clone().map(|buffer| buffer.into_bytes()),
+                )?,
+                buffer(
+                    5,
+                    frame
+                        .instance_buffer
+                        .clone()
+                        .map(|buffer| buffer.into_bytes()),
+                )?,
+                buffer(
+                    6,
+                    frame
+                        .shadow_uniform_buffer
+                        .clone()
+                        .map(|buffer| buffer.into_bytes()),
+                )?,
+                vulkano::descriptor_set::WriteDescriptorSet::image_view_sampler(
+                    7,
+                    frame
+                        .shadow_maps
+                        .as_ref()
+                        .map(|maps| maps.array_view.clone())
+                        .ok_or_else(|| {
+                            VulkanError::SceneError("Shadow maps were never created".to_string())
+                        })?,
+                    self.shadow_sampler.clone(),
+                ),
+                buffer(
+                    8,
+                    frame
+                        .point_shadow_uniform_buffer
+                        .clone()
+                        .map(|buffer| buffer.into_bytes()),
+                )?,
+                vulkano::descriptor_set::WriteDescriptorSet::image_view_sampler(
+                    9,
+                    frame
+                        .point_shadow_maps
+                        .as_ref()
+                        .map(|maps| maps.array_view.clone())
+                        .ok_or_else(|| {
+                            VulkanError::SceneError(
+                                "Point shadow maps were never created".to_string(),
+                            )
+                        })?,
+                    self.shadow_sampler.clone(),
+                ),
+            ],
+            [],
+        )
+        .map_err(|e| {
+            VulkanError::CommandBufferError(format!("Failed to create descriptor set: {}", e))
+        })
+    }
+
+    fn patch_instances(
+        &self,
+        builder: &mut vulkano::command_buffer::AutoCommandBufferBuilder<
+            vulkano::command_buffer::PrimaryAutoCommandBuffer,
+        >,
+        frame: &FrameResources,
+        slots: &[usize],
+        transforms: bool,
+        materials: bool,
+    ) -> Result<(), VulkanError> {
+        // slots of instances removed since the change was recorded are gone with them
+        let slots = slots
+            .iter()
+            .copied()
+            .filter(|&slot| slot < self.instances.len())
+            .collect::<Vec<_>>();
+        if slots.is_empty() {
+            return Ok(());
+        }
+
+        if transforms {
+            let matrices = slots
+                .iter()
+                .map(|&slot| (slot as u64, self.instances[slot].model_matrix))
+                .collect::<Vec<_>>();
+            // world space normals, the shader applies the view rotation
+            let normals = matrices
+                .iter()
+                .map(|&(slot, model_matrix)| (slot, model_matrix.inverse().transpose()))
+                .collect::<Vec<_>>();
+            self.patch(builder, frame.matrix_buffer.clone(), &matrices)?;
+            self.patch(builder, frame.normal_buffer.clone(), &normals)?;
+        }
+        if materials {
+            let materials = slots
+                .iter()
+                .map(|&slot| (slot as u64, self.instances[slot].material.clone()))
+                .collect::<Vec<_>>();
+            self.patch(builder, frame.material_buffer.clone(), &materials)?;
+        }
+        Ok(())
+    }
+
+    fn upload_scene_geometry(&mut self, scene: &Scene) -> Result<(), VulkanError> {
+        self.upload_geometry(
+            scene
+                .objects
+                .values()
+                .flat_map(|object| &object.submeshes)
+                .filter(|submesh| !submesh.vertices.is_empty() && !submesh.indices.is_empty()),
+        )
+    }
+
+    // keeps the geometry of the given submeshes on the gpu, whether they are drawn or not,
+    // new geometry is uploaded through the transfer queue behind the used front of the
+    // buffers, which the frames in flight do not read, geometry no submesh uses any more is
+    // dropped and its space reclaimed once the buffers have to grow, kept geometry is then
+    // copied over on the gpu
+    fn upload_geometry<'a>(
+        &mut self,
+        submeshes: impl Iterator<Item = &'a SubMesh>,
+    ) -> Result<(), VulkanError> {
+        let mut used = HashMap::<u64, &Geometry>::new();
+        for submesh in submeshes {
+            used.insert(submesh.geometry.revision(), &*submesh.geometry);
+        }
+        self.geometry
+            .retain(|revision, _| used.contains_key(revision));
+        let new = used
+            .iter()
+            .filter(|(revision, _)| !self.geometry.contains_key(revision))
+            .map(|(&revision, &data)| (revision, data))
+            .collect::<Vec<_>>();
+        if new.is_empty() {
+            return Ok(());
+        }
+
+        let index_len = |geometry: &Geometry| {
+            geometry.indices.len() as u64
+                + geometry
+                    .lods
+                    .iter()
+                    .map(|lod| lod.indices.len() as u64)
+                    .sum::<u64>()
+        };
+        let new_vertices = new
+            .iter()
+            .map(|(_, data)| data.vertices.len() as u64)
+            .sum::<u64>();
+        let new_indices = new.iter().map(|(_, data)| index_len(data)).sum::<u64>();
+        let used_vertices = self.vertex_buffer.as_ref().map_or(0, |buffer| buffer.len());
+        let used_indices = self.index_buffer.as_ref().map_or(0, |buffer| buffer.len());
+
+        let mut transfer = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
+            self.command_buffer_allocator.clone(),
+            self.transfer_queue.queue_family_index(),
+            vulkano::command_buffer::CommandBufferUsage::OneTimeSubmit,
+        )
+        .map_err(|e| {
+            VulkanError::CommandBufferError(format!(
+                "Failed to create transfer command buffer builder: {}",
+                e
+            ))
+        })?;
+
+        let (vertex_storage, index_storage, first_vertex, first_index) =
+            match (self.vertex_storage.clone(), self.index_storage.clone()) {
+                (Some(vertex_storage), Some(index_storage))
+                    if vertex_storage.len() >= used_vertices + new_vertices
+                        && index_storage.len() >= used_indices + new_indices =>
+                {
+                    (vertex_storage, index_storage, used_vertices, used_indices)
+                }
+                (old_vertex_storage, old_index_storage) => {
+                    // repacked into larger buffers, with room to grow before the next repack
+                    let kept_vertices = self
+                        .geometry
+                        .keys()
+                        .map(|revision| used[revision].vertices.len() as u64)
+                        .sum::<u64>();
+                    let kept_indices = self
+                        .geometry
+                        .keys()
+                        .map(|revision| index_len(used[revision]))
+                        .sum::<u64>();
+                    let vertex_storage = self.create_device_buffer::<Vertex>(
+                        vulkano::buffer::BufferUsage::VERTEX_BUFFER
+                            | vulkano::buffer::BufferUsage::TRANSFER_SRC,
+                        (kept_vertices + new_vertices).max(1).next_power_of_two(),
+                    )?;
+                    let index_storage = self.create_device_buffer::<u32>(
+                        vulkano::buffer::BufferUsage::INDEX_BUFFER
+                            | vulkano::buffer::BufferUsage::TRANSFER_SRC,
+                        (kept_indices + new_indices).max(1).next_power_of_two(),
+                    )?;
+
+                    let mut vertex_regions = Vec::new();
+                    let mut index_regions = Vec::new();
+                    let mut vertex_count = 0;
+                    let mut index_count = 0;
+                    for (revision, old) in &mut self.geometry {
+                        let data = used[revision];
+                        let vertex_len = data.vertices.len() as u64;
+                        let index_len = index_len(data);
+                        vertex_regions.push(vulkano::command_buffer::BufferCopy {
+                            src_offset: old.vertex_offset as u64,
+                            dst_offset: vertex_count,
+                            size: vertex_len,
+                            ..Default::default()
+                        });
+                        // the lists of one geometry are back to back, so they move as one block
+                        let old_first_index = old.index_ranges[0].0 as u64;
+                        index_regions.push(vulkano::command_buffer::BufferCopy {
+                            src_offset: old_first_index,
+                            dst_offset: index_count,
+                            size: index_len,
+                            ..Default::default()
+                        });
+                        old.vertex_offset = vertex_count as u32;
+                        for (first, _) in &mut old.index_ranges {
+                            *first = (*first as u64 - old_first_index + index_count) as u32;
+                        }
+                        vertex_count += vertex_len;
+                        index_count += index_len;
+                    }
+
+                    if let (Some(old_vertex_storage), Some(old_index_storage)) =
+                        (old_vertex_storage, old_index_storage)
+                        && !vertex_regions.is_empty()
+                    {
+                        transfer
+                            .copy_buffer(vulkano::command_buffer::CopyBufferInfoTyped {
+                                regions: vertex_regions.into(),
+                                ..vulkano::command_buffer::CopyBufferInfoTyped::buffers(
+                                    old_vertex_storage,
+                                    vertex_storage.clone(),
+                                )
+                            })
+                            .map_err(|e| {
+                                VulkanError::CommandBufferError(format!(
+                                    "Failed to copy vertices: {}",
+                                    e
+                                ))
+                            })?
+                            .copy_buffer(vulkano::command_buffer::CopyBufferInfoTyped {
+                                regions: index_regions.into(),
+                                ..vulkano::command_buffer::CopyBufferInfoTyped::buffers(
+                                    old_index_storage,
+                                    index_storage.clone(),
+                                )
+                            })
+                            .map_err(|e| {
+                                VulkanError::CommandBufferError(format!(
+                                    "Failed to copy indices: {}",
+                                    e
+                                ))
+                            })?;
+                    }
+                    (vertex_storage, index_storage, vertex_count, index_count)
+                }
+            };
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (revision, data) in new {
+            let vertex_offset = (first_vertex + vertices.len() as u64) as u32;
+            vertices.extend_from_slice(&data.vertices);
+
+            let mut index_ranges = Vec::with_capacity(data.lods.len() + 1);
+            for list in
+                std::iter::once(&data.indices).chain(data.lods.iter().map(|lod| &lod.indices))
+            {
+                index_ranges.push((
+                    (first_index + indices.len() as u64) as u32,
+                    list.len() as u32,
+                ));
+                indices.extend_from_slice(list);
+            }
+
+            self.geometry.insert(
+                revision,
+                GpuGeometry {
+                    vertex_offset,
+                    index_ranges,
+                },
+            );
+        }
+        let vertex_count = first_vertex + vertices.len() as u64;
+        let index_count = first_index + indices.len() as u64;
+        self.copy_from_staging(
+            &mut transfer,
+            vertex_storage.clone(),
+            first_vertex,
+            vertices,
+        )?;
+        self.copy_from_staging(&mut transfer, index_storage.clone(), first_index, indices)?;
+        let transfer = transfer.build().map_err(|e| {
+            VulkanError::CommandBufferError(format!(
+                "Failed to build transfer command buffer: {}",
+                e
+            ))
+        })?;
+
+        // waited for right away, uploads only happen when objects are added or changed and
+        // the frames recorded next then see the whole geometry
+        vulkano::sync::now(self.transfer_queue.device().clone())
+            .then_execute(self.transfer_queue.clone(), transfer)
+            .map_err(|e| {
+                VulkanError::CommandBufferError(format!(
+                    "Failed to execute transfer command buffer: {}",
+                    e
+                ))
+            })?
+            .then_signal_fence_and_flush()
+            .map_err(|e| {
+                VulkanError::SynchronizationError(format!("Failed to flush geometry upload: {}", e))
+            })?
+            .wait(None)
+            .map_err(|e| {
+                VulkanError::SynchronizationError(format!(
+                    "Failed to wait for geometry upload: {}",
+                    e
+                ))
+            })?;
+
+        // a subbuffer can not be empty, nothing is drawn without geometry
+        self.vertex_buffer =
+            (vertex_count > 0).then(|| vertex_storage.clone().slice(0..vertex_count));
+        self.index_buffer = (index_count > 0).then(|| index_storage.clone().slice(0..index_count));
+        self.vertex_storage = Some(vertex_storage);
+        self.index_storage = Some(index_storage);
+        Ok(())
+    }
+
+    fn copy_from_staging<T: vulkano::buffer::BufferContents>(
+        &self,
+        builder: &mut vulkano::command_buffer::AutoCommandBufferBuilder<
+            vulkano::command_buffer::PrimaryAutoCommandBuffer,
+        >,
+        destination: vulkano::buffer::Subbuffer<[T]>,
+        offset: u64,
+        values: Vec<T>,
+    ) -> Result<(), VulkanError> {
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        let len = values.len() as u64;
+        let staging = vulkano::buffer::Buffer::from_iter(
+            self.memory_allocator.clone(),
+            vulkano::buffer::BufferCreateInfo {
+                usage: vulkano::buffer::BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            host_allocation_info(),
+            values,
+        )
+        .map_err(|e| {
+            VulkanError::BufferCreationError(format!("Failed to create staging buffer: {}", e))
+        })?;
+
+        builder
+            .copy_buffer(vulkano::command_buffer::CopyBufferInfoTyped {
+                regions: [vulkano::command_buffer::BufferCopy {
+                    src_offset: 0,
+                    dst_offset: offset,
+                    size: len,
+                    ..Default::default()
+                }]
+                .into(),
+                ..vulkano::command_buffer::CopyBufferInfoTyped::buffers(staging, destination)
+            })
+            .map_err(|e| {
+                VulkanError::CommandBufferError(format!("Failed to copy staging buffer: {}", e))
+            })?;
+        Ok(())
+    }
+
+    // stages the values and copies each to its element offset, neighbouring offsets
+    // are copied as one region
+    fn patch<T: vulkano::buffer::BufferContents + Clone>(
+        &self,
+        builder: &mut vulkano::command_buffer::AutoCommandBufferBuilder<
+            vulkano::command_buffer::PrimaryAutoCommandBuffer,
+        >,
+        destination: Option<vulkano::buffer::Subbuffer<[T]>>,
+        values: &[(u64, T)],
+    ) -> Result<(), VulkanError> {
+        let Some(destination) = destination else {
+            return Ok(());
+        };
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        let staging = self
+            .upload_allocator
+            .allocate_slice::<T>(values.len() as u64)
+            .map_err(|e| {
+                VulkanError::BufferCreationError(format!("Failed to allocate staging: {}", e))
+            })?;
+        {
+            let mut staged = staging.write().map_err(|e| {
+                VulkanError::BufferCreationError(format!("Failed to write staging: {}", e))
+            })?;
+            for (staged, (_, value)) in staged.iter_mut().zip(values) {
+                *staged = value.clone();
+            }
+        }
+
+        let mut regions = Vec::<vulkano::command_buffer::BufferCopy>::new();
+        for (index, &(offset, _)) in values.iter().enumerate() {
+            match regions.last_mut() {
+                Some(region)
+                    if region.src_offset + region.size == index as u64
+                        && region.dst_offset + region.size == offset =>
+                {
+                    region.size += 1;
+                }
+                _ => regions.push(vulkano::command_buffer::BufferCopy {
+                    src_offset: index as u64,
+                    dst_offset: offset,
+                    size: 1,
+                    ..Default::default()
+                }),
+            }
+        }
+
+        builder
+            .copy_buffer(vulkano::command_buffer::CopyBufferInfoTyped {
+                regions: regions.into(),
+                ..vulkano::command_buffer::CopyBufferInfoTyped::buffers(staging, destination)
+            })
+            .map_err(|e| {
+                VulkanError::CommandBufferError(format!("Failed to patch scene buffer: {}", e))
+            })?;
+        Ok(())
+    }
+
+    // writes the values to the front of a host visible buffer of the frame, returns whether
+    // a new buffer had to be created because the old one was too small
+    fn write_host_buffer<T: vulkano::buffer::BufferContents + Clone>(
+        &self,
+        buffer: &mut Option<vulkano::buffer::Subbuffer<[T]>>,
+        usage: vulkano::buffer::BufferUsage,
+        values: &[T],
+    ) -> Result<bool, VulkanError> {
+        let len = values.len() as u64;
+        let replaced = buffer.as_ref().is_none_or(|buffer| buffer.len() < len);
+        if replaced {
+            *buffer = Some(
+                vulkano::buffer::Buffer::new_slice(
+                    self.memory_allocator.clone(),
+                    vulkano::buffer::BufferCreateInfo {
+                        usage,
+                        ..Default::default()
+                    },
+                    host_allocation_info(),
+                    len.max(1).next_power_of_two(),
+                )
+                .map_err(|e| {
+                    VulkanError::BufferCreationError(format!(
+                        "Failed to create frame buffer: {}",
+                        e
+                    ))
+                })?,
+            );
+        }
+
+        if let Some(buffer) = buffer {
+            buffer.write().map_err(|e| {
+                VulkanError::BufferCreationError(format!("Failed to write frame data: {}", e))
+            })?[..values.len()]
+                .clone_from_slice(values);
+        }
+        Ok(replaced)
+    }
+
+    // keeps the buffer if it is large enough, its contents are not carried over otherwise
+    fn reserve<T: vulkano::buffer::BufferContents>(
+        &self,
+        buffer: Option<vulkano::buffer::Subbuffer<[T]>>,
+        usage: vulkano::buffer::BufferUsage,
+        len: u64,
+    ) -> Result<vulkano::buffer::Subbuffer<[T]>, VulkanError> {
+        match buffer {
+            Some(buffer) if buffer.len() >= len => Ok(buffer),
+            _ => self.create_device_buffer(usage, len.next_power_of_two()),
+        }
+    }
+
+    fn create_device_buffer<T: vulkano::buffer::BufferContents>(
+        &self,
+        usage: vulkano::buffer::BufferUsage,
+        len: u64,
+    ) -> Result<vulkano::buffer::Subbuffer<[T]>, VulkanError> {
+        let sharing = if self.queue_family_indices.len() > 1 {
+            vulkano::sync::Sharing::Concurrent(self.queue_family_indices.iter().copied().collect())
+        } else {
+            vulkano::sync::Sharing::Exclusive
+        };
+
+        vulkano::buffer::Buffer::new_slice(
+            self.memory_allocator.clone(),
+            vulkano::buffer::BufferCreateInfo {
+                usage: usage | vulkano::buffer::BufferUsage::TRANSFER_DST,
+                sharing,
+                ..Default::default()
+            },
+            vulkano::memory::allocator::AllocationCreateInfo {
+                memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+            len,
+        )
+        .map_err(|e| {
+            VulkanError::BufferCreationError(format!("Failed to create scene buffer: {}", e))
+        })
+    }
+}
+
+// memory the cpu writes once and the gpu reads
+fn host_allocation_info() -> vulkano::memory::allocator::AllocationCreateInfo {
+    vulkano::memory::allocator::AllocationCreateInfo {
+        memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_HOST
+            | vulkano::memory::allocator::MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+        ..Default::default()
+    }
+}
+
+// a removed material leaves a stale handle behind, drawn like no material
+fn submesh_material(scene: &Scene, submesh: &SubMesh) -> GpuMaterials {
+    submesh
+        .material
+        .and_then(|material| scene.materials.get(material))
+        .map_or_else(GpuMaterials::default, |material| {
+            material.properties.clone()
+        })
+}
@@ -0,0 +1,445 @@
+use glam::{Mat3, Mat4, Vec3};
+
+use crate::graphics::raycast::Ray;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::EMPTY
+    }
+}
+
+impl Aabb {
+    // an inverted box, so that any point or box merged into it replaces it
+    pub const EMPTY: Aabb = Aabb {
+        min: Vec3::splat(f32::MAX),
+        max: Vec3::splat(f32::MIN),
+    };
+
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut aabb = Aabb::EMPTY;
+        for point in points {
+            aabb.extend(point);
+        }
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn extend(&mut self, point: Vec3) {
+        self.min = self.min.min(point);
+        self.max = self.max.max(point);
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        self.size() * 0.5
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    // slab test, returns the distance at which the ray enters the box
+    pub fn intersect_ray(&self, ray: &Ray, max_distance: f32) -> Option<f32> {
+        let t0 = (self.min - ray.origin) * ray.inverse_direction;
+        let t1 = (self.max - ray.origin) * ray.inverse_direction;
+
+        let t_near = t0.min(t1).max_element().max(0.0);
+        let t_far = t0.max(t1).min_element().min(max_distance);
+
+        if t_near <= t_far { Some(t_near) } else { None }
+    }
+
+    // smallest axis aligned box enclosing this box after the transform (Arvo's method)
+    pub fn transform(&self, transform: &Mat4) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+
+        let center = transform.transform_point3(self.center());
+        let half_extents = self.half_extents();
+        let matrix = Mat3::from_mat4(*transform);
+        let world_half_extents = Vec3::new(
+            matrix.row(0).abs().dot(half_extents),
+            matrix.row(1).abs().dot(half_extents),
+            matrix.row(2).abs().dot(half_extents),
+        );
+
+        Aabb {
+            min: center - world_half_extents,
+            max: center + world_half_extents,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        BoundingSphere { center, radius }
+    }
+
+    // Ritter's algorithm: a sphere through the two most distant points, grown to fit the rest
+    pub fn from_points(points: &[Vec3]) -> Self {
+        let Some(&first) = points.first() else {
+            return BoundingSphere::default();
+        };
+
+        let farthest_from = |from: Vec3| {
+            points
+                .iter()
+                .copied()
+                .max_by(|a, b| {
+                    a.distance_squared(from)
+                        .total_cmp(&b.distance_squared(from))
+                })
+                .unwrap_or(from)
+        };
+
+        let a = farthest_from(first);
+        let b = farthest_from(a);
+
+        let mut center = (a + b) * 0.5;
+        let mut radius = a.distance(b) * 0.5;
+
+        for &point in points {
+            let distance = point.distance(center);
+            if distance > radius {
+                let new_radius = (radius + distance) * 0.5;
+                center += (point - center) * ((new_radius - radius) / distance);
+                radius = new_radius;
+            }
+        }
+
+        BoundingSphere { center, radius }
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        point.distance_squared(self.center) <= self.radius * self.radius
+    }
+
+    pub fn transform(&self, transform: &Mat4) -> BoundingSphere {
+        let matrix = Mat3::from_mat4(*transform);
+        let max_scale = matrix
+            .x_axis
+            .length()
+            .max(matrix.y_axis.length())
+            .max(matrix.z_axis.length());
+
+        BoundingSphere {
+            center: transform.transform_point3(self.center),
+            radius: self.radius * max_scale,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obb {
+    pub center: Vec3,
+    // unit length box axes stored as the matrix columns
+    pub axes: Mat3,
+    pub half_extents: Vec3,
+}
+
+impl Default for Obb {
+    fn default() -> Self {
+        Obb {
+            center: Vec3::ZERO,
+            axes: Mat3::IDENTITY,
+            half_extents: Vec3::ZERO,
+        }
+    }
+}
+
+impl Obb {
+    pub fn from_aabb(aabb: &Aabb) -> Self {
+        if aabb.is_empty() {
+            return Obb::default();
+        }
+
+        Obb {
+            center: aabb.center(),
+            axes: Mat3::IDENTITY,
+            half_extents: aabb.half_extents(),
+        }
+    }
+
+    // fits the box to the principal axes of the point cloud, falling back to the
+    // axis aligned box when that one is tighter
+    pub fn from_points(points: &[Vec3]) -> Self {
+        if points.is_empty() {
+            return Obb::default();
+        }
+
+        let mean = points.iter().copied().sum::<Vec3>() / points.len() as f32;
+
+        let mut covariance = [[0.0f32; 3]; 3];
+        for point in points {
+            let d = (*point - mean).to_array();
+            for row in 0..3 {
+                for col in 0..3 {
+                    covariance[row][col] += d[row] * d[col];
+                }
+            }
+        }
+
+        let axes = orthonormalize(symmetric_eigenvectors(covariance));
+
+        let mut local = Aabb::EMPTY;
+        for point in points {
+            local.extend(axes.transpose() * (*point - mean));
+        }
+
+        let pca_box = Obb {
+            center: mean + axes * local.center(),
+            axes,
+            half_extents: local.half_extents(),
+        };
+
+        let aligned_box = Obb::from_aabb(&Aabb::from_points(points.iter().copied()));
+
+        if aligned_box.volume() <= pca_box.volume() {
+            aligned_box
+        } else {
+            pca_box
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        8.0 * self.half_extents.x * self.half_extents.y * self.half_extents.z
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let local = Aabb::new(-self.half_extents, self.half_extents).corners();
+        local.map(|corner| self.center + self.axes * corner)
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        let local = self.axes.transpose() * (point - self.center);
+        local.abs().cmple(self.half_extents).all()
+    }
+
+    pub fn to_aabb(&self) -> Aabb {
+        Aabb::from_points(self.corners())
+    }
+
+    // a non uniform scale shears the box into a parallelepiped, the new box keeps the
+    // orthonormalized axes and grows its half extents to the projections of all three
+    // scaled edges, so it still contains every corner
+    pub fn transform(&self, transform: &Mat4) -> Obb {
+        let matrix = Mat3::from_mat4(*transform) * self.axes;
+        let axes = orthonormalize(Mat3::from_cols(
+            matrix.x_axis.normalize_or_zero(),
+            matrix.y_axis.normalize_or_zero(),
+            matrix.z_axis.normalize_or_zero(),
+        ));
+        let edges = [
+            matrix.x_axis * self.half_extents.x,
+            matrix.y_axis * self.half_extents.y,
+            matrix.z_axis * self.half_extents.z,
+        ];
+        let project = |axis: Vec3| edges.iter().map(|edge| axis.dot(*edge).abs()).sum::<f32>();
+
+        Obb {
+            center: transform.transform_point3(self.center),
+            axes,
+            half_extents: Vec3::new(
+                project(axes.x_axis),
+                project(axes.y_axis),
+                project(axes.z_axis),
+            ),
+        }
+    }
+}
+
+// all the bounding volumes of a piece of geometry, computed together from its vertices
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Bounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+    pub obb: Obb,
+}
+
+impl Bounds {
+    pub fn from_points(points: &[Vec3]) -> Self {
+        Bounds {
+            aabb: Aabb::from_points(points.iter().copied()),
+            sphere: BoundingSphere::from_points(points),
+            obb: Obb::from_points(points),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aabb.is_empty()
+    }
+
+    pub fn transform(&self, transform: &Mat4) -> Bounds {
+        Bounds {
+            aabb: self.aabb.transform(transform),
+            sphere: self.sphere.transform(transform),
+            obb: self.obb.transform(transform),
+        }
+    }
+}
+
+// Jacobi eigenvalue iteration for a symmetric 3x3 matrix, returns the eigenvectors as columns
+fn symmetric_eigenvectors(matrix: [[f32; 3]; 3]) -> Mat3 {
+    let mut a = matrix;
+    let mut v = [[1.0f32, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+    for _ in 0..32 {
+        // pick the largest off-diagonal element
+        let (p, q) = [(0, 1), (0, 2), (1, 2)]
+            .into_iter()
+            .max_by(|&(a0, b0), &(a1, b1)| a[a0][b0].abs().total_cmp(&a[a1][b1].abs()))
+            .unwrap_or((0, 1));
+
+        if a[p][q].abs() < 1e-9 {
+            break;
+        }
+
+        let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+        let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+        let c = 1.0 / (t * t + 1.0).sqrt();
+        let s = t * c;
+
+        for row in a.iter_mut() {
+            let akp = row[p];
+            let akq = row[q];
+            row[p] = c * akp - s * akq;
+            row[q] = s * akp + c * akq;
+        }
+        let (row_p, row_q) = (a[p], a[q]);
+        a[p] = std::array::from_fn(|k| c * row_p[k] - s * row_q[k]);
+        a[q] = std::array::from_fn(|k| s * row_p[k] + c * row_q[k]);
+        for row in v.iter_mut() {
+            let vkp = row[p];
+            let vkq = row[q];
+            row[p] = c * vkp - s * vkq;
+            row[q] = s * vkp + c * vkq;
+        }
+    }
+
+    Mat3::from_cols(
+        Vec3::new(v[0][0], v[1][0], v[2][0]),
+        Vec3::new(v[0][1], v[1][1], v[2][1]),
+        Vec3::new(v[0][2], v[1][2], v[2][2]),
+    )
+}
+
+// Gram-Schmidt, keeping the result right handed
+fn orthonormalize(axes: Mat3) -> Mat3 {
+    let x = axes.x_axis.try_normalize().unwrap_or(Vec3::X);
+    let y = (axes.y_axis - x * x.dot(axes.y_axis))
+        .try_normalize()
+        .unwrap_or_else(|| x.any_orthonormal_vector());
+    let z = x.cross(y);
+    Mat3::from_cols(x, y, z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Quat;
+
+    // points on the surface and inside of a long thin box turned away from the axes
+    fn points() -> Vec<Vec3> {
+        let rotation = Quat::from_euler(glam::EulerRot::XYZ, 0.4, 0.7, 0.2);
+        let mut points = Vec::new();
+        for x in -4..=4 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let local = Vec3::new(x as f32, y as f32 * 0.3, z as f32 * 0.1);
+                    points.push(rotation * local + Vec3::new(1.0, -2.0, 0.5));
+                }
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn volumes_contain_their_points() {
+        let points = points();
+        let bounds = Bounds::from_points(&points);
+
+        for point in &points {
+            assert!(bounds.aabb.contains_point(*point));
+            assert!(bounds.sphere.radius >= (*point - bounds.sphere.center).length() - 1e-4);
+            let local = bounds.obb.axes.transpose() * (*point - bounds.obb.center);
+            assert!(local.abs().cmple(bounds.obb.half_extents + 1e-4).all());
+        }
+        // the box follows the rotation instead of the axes
+        assert!(bounds.obb.volume() < bounds.aabb.size().element_product() * 0.5);
+    }
+
+    #[test]
+    fn transformed_volumes_contain_transformed_points() {
+        let points = points();
+        let bounds = Bounds::from_points(&points);
+        // the non uniform scale is not aligned to the axes of the box, which shears it
+        let transform = Mat4::from_scale_rotation_translation(
+            Vec3::new(3.0, 0.5, 1.5),
+            Quat::from_rotation_y(0.3),
+            Vec3::new(-1.0, 4.0, 2.0),
+        );
+        let transformed = bounds.transform(&transform);
+
+        let obb = transformed.obb;
+        for point in &points {
+            let point = transform.transform_point3(*point);
+            let aabb = transformed.aabb;
+            assert!(point.cmpge(aabb.min - 1e-3).all() && point.cmple(aabb.max + 1e-3).all());
+            let sphere = transformed.sphere;
+            assert!(sphere.radius >= (point - sphere.center).length() - 1e-3);
+        }
+        // the corners of the sheared box are the farthest from its center
+        for corner in bounds.obb.corners() {
+            let local = obb.axes.transpose() * (transform.transform_point3(corner) - obb.center);
+            assert!(local.abs().cmple(obb.half_extents + 1e-3).all());
+        }
+    }
+}
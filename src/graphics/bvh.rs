@@ -0,0 +1,247 @@
+use glam::Vec3;
+
+use crate::graphics::{bounds::Aabb, raycast::Ray};
+
+const MAX_LEAF_SIZE: usize = 4;
+const SAH_BINS: usize = 12;
+
+#[derive(Debug, Clone)]
+struct BvhNode {
+    bounds: Aabb,
+    // first child for inner nodes (the second one follows it), first primitive for leaves
+    first: u32,
+    // number of primitives, zero for inner nodes
+    count: u32,
+}
+
+// bounding volume hierarchy over arbitrary primitives, built from their bounding boxes
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    primitive_indices: Vec<u32>,
+}
+
+impl Bvh {
+    pub fn build(primitive_bounds: &[Aabb]) -> Self {
+        if primitive_bounds.is_empty() {
+            return Bvh::default();
+        }
+
+        let centroids = primitive_bounds
+            .iter()
+            .map(|bounds| bounds.center())
+            .collect::<Vec<Vec3>>();
+
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(primitive_bounds.len() * 2),
+            primitive_indices: (0..primitive_bounds.len() as u32).collect(),
+        };
+
+        bvh.nodes.push(BvhNode {
+            bounds: Aabb::EMPTY,
+            first: 0,
+            count: primitive_bounds.len() as u32,
+        });
+
+        let mut stack = vec![0usize];
+        while let Some(node_index) = stack.pop() {
+            let first = bvh.nodes[node_index].first as usize;
+            let count = bvh.nodes[node_index].count as usize;
+            let range = first..first + count;
+
+            let bounds = bvh.primitive_indices[range.clone()]
+                .iter()
+                .fold(Aabb::EMPTY, |bounds, &primitive| {
+                    bounds.union(&primitive_bounds[primitive as usize])
+                });
+            bvh.nodes[node_index].bounds = bounds;
+
+            if count <= MAX_LEAF_SIZE {
+                continue;
+            }
+
+            let Some(split) = bvh.find_split(range.clone(), primitive_bounds, &centroids) else {
+                continue;
+            };
+
+            let left_count = split - first;
+            let left_index = bvh.nodes.len();
+            bvh.nodes.push(BvhNode {
+                bounds: Aabb::EMPTY,
+                first: first as u32,
+                count: left_count as u32,
+            });
+            bvh.nodes.push(BvhNode {
+                bounds: Aabb::EMPTY,
+                first: split as u32,
+                count: (count - left_count) as u32,
+            });
+
+            bvh.nodes[node_index].first = left_index as u32;
+            bvh.nodes[node_index].count = 0;
+
+            stack.push(left_index);
+            stack.push(left_index + 1);
+        }
+
+        bvh
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map(|node| node.bounds)
+            .unwrap_or(Aabb::EMPTY)
+    }
+
+    // partitions the primitives of the range with a binned surface area heuristic,
+    // returns the index of the first primitive of the right half
+    fn find_split(
+        &mut self,
+        range: std::ops::Range<usize>,
+        primitive_bounds: &[Aabb],
+        centroids: &[Vec3],
+    ) -> Option<usize> {
+        let centroid_bounds = Aabb::from_points(
+            self.primitive_indices[range.clone()]
+                .iter()
+                .map(|&primitive| centroids[primitive as usize]),
+        );
+        let extent = centroid_bounds.size();
+
+        let mut best: Option<(usize, f32, f32)> = None; // axis, split position, cost
+
+        for axis in 0..3 {
+            if extent[axis] <= f32::EPSILON {
+                continue;
+            }
+
+            let mut bins = [(Aabb::EMPTY, 0usize); SAH_BINS];
+            let scale = SAH_BINS as f32 / extent[axis];
+
+            for &primitive in &self.primitive_indices[range.clone()] {
+                let bin = (((centroids[primitive as usize][axis] - centroid_bounds.min[axis])
+                    * scale) as usize)
+                    .min(SAH_BINS - 1);
+                bins[bin].0 = bins[bin].0.union(&primitive_bounds[primitive as usize]);
+                bins[bin].1 += 1;
+            }
+
+            // sweep from the right to accumulate the right side of each plane
+            let mut right_areas = [0.0f32; SAH_BINS];
+            let mut right_counts = [0usize; SAH_BINS];
+            let mut accumulated = (Aabb::EMPTY, 0usize);
+            for bin in (1..SAH_BINS).rev() {
+                accumulated.0 = accumulated.0.union(&bins[bin].0);
+                accumulated.1 += bins[bin].1;
+                right_areas[bin] = surface_area(&accumulated.0);
+                right_counts[bin] = accumulated.1;
+            }
+
+            let mut left = (Aabb::EMPTY, 0usize);
+            for bin in 1..SAH_BINS {
+                left.0 = left.0.union(&bins[bin - 1].0);
+                left.1 += bins[bin - 1].1;
+
+                if left.1 == 0 || right_counts[bin] == 0 {
+                    continue;
+                }
+
+                let cost = left.1 as f32 * surface_area(&left.0)
+                    + right_counts[bin] as f32 * right_areas[bin];
+
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    let position = centroid_bounds.min[axis] + bin as f32 / scale;
+                    best = Some((axis, position, cost));
+                }
+            }
+        }
+
+        let (axis, position, _) = best?;
+
+        let indices = &mut self.primitive_indices[range.clone()];
+        let mut left = 0;
+        for i in 0..indices.len() {
+            if centroids[indices[i] as usize][axis] < position {
+                indices.swap(i, left);
+                left += 1;
+            }
+        }
+
+        if left == 0 || left == indices.len() {
+            return None;
+        }
+
+        Some(range.start + left)
+    }
+
+    // walks the hierarchy front to back and calls `intersect` for every primitive
+    // whose leaf the ray reaches, `intersect` returns the hit distance if the primitive
+    // was hit closer than the given maximum, returns the closest primitive and distance
+    pub fn traverse(
+        &self,
+        ray: &Ray,
+        max_distance: f32,
+        mut intersect: impl FnMut(u32, f32) -> Option<f32>,
+    ) -> Option<(u32, f32)> {
+        let root = self.nodes.first()?;
+
+        let mut closest: Option<(u32, f32)> = None;
+        let mut max_distance = max_distance;
+
+        root.bounds.intersect_ray(ray, max_distance)?;
+
+        let mut stack = vec![0usize];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+
+            if node.count > 0 {
+                let first = node.first as usize;
+                for &primitive in &self.primitive_indices[first..first + node.count as usize] {
+                    if let Some(distance) = intersect(primitive, max_distance)
+                        && distance < max_distance
+                    {
+                        max_distance = distance;
+                        closest = Some((primitive, distance));
+                    }
+                }
+                continue;
+            }
+
+            let left = node.first as usize;
+            let right = left + 1;
+            let left_hit = self.nodes[left].bounds.intersect_ray(ray, max_distance);
+            let right_hit = self.nodes[right].bounds.intersect_ray(ray, max_distance);
+
+            // push the farther child first so the nearer one is visited next
+            match (left_hit, right_hit) {
+                (Some(l), Some(r)) => {
+                    if l <= r {
+                        stack.push(right);
+                        stack.push(left);
+                    } else {
+                        stack.push(left);
+                        stack.push(right);
+                    }
+                }
+                (Some(_), None) => stack.push(left),
+                (None, Some(_)) => stack.push(right),
+                (None, None) => {}
+            }
+        }
+
+        closest
+    }
+}
+
+fn surface_area(aabb: &Aabb) -> f32 {
+    if aabb.is_empty() {
+        return 0.0;
+    }
+    let size = aabb.size();
+    2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+}
@@ -1,6 +1,6 @@
 use glam::Vec3;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Camera {
     pub position: Vec3,
     pub target: Vec3,
@@ -43,6 +43,14 @@ impl Camera {
         )
     }
 
+    pub fn view_projection_matrix(&self, aspect_ratio: f32) -> glam::Mat4 {
+        self.projection_matrix(aspect_ratio) * self.view_matrix()
+    }
+
+    pub fn frustum(&self, aspect_ratio: f32) -> crate::graphics::frustum::Frustum {
+        crate::graphics::frustum::Frustum::from_matrix(&self.view_projection_matrix(aspect_ratio))
+    }
+
     pub fn rotate(&mut self, rotation: Vec3) {
         // Update the camera's target based on the rotation
         let direction = (self.target - self.position).normalize();
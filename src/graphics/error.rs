@@ -23,6 +23,15 @@ pub enum GraphicsError {
     #[error("Camera setup error: {0}")]
     CameraSetupError(String),
 
+    #[error("Invalid mesh topology: {0}")]
+    InvalidMeshTopology(String),
+
+    #[error("Invalid node hierarchy: {0}")]
+    InvalidNodeHierarchy(String),
+
+    #[error("Font error: {0}")]
+    FontError(String),
+
     #[error("No mesh data found")]
     NoMeshDataFound,
 
@@ -0,0 +1,76 @@
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::graphics::bounds::{Aabb, BoundingSphere};
+
+// the six planes of a view frustum in world space, their normals point inwards,
+// in the order left, right, bottom, top, near, far
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    // extracts the planes from a projection * view matrix with a depth range of 0 to 1
+    pub fn from_matrix(view_projection: &Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|row| view_projection.row(row));
+
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            let length = plane.truncate().length();
+            if length > 0.0 { plane / length } else { plane }
+        });
+        Frustum { planes }
+    }
+
+    // signed distance of the point to each plane, negative outside
+    fn distance(plane: Vec4, point: Vec3) -> f32 {
+        plane.truncate().dot(point) + plane.w
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|&plane| Frustum::distance(plane, point) >= 0.0)
+    }
+
+    // conservative, a sphere near a corner of the frustum may pass while outside it
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|&plane| Frustum::distance(plane, sphere.center) >= -sphere.radius)
+    }
+
+    // conservative like the sphere test, only the corner furthest along each plane is checked
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        if aabb.is_empty() {
+            return false;
+        }
+
+        self.planes.iter().all(|&plane| {
+            let normal = plane.truncate();
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            Frustum::distance(plane, corner) >= 0.0
+        })
+    }
+}
+
+// how many objects and submeshes the last frame drew and skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CullingStats {
+    pub visible_objects: usize,
+    pub culled_objects: usize,
+    pub visible_submeshes: usize,
+    pub culled_submeshes: usize,
+}
+
+impl std::fmt::Display for CullingStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} objects visible, {} culled, {} submeshes visible, {} culled",
+            self.visible_objects,
+            self.culled_objects,
+            self.visible_submeshes,
+            self.culled_submeshes
+        )
+    }
+}
@@ -1,7 +1,9 @@
+// the `light_type` the shaders branch on
+#[repr(u32)]
 enum LightType {
-    Point,
-    Directional,
-    Spot,
+    Point = 0,
+    Directional = 1,
+    Spot = 2,
 }
 
 #[derive(Debug, Clone)]
@@ -9,6 +11,8 @@ pub struct PointLight {
     pub position: glam::Vec3,
     pub color: glam::Vec3,
     pub intensity: f32,
+    // rendered into a cube of shadow maps, see `ShadowSettings::point_shadow_lights`
+    pub cast_shadows: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -16,12 +20,82 @@ pub struct DirectionalLight {
     pub direction: glam::Vec3,
     pub color: glam::Vec3,
     pub intensity: f32,
+    // rendered into cascaded shadow maps, see `ShadowSettings`
+    pub cast_shadows: bool,
+}
+
+// a point light limited to a cone around `direction`, at full strength inside the inner
+// angle and fading out until the outer one, the angles are in degrees from the axis
+#[derive(Debug, Clone)]
+pub struct SpotLight {
+    pub position: glam::Vec3,
+    pub direction: glam::Vec3,
+    pub color: glam::Vec3,
+    pub intensity: f32,
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    // no light reaches further than this, 0 for no limit
+    pub range: f32,
+}
+
+impl SpotLight {
+    // cosines of the inner and outer angle, the inner cone is kept inside the outer one
+    pub fn cone_cosines(&self) -> (f32, f32) {
+        let outer = self.outer_angle.max(self.inner_angle).to_radians().cos();
+        let inner = self.inner_angle.to_radians().cos().max(outer + 1e-4);
+        (inner, outer)
+    }
+
+    // 1 inside the inner cone, 0 outside the outer one and smooth in between
+    pub fn cone_falloff(&self, direction_from_light: glam::Vec3) -> f32 {
+        let (inner, outer) = self.cone_cosines();
+        let cosine = self
+            .direction
+            .normalize_or_zero()
+            .dot(direction_from_light.normalize_or_zero());
+        let t = ((cosine - outer) / (inner - outer)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    // fades the light out smoothly until it reaches 0 at the range
+    pub fn range_falloff(&self, distance: f32) -> f32 {
+        if self.range <= 0.0 {
+            return 1.0;
+        }
+        let window = (1.0 - (distance / self.range).powi(4)).clamp(0.0, 1.0);
+        window * window
+    }
+}
+
+// a one sided rectangle around `position` that shines towards `direction`
+#[derive(Debug, Clone)]
+pub struct AreaLight {
+    pub position: glam::Vec3,
+    pub direction: glam::Vec3,
+    pub size: glam::Vec2,
+    pub color: glam::Vec3,
+    pub intensity: f32,
+}
+
+impl AreaLight {
+    // a corner of the rectangle and the two edges leaving it
+    pub fn edges(&self) -> (glam::Vec3, glam::Vec3, glam::Vec3) {
+        let (tangent, bitangent) = self
+            .direction
+            .normalize_or(glam::Vec3::NEG_Y)
+            .any_orthonormal_pair();
+        let width = tangent * self.size.x;
+        let height = bitangent * self.size.y;
+        (self.position - (width + height) * 0.5, width, height)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Light {
     Point(PointLight),
     Directional(DirectionalLight),
+    Spot(SpotLight),
+    Area(AreaLight),
 }
 
 impl Default for Light {
@@ -30,34 +104,41 @@ impl Default for Light {
             position: glam::Vec3::ZERO,
             color: glam::Vec3::new(1.0, 1.0, 1.0),
             intensity: 1.0,
+            cast_shadows: false,
         })
     }
 }
 
-#[derive(vulkano::buffer::BufferContents, Debug)]
+#[derive(vulkano::buffer::BufferContents, Debug, Clone)]
 #[repr(C)]
 pub struct GpuLight {
     pub position: glam::Vec3,
-    pub _padding: f32,
+    // spot lights only, see `SpotLight`
+    pub range: f32,
     pub direction: glam::Vec3,
-    pub _padding2: f32,
+    pub inner_cone_cos: f32,
     pub color: glam::Vec3,
     pub intensity: f32,
     pub light_type: u32,
-    pub _padding4: [u32; 3],
+    pub outer_cone_cos: f32,
+    // the first shadow map of the light, -1 for none, set by the renderer
+    pub shadow_index: i32,
+    pub _padding: u32,
 }
 
 impl Default for GpuLight {
     fn default() -> Self {
         GpuLight {
             position: glam::Vec3::ZERO,
-            _padding: 0.0,
+            range: 0.0,
             direction: glam::Vec3::ZERO,
-            _padding2: 0.0,
+            inner_cone_cos: 1.0,
             color: glam::Vec3::ZERO,
             intensity: 1.0,
-            light_type: 0, // Default to Point light
-            _padding4: [0; 3],
+            light_type: LightType::Point as u32,
+            outer_cone_cos: 1.0,
+            shadow_index: -1,
+            _padding: 0,
         }
     }
 }
@@ -71,7 +152,7 @@ impl From<&Light> for GpuLight {
                 direction: glam::Vec3::ZERO,
                 color: point_light.color,
                 intensity: point_light.intensity,
-                light_type: 0, // Point light type
+                light_type: LightType::Point as u32,
                 ..Default::default()
             },
             Light::Directional(directional_light) => GpuLight {
@@ -81,7 +162,31 @@ impl From<&Light> for GpuLight {
 
                 color: directional_light.color,
                 intensity: directional_light.intensity,
-                light_type: 1, // Directional light type
+                light_type: LightType::Directional as u32,
+                ..Default::default()
+            },
+            Light::Spot(spot_light) => {
+                let (inner_cone_cos, outer_cone_cos) = spot_light.cone_cosines();
+                GpuLight {
+                    position: spot_light.position,
+                    range: spot_light.range,
+                    direction: spot_light.direction,
+                    inner_cone_cos,
+                    color: spot_light.color,
+                    intensity: spot_light.intensity,
+                    light_type: LightType::Spot as u32,
+                    outer_cone_cos,
+                    ..Default::default()
+                }
+            }
+            // the shaders have no area lights, they light like a point light in the center
+            Light::Area(area_light) => GpuLight {
+                position: area_light.position,
+
+                direction: glam::Vec3::ZERO,
+                color: area_light.color,
+                intensity: area_light.intensity,
+                light_type: LightType::Point as u32,
                 ..Default::default()
             },
         }
@@ -90,27 +195,6 @@ impl From<&Light> for GpuLight {
 
 impl From<Light> for GpuLight {
     fn from(light: Light) -> Self {
-        match light {
-            Light::Point(point_light) => GpuLight {
-                position: point_light.position,
-
-                direction: glam::Vec3::ZERO,
-
-                color: point_light.color,
-                intensity: point_light.intensity,
-                light_type: 0, // Point light type
-                ..Default::default()
-            },
-            Light::Directional(directional_light) => GpuLight {
-                position: glam::Vec3::ZERO,
-
-                direction: directional_light.direction,
-
-                color: directional_light.color,
-                intensity: directional_light.intensity,
-                light_type: 1, // Directional light type
-                ..Default::default()
-            },
-        }
+        GpuLight::from(&light)
     }
 }
@@ -1,6 +1,6 @@
 use glam::Vec3;
 
-#[derive(Debug, Clone, vulkano::buffer::BufferContents)]
+#[derive(Debug, Clone, PartialEq, vulkano::buffer::BufferContents)]
 #[repr(C)]
 pub struct GpuMaterials {
     pub ambient_color: Vec3,
@@ -24,11 +24,15 @@ impl Default for GpuMaterials {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Material {
     pub name: String,
 
     pub properties: GpuMaterials,
+    // not sampled by the renderer yet
+    pub diffuse_texture: Option<crate::graphics::assets::Asset<crate::graphics::texture::Texture>>,
+    // light given off by the surface, only the path tracer renders it
+    pub emission: Vec3,
 }
 
 impl Default for Material {
@@ -36,6 +40,11 @@ impl Default for Material {
         Material {
             name: "Default Material".to_string(),
             properties: GpuMaterials::default(),
+            diffuse_texture: None,
+            emission: Vec3::ZERO,
         }
     }
 }
+
+// scenes keep shared material assets, so imports and merged scenes use the same data
+pub type MaterialHandle = crate::graphics::arena::Handle<crate::graphics::assets::Asset<Material>>;
@@ -0,0 +1,927 @@
+use std::collections::{HashMap, HashSet};
+
+use glam::{Vec2, Vec3};
+
+use crate::graphics::{
+    error::GraphicsError,
+    material::MaterialHandle,
+    mesh::{Polygons, SubMesh, validate::weld_positions},
+    vertex::Vertex,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VertexId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct HalfEdgeId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FaceId(pub u32);
+
+#[derive(Debug, Clone)]
+pub struct HalfEdgeVertex {
+    pub position: Vec3,
+    // one outgoing half edge, the one along the border for boundary vertices
+    pub half_edge: Option<HalfEdgeId>,
+    removed: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct HalfEdge {
+    pub origin: VertexId,
+    pub twin: HalfEdgeId,
+    pub next: HalfEdgeId,
+    pub prev: HalfEdgeId,
+    // none for the half edges running along a border
+    pub face: Option<FaceId>,
+    // attributes of the face corner at `origin`, so uv and normal seams need no split vertices
+    pub normal: Vec3,
+    pub tex_coord: Vec2,
+    removed: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct HalfEdgeFace {
+    pub half_edge: HalfEdgeId,
+    removed: bool,
+}
+
+// polygon mesh with explicit adjacency, vertices sharing a position are merged so the
+// whole surface is connected, elements removed by the operators leave holes in the ids
+#[derive(Debug, Clone, Default)]
+pub struct HalfEdgeMesh {
+    vertices: Vec<HalfEdgeVertex>,
+    half_edges: Vec<HalfEdge>,
+    faces: Vec<HalfEdgeFace>,
+}
+
+impl HalfEdgeMesh {
+    // uses the polygons of the submesh when it still has them
+    pub fn from_submesh(submesh: &SubMesh) -> Result<Self, GraphicsError> {
+        if let Some(polygons) = &submesh.polygons {
+            return HalfEdgeMesh::from_faces(&submesh.vertices, polygons.iter());
+        }
+
+        if !submesh.indices.len().is_multiple_of(3) {
+            return Err(GraphicsError::InvalidMeshTopology(format!(
+                "index count {} is not a multiple of three",
+                submesh.indices.len()
+            )));
+        }
+
+        HalfEdgeMesh::from_faces(&submesh.vertices, submesh.indices.chunks_exact(3))
+    }
+
+    // every face lists its corners counter clockwise, the surface has to be manifold
+    // and consistently wound, which `repair` takes care of for triangle meshes
+    pub fn from_faces<'a>(
+        vertices: &[Vertex],
+        faces: impl IntoIterator<Item = &'a [u32]>,
+    ) -> Result<Self, GraphicsError> {
+        let welded = weld_positions(vertices);
+        let mut mesh = HalfEdgeMesh::default();
+
+        let mut vertex_ids = HashMap::<u32, VertexId>::new();
+        let mut directed = HashMap::<(VertexId, VertexId), HalfEdgeId>::new();
+
+        for (face_index, corners) in faces.into_iter().enumerate() {
+            if corners.len() < 3 {
+                return Err(GraphicsError::InvalidMeshTopology(format!(
+                    "face {} has only {} corners",
+                    face_index,
+                    corners.len()
+                )));
+            }
+
+            let mut ids = Vec::with_capacity(corners.len());
+            for &index in corners {
+                let Some(&position_index) = welded.get(index as usize) else {
+                    return Err(GraphicsError::InvalidMeshTopology(format!(
+                        "face {} uses vertex {} but there are only {} vertices",
+                        face_index,
+                        index,
+                        vertices.len()
+                    )));
+                };
+
+                let id = *vertex_ids.entry(position_index).or_insert_with(|| {
+                    mesh.vertices.push(HalfEdgeVertex {
+                        position: vertices[position_index as usize].position,
+                        half_edge: None,
+                        removed: false,
+                    });
+                    VertexId(mesh.vertices.len() as u32 - 1)
+                });
+
+                if ids.contains(&id) {
+                    return Err(GraphicsError::InvalidMeshTopology(format!(
+                        "face {} uses the same position twice",
+                        face_index
+                    )));
+                }
+                ids.push(id);
+            }
+
+            let face = FaceId(mesh.faces.len() as u32);
+            let first = mesh.half_edges.len() as u32;
+            let count = corners.len() as u32;
+            mesh.faces.push(HalfEdgeFace {
+                half_edge: HalfEdgeId(first),
+                removed: false,
+            });
+
+            for (corner, (&index, &origin)) in corners.iter().zip(&ids).enumerate() {
+                let corner = corner as u32;
+                let id = HalfEdgeId(first + corner);
+                let target = ids[((corner + 1) % count) as usize];
+
+                // a second face walking the same edge the same way is either a flipped
+                // neighbor or a third face on the edge
+                if directed.insert((origin, target), id).is_some() {
+                    return Err(GraphicsError::InvalidMeshTopology(format!(
+                        "edge {} -> {} of face {} is non-manifold or inconsistently wound",
+                        origin.0, target.0, face_index
+                    )));
+                }
+
+                mesh.half_edges.push(HalfEdge {
+                    origin,
+                    twin: id,
+                    next: HalfEdgeId(first + (corner + 1) % count),
+                    prev: HalfEdgeId(first + (corner + count - 1) % count),
+                    face: Some(face),
+                    normal: vertices[index as usize].normal,
+                    tex_coord: vertices[index as usize].tex_coord,
+                    removed: false,
+                });
+                mesh.vertices[origin.0 as usize].half_edge.get_or_insert(id);
+            }
+        }
+
+        // pair up the half edges, the unpaired ones get a twin running along the border
+        let mut boundary_from = HashMap::<VertexId, HalfEdgeId>::new();
+        for index in 0..mesh.half_edges.len() {
+            let id = HalfEdgeId(index as u32);
+            let origin = mesh.half_edges[index].origin;
+            let target = mesh.half_edges[mesh.half_edges[index].next.0 as usize].origin;
+
+            if let Some(&twin) = directed.get(&(target, origin)) {
+                mesh.half_edges[index].twin = twin;
+                continue;
+            }
+
+            let boundary = HalfEdgeId(mesh.half_edges.len() as u32);
+            mesh.half_edges.push(HalfEdge {
+                origin: target,
+                twin: id,
+                next: boundary,
+                prev: boundary,
+                face: None,
+                normal: Vec3::ZERO,
+                tex_coord: Vec2::ZERO,
+                removed: false,
+            });
+            mesh.half_edges[index].twin = boundary;
+
+            // two borders touching in a single vertex can not be walked unambiguously
+            if boundary_from.insert(target, boundary).is_some() {
+                return Err(GraphicsError::InvalidMeshTopology(format!(
+                    "vertex {} joins more than one border",
+                    target.0
+                )));
+            }
+        }
+
+        // link the border half edges into loops
+        for (&origin, &boundary) in &boundary_from {
+            let twin = mesh.half_edges[boundary.0 as usize].twin;
+            let target = mesh.half_edges[twin.0 as usize].origin;
+            let Some(&next) = boundary_from.get(&target) else {
+                return Err(GraphicsError::InvalidMeshTopology(format!(
+                    "border through vertex {} does not continue",
+                    target.0
+                )));
+            };
+
+            mesh.half_edges[boundary.0 as usize].next = next;
+            mesh.half_edges[next.0 as usize].prev = boundary;
+            mesh.vertices[origin.0 as usize].half_edge = Some(boundary);
+        }
+
+        Ok(mesh)
+    }
+
+    // fan triangulates every face, so faces are expected to be convex, the fan starts at
+    // the flattest corner so corners added by `split_edge` do not produce slivers
+    pub fn to_submesh(&self, material: Option<MaterialHandle>) -> SubMesh {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut lookup = HashMap::<Vertex, u32>::new();
+        let mut polygons = Polygons::default();
+
+        for face in self.face_ids() {
+            let corners = self
+                .face_half_edges(face)
+                .map(|id| {
+                    let half_edge = self.half_edge(id);
+                    let vertex = Vertex {
+                        position: self.vertex(half_edge.origin).position,
+                        normal: half_edge.normal,
+                        tex_coord: half_edge.tex_coord,
+                    };
+                    *lookup.entry(vertex).or_insert_with(|| {
+                        vertices.push(vertex);
+                        vertices.len() as u32 - 1
+                    })
+                })
+                .collect::<Vec<_>>();
+            polygons.push(&corners);
+
+            let positions = corners
+                .iter()
+                .map(|&corner| vertices[corner as usize].position)
+                .collect::<Vec<_>>();
+            let count = corners.len();
+            let start = (0..count)
+                .min_by(|&a, &b| {
+                    let cosine = |i: usize| {
+                        let to_prev = positions[(i + count - 1) % count] - positions[i];
+                        let to_next = positions[(i + 1) % count] - positions[i];
+                        to_prev.normalize_or_zero().dot(to_next.normalize_or_zero())
+                    };
+                    cosine(a).total_cmp(&cosine(b))
+                })
+                .unwrap_or(0);
+
+            for i in 1..count - 1 {
+                indices.extend_from_slice(&[
+                    corners[start],
+                    corners[(start + i) % count],
+                    corners[(start + i + 1) % count],
+                ]);
+            }
+        }
+
+        let mut submesh = SubMesh::new(vertices, indices, material);
+        if polygons.has_non_triangles() {
+            submesh.polygons = Some(polygons);
+        }
+        submesh
+    }
+
+    pub fn vertex(&self, id: VertexId) -> &HalfEdgeVertex {
+        &self.vertices[id.0 as usize]
+    }
+
+    pub fn half_edge(&self, id: HalfEdgeId) -> &HalfEdge {
+        &self.half_edges[id.0 as usize]
+    }
+
+    pub fn face(&self, id: FaceId) -> &HalfEdgeFace {
+        &self.faces[id.0 as usize]
+    }
+
+    pub fn set_position(&mut self, id: VertexId, position: Vec3) {
+        self.vertices[id.0 as usize].position = position;
+    }
+
+    pub fn vertex_ids(&self) -> impl Iterator<Item = VertexId> + '_ {
+        (0..self.vertices.len() as u32)
+            .map(VertexId)
+            .filter(|&id| !self.vertex(id).removed)
+    }
+
+    pub fn half_edge_ids(&self) -> impl Iterator<Item = HalfEdgeId> + '_ {
+        (0..self.half_edges.len() as u32)
+            .map(HalfEdgeId)
+            .filter(|&id| !self.half_edge(id).removed)
+    }
+
+    // one half edge per edge
+    pub fn edge_ids(&self) -> impl Iterator<Item = HalfEdgeId> + '_ {
+        self.half_edge_ids()
+            .filter(|&id| id < self.half_edge(id).twin)
+    }
+
+    pub fn face_ids(&self) -> impl Iterator<Item = FaceId> + '_ {
+        (0..self.faces.len() as u32)
+            .map(FaceId)
+            .filter(|&id| !self.face(id).removed)
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertex_ids().count()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edge_ids().count()
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.face_ids().count()
+    }
+
+    pub fn target(&self, id: HalfEdgeId) -> VertexId {
+        self.half_edge(self.half_edge(id).twin).origin
+    }
+
+    pub fn is_boundary_edge(&self, id: HalfEdgeId) -> bool {
+        let half_edge = self.half_edge(id);
+        half_edge.face.is_none() || self.half_edge(half_edge.twin).face.is_none()
+    }
+
+    pub fn is_boundary_vertex(&self, id: VertexId) -> bool {
+        // the stored half edge is the border one whenever there is a border
+        self.vertex(id)
+            .half_edge
+            .is_none_or(|half_edge| self.half_edge(half_edge).face.is_none())
+    }
+
+    // half edges leaving the vertex, rotating around it
+    pub fn vertex_half_edges(&self, id: VertexId) -> impl Iterator<Item = HalfEdgeId> + '_ {
+        let start = self.vertex(id).half_edge;
+        std::iter::successors(start, move |&current| {
+            let next = self.half_edge(self.half_edge(current).prev).twin;
+            (Some(next) != start).then_some(next)
+        })
+    }
+
+    pub fn vertex_neighbors(&self, id: VertexId) -> impl Iterator<Item = VertexId> + '_ {
+        self.vertex_half_edges(id)
+            .map(|half_edge| self.target(half_edge))
+    }
+
+    pub fn vertex_faces(&self, id: VertexId) -> impl Iterator<Item = FaceId> + '_ {
+        self.vertex_half_edges(id)
+            .filter_map(|half_edge| self.half_edge(half_edge).face)
+    }
+
+    pub fn valence(&self, id: VertexId) -> usize {
+        self.vertex_half_edges(id).count()
+    }
+
+    // half edges around the face, also works for a border loop when given one of its half edges
+    pub fn loop_half_edges(&self, start: HalfEdgeId) -> impl Iterator<Item = HalfEdgeId> + '_ {
+        std::iter::successors(Some(start), move |&current| {
+            let next = self.half_edge(current).next;
+            (next != start).then_some(next)
+        })
+    }
+
+    pub fn face_half_edges(&self, id: FaceId) -> impl Iterator<Item = HalfEdgeId> + '_ {
+        self.loop_half_edges(self.face(id).half_edge)
+    }
+
+    pub fn face_vertices(&self, id: FaceId) -> impl Iterator<Item = VertexId> + '_ {
+        self.face_half_edges(id)
+            .map(|half_edge| self.half_edge(half_edge).origin)
+    }
+
+    // faces on the other side of each edge of the face
+    pub fn face_neighbors(&self, id: FaceId) -> impl Iterator<Item = FaceId> + '_ {
+        self.face_half_edges(id)
+            .filter_map(|half_edge| self.half_edge(self.half_edge(half_edge).twin).face)
+    }
+
+    pub fn face_degree(&self, id: FaceId) -> usize {
+        self.face_half_edges(id).count()
+    }
+
+    pub fn face_normal(&self, id: FaceId) -> Vec3 {
+        self.face_area_vector(id).normalize_or_zero()
+    }
+
+    // normal scaled by the area, Newell's method so it also works for non planar polygons
+    pub fn face_area_vector(&self, id: FaceId) -> Vec3 {
+        let positions = self
+            .face_vertices(id)
+            .map(|vertex| self.vertex(vertex).position)
+            .collect::<Vec<_>>();
+
+        positions
+            .iter()
+            .zip(positions.iter().cycle().skip(1))
+            .fold(Vec3::ZERO, |normal, (current, next)| {
+                normal + (*current - *next).cross(*current + *next)
+            })
+            * 0.5
+    }
+
+    // gives every corner the area weighted average normal of the faces around its vertex
+    pub fn compute_smooth_normals(&mut self) {
+        let vertices = self.vertex_ids().collect::<Vec<_>>();
+        for vertex in vertices {
+            let normal = self
+                .vertex_faces(vertex)
+                .fold(Vec3::ZERO, |normal, face| {
+                    normal + self.face_area_vector(face)
+                })
+                .normalize_or_zero();
+
+            let outgoing = self.vertex_half_edges(vertex).collect::<Vec<_>>();
+            for half_edge in outgoing {
+                self.half_edges[half_edge.0 as usize].normal = normal;
+            }
+        }
+    }
+
+    pub fn face_centroid(&self, id: FaceId) -> Vec3 {
+        let (sum, count) = self
+            .face_vertices(id)
+            .fold((Vec3::ZERO, 0), |(sum, count), vertex| {
+                (sum + self.vertex(vertex).position, count + 1)
+            });
+        sum / count as f32
+    }
+
+    // inserts a vertex at `t` along the edge, triangles on either side are split in two,
+    // larger faces just gain a corner
+    pub fn split_edge(&mut self, id: HalfEdgeId, t: f32) -> VertexId {
+        let forward = id;
+        let backward = self.half_edge(forward).twin;
+        let a = self.half_edge(forward).origin;
+        let b = self.half_edge(backward).origin;
+
+        let position = self.vertex(a).position.lerp(self.vertex(b).position, t);
+        let middle = VertexId(self.vertices.len() as u32);
+        self.vertices.push(HalfEdgeVertex {
+            position,
+            half_edge: None,
+            removed: false,
+        });
+
+        // forward keeps running a -> middle and backward b -> middle, the new
+        // half edges continue from the middle to their old targets
+        let mut triangles = Vec::new();
+        let mut inserted = [forward; 2];
+        for (slot, (current, t)) in [(forward, t), (backward, 1.0 - t)].into_iter().enumerate() {
+            let next = self.half_edge(current).next;
+            let face = self.half_edge(current).face;
+            let is_triangle = face.is_some_and(|face| self.face_degree(face) == 3);
+
+            let (normal, tex_coord) = self.lerp_corner(current, next, t);
+            let half_edge = self.push_half_edge(HalfEdge {
+                origin: middle,
+                twin: current,
+                next,
+                prev: current,
+                face,
+                normal,
+                tex_coord,
+                removed: false,
+            });
+
+            self.half_edges[current.0 as usize].next = half_edge;
+            self.half_edges[next.0 as usize].prev = half_edge;
+            inserted[slot] = half_edge;
+
+            if is_triangle {
+                triangles.push(half_edge);
+            }
+        }
+
+        let [after_forward, after_backward] = inserted;
+        self.half_edges[forward.0 as usize].twin = after_backward;
+        self.half_edges[after_backward.0 as usize].twin = forward;
+        self.half_edges[backward.0 as usize].twin = after_forward;
+        self.half_edges[after_forward.0 as usize].twin = backward;
+
+        self.vertices[middle.0 as usize].half_edge =
+            Some(if self.half_edge(after_backward).face.is_none() {
+                after_backward
+            } else {
+                after_forward
+            });
+
+        // connect the new vertex to the corner opposite of the edge
+        for from in triangles {
+            let opposite = self.half_edge(self.half_edge(from).next).next;
+            self.split_face(from, opposite);
+        }
+
+        middle
+    }
+
+    // merges the two vertices of the edge into one at its midpoint, both neighboring
+    // faces have to be triangles, fails when the result would not be manifold
+    pub fn collapse_edge(&mut self, id: HalfEdgeId) -> Result<VertexId, GraphicsError> {
+        let forward = id;
+        let backward = self.half_edge(forward).twin;
+        let kept = self.half_edge(forward).origin;
+        let removed = self.half_edge(backward).origin;
+
+        let sides = [forward, backward];
+        let mut opposite_count = 0;
+        for side in sides {
+            let Some(face) = self.half_edge(side).face else {
+                continue;
+            };
+            if self.face_degree(face) != 3 {
+                return Err(GraphicsError::InvalidMeshTopology(format!(
+                    "can only collapse edges between triangles, face {} has {} corners",
+                    face.0,
+                    self.face_degree(face)
+                )));
+            }
+
+            // a lone triangle would leave a dangling edge behind
+            let next = self.half_edge(side).next;
+            let prev = self.half_edge(side).prev;
+            if self.is_boundary_edge(next) && self.is_boundary_edge(prev) {
+                return Err(GraphicsError::InvalidMeshTopology(format!(
+                    "collapsing the edge would leave face {} degenerate",
+                    face.0
+                )));
+            }
+            opposite_count += 1;
+        }
+
+        // joining two borders through the inside pinches the surface
+        if !self.is_boundary_edge(forward)
+            && self.is_boundary_vertex(kept)
+            && self.is_boundary_vertex(removed)
+        {
+            return Err(GraphicsError::InvalidMeshTopology(
+                "collapsing an inner edge between two border vertices".to_string(),
+            ));
+        }
+
+        // link condition, the only shared neighbors are the corners opposite of the edge
+        let kept_neighbors = self.vertex_neighbors(kept).collect::<HashSet<_>>();
+        let shared = self
+            .vertex_neighbors(removed)
+            .filter(|vertex| kept_neighbors.contains(vertex))
+            .count();
+        if shared != opposite_count {
+            return Err(GraphicsError::InvalidMeshTopology(format!(
+                "vertices {} and {} share {} neighbors, collapsing would fold the surface",
+                kept.0, removed.0, shared
+            )));
+        }
+
+        let moved = self.vertex_half_edges(removed).collect::<Vec<_>>();
+        let mut candidates = self.vertex_half_edges(kept).collect::<Vec<_>>();
+        candidates.extend_from_slice(&moved);
+
+        for side in sides {
+            let next = self.half_edge(side).next;
+            let prev = self.half_edge(side).prev;
+
+            if let Some(face) = self.half_edge(side).face {
+                // the two outer edges of the triangle become one
+                let outer_next = self.half_edge(next).twin;
+                let outer_prev = self.half_edge(prev).twin;
+                self.half_edges[outer_next.0 as usize].twin = outer_prev;
+                self.half_edges[outer_prev.0 as usize].twin = outer_next;
+
+                let opposite = self.half_edge(prev).origin;
+                if self.vertex(opposite).half_edge == Some(prev) {
+                    self.vertices[opposite.0 as usize].half_edge = Some(outer_next);
+                }
+
+                self.faces[face.0 as usize].removed = true;
+                self.half_edges[next.0 as usize].removed = true;
+                self.half_edges[prev.0 as usize].removed = true;
+            } else {
+                self.half_edges[prev.0 as usize].next = next;
+                self.half_edges[next.0 as usize].prev = prev;
+            }
+
+            self.half_edges[side.0 as usize].removed = true;
+        }
+
+        for half_edge in moved {
+            self.half_edges[half_edge.0 as usize].origin = kept;
+        }
+
+        let position = (self.vertex(kept).position + self.vertex(removed).position) * 0.5;
+        let half_edge = candidates
+            .iter()
+            .copied()
+            .filter(|&half_edge| !self.half_edge(half_edge).removed)
+            .min_by_key(|&half_edge| self.half_edge(half_edge).face.is_some());
+
+        self.vertices[kept.0 as usize].position = position;
+        self.vertices[kept.0 as usize].half_edge = half_edge;
+        self.vertices[removed.0 as usize].removed = true;
+        self.vertices[removed.0 as usize].half_edge = None;
+
+        Ok(kept)
+    }
+
+    // turns the edge shared by two triangles so it connects their other two corners
+    pub fn flip_edge(&mut self, id: HalfEdgeId) -> Result<(), GraphicsError> {
+        let forward = id;
+        let backward = self.half_edge(forward).twin;
+
+        let (Some(forward_face), Some(backward_face)) =
+            (self.half_edge(forward).face, self.half_edge(backward).face)
+        else {
+            return Err(GraphicsError::InvalidMeshTopology(
+                "can not flip a border edge".to_string(),
+            ));
+        };
+
+        if self.face_degree(forward_face) != 3 || self.face_degree(backward_face) != 3 {
+            return Err(GraphicsError::InvalidMeshTopology(
+                "can only flip edges between triangles".to_string(),
+            ));
+        }
+
+        // forward runs a -> b in (a, b, c), backward b -> a in (b, a, d)
+        let forward_next = self.half_edge(forward).next;
+        let forward_prev = self.half_edge(forward).prev;
+        let backward_next = self.half_edge(backward).next;
+        let backward_prev = self.half_edge(backward).prev;
+
+        let a = self.half_edge(forward).origin;
+        let b = self.half_edge(backward).origin;
+        let c = self.half_edge(forward_prev).origin;
+        let d = self.half_edge(backward_prev).origin;
+
+        if c == d || self.vertex_neighbors(c).any(|vertex| vertex == d) {
+            return Err(GraphicsError::InvalidMeshTopology(format!(
+                "vertices {} and {} are already connected",
+                c.0, d.0
+            )));
+        }
+
+        // afterwards forward runs d -> c in (d, c, a) and backward c -> d in (c, d, b)
+        let corner_d = self.corner(backward_prev);
+        let corner_c = self.corner(forward_prev);
+        self.set_corner(forward, corner_d);
+        self.set_corner(backward, corner_c);
+        self.half_edges[forward.0 as usize].origin = d;
+        self.half_edges[backward.0 as usize].origin = c;
+
+        self.link_face(forward_face, &[forward, forward_prev, backward_next]);
+        self.link_face(backward_face, &[backward, backward_prev, forward_next]);
+
+        if self.vertex(a).half_edge == Some(forward) {
+            self.vertices[a.0 as usize].half_edge = Some(backward_next);
+        }
+        if self.vertex(b).half_edge == Some(backward) {
+            self.vertices[b.0 as usize].half_edge = Some(forward_next);
+        }
+
+        Ok(())
+    }
+
+    // moves a copy of the face `distance` along its normal and connects it to the
+    // original outline with a ring of quads, returns the moved face
+    pub fn extrude_face(&mut self, id: FaceId, distance: f32) -> FaceId {
+        let outline = self.face_half_edges(id).collect::<Vec<_>>();
+        let count = outline.len();
+        let offset = self.face_normal(id) * distance;
+
+        let bottom = outline
+            .iter()
+            .map(|&half_edge| self.half_edge(half_edge).origin)
+            .collect::<Vec<_>>();
+        let top = bottom
+            .iter()
+            .map(|&vertex| {
+                let position = self.vertex(vertex).position + offset;
+                self.vertices.push(HalfEdgeVertex {
+                    position,
+                    half_edge: None,
+                    removed: false,
+                });
+                VertexId(self.vertices.len() as u32 - 1)
+            })
+            .collect::<Vec<_>>();
+
+        // the cap takes over the corner attributes of the original face
+        let cap = (0..count)
+            .map(|i| {
+                let (normal, tex_coord) = self.corner(outline[i]);
+                let half_edge = self.push_half_edge(HalfEdge {
+                    origin: top[i],
+                    twin: outline[i],
+                    next: outline[i],
+                    prev: outline[i],
+                    face: Some(id),
+                    normal,
+                    tex_coord,
+                    removed: false,
+                });
+                self.vertices[top[i].0 as usize].half_edge = Some(half_edge);
+                half_edge
+            })
+            .collect::<Vec<_>>();
+        self.link_face(id, &cap);
+
+        let mut ups = Vec::with_capacity(count);
+        let mut downs = Vec::with_capacity(count);
+
+        for i in 0..count {
+            let j = (i + 1) % count;
+            let face = FaceId(self.faces.len() as u32);
+            self.faces.push(HalfEdgeFace {
+                half_edge: outline[i],
+                removed: false,
+            });
+
+            let side = (self.vertex(bottom[j]).position - self.vertex(bottom[i]).position)
+                .cross(offset)
+                .normalize_or_zero();
+            let mut side_half_edge = |origin: VertexId, twin: HalfEdgeId, tex_coord: Vec2| {
+                self.push_half_edge(HalfEdge {
+                    origin,
+                    twin,
+                    next: twin,
+                    prev: twin,
+                    face: Some(face),
+                    normal: side,
+                    tex_coord,
+                    removed: false,
+                })
+            };
+
+            // the quad runs bottom i -> bottom j -> top j -> top i
+            let up = side_half_edge(bottom[j], outline[i], Vec2::new(1.0, 0.0));
+            let across = side_half_edge(top[j], cap[i], Vec2::new(1.0, 1.0));
+            let down = side_half_edge(top[i], outline[i], Vec2::new(0.0, 1.0));
+
+            self.half_edges[cap[i].0 as usize].twin = across;
+            self.set_corner(outline[i], (side, Vec2::ZERO));
+            self.half_edges[outline[i].0 as usize].face = Some(face);
+            self.link_face(face, &[outline[i], up, across, down]);
+
+            ups.push(up);
+            downs.push(down);
+        }
+
+        for (i, &up) in ups.iter().enumerate() {
+            let down = downs[(i + 1) % count];
+            self.half_edges[up.0 as usize].twin = down;
+            self.half_edges[down.0 as usize].twin = up;
+        }
+
+        id
+    }
+
+    // connects the origins of two non adjacent half edges of the same face, the
+    // part starting at `to` becomes a new face, returns the new half edge inside it
+    fn split_face(&mut self, from: HalfEdgeId, to: HalfEdgeId) -> HalfEdgeId {
+        let face = self.half_edge(from).face;
+        let before_from = self.half_edge(from).prev;
+        let before_to = self.half_edge(to).prev;
+        let (from_normal, from_tex_coord) = self.corner(from);
+        let (to_normal, to_tex_coord) = self.corner(to);
+
+        let closing = self.push_half_edge(HalfEdge {
+            origin: self.half_edge(to).origin,
+            twin: from,
+            next: from,
+            prev: before_to,
+            face,
+            normal: to_normal,
+            tex_coord: to_tex_coord,
+            removed: false,
+        });
+        let opening = self.push_half_edge(HalfEdge {
+            origin: self.half_edge(from).origin,
+            twin: closing,
+            next: to,
+            prev: before_from,
+            face: None,
+            normal: from_normal,
+            tex_coord: from_tex_coord,
+            removed: false,
+        });
+        self.half_edges[closing.0 as usize].twin = opening;
+
+        self.half_edges[before_to.0 as usize].next = closing;
+        self.half_edges[from.0 as usize].prev = closing;
+        self.half_edges[before_from.0 as usize].next = opening;
+        self.half_edges[to.0 as usize].prev = opening;
+
+        let new_face = FaceId(self.faces.len() as u32);
+        self.faces.push(HalfEdgeFace {
+            half_edge: to,
+            removed: false,
+        });
+        if let Some(face) = face {
+            self.faces[face.0 as usize].half_edge = from;
+        }
+
+        let moved = self.loop_half_edges(to).collect::<Vec<_>>();
+        for half_edge in moved {
+            self.half_edges[half_edge.0 as usize].face = Some(new_face);
+        }
+
+        opening
+    }
+
+    fn push_half_edge(&mut self, half_edge: HalfEdge) -> HalfEdgeId {
+        self.half_edges.push(half_edge);
+        HalfEdgeId(self.half_edges.len() as u32 - 1)
+    }
+
+    // closes the given half edges into the loop of a face
+    fn link_face(&mut self, face: FaceId, half_edges: &[HalfEdgeId]) {
+        for (i, &half_edge) in half_edges.iter().enumerate() {
+            let next = half_edges[(i + 1) % half_edges.len()];
+            self.half_edges[half_edge.0 as usize].next = next;
+            self.half_edges[half_edge.0 as usize].face = Some(face);
+            self.half_edges[next.0 as usize].prev = half_edge;
+        }
+        self.faces[face.0 as usize].half_edge = half_edges[0];
+    }
+
+    fn corner(&self, id: HalfEdgeId) -> (Vec3, Vec2) {
+        let half_edge = self.half_edge(id);
+        (half_edge.normal, half_edge.tex_coord)
+    }
+
+    fn set_corner(&mut self, id: HalfEdgeId, (normal, tex_coord): (Vec3, Vec2)) {
+        self.half_edges[id.0 as usize].normal = normal;
+        self.half_edges[id.0 as usize].tex_coord = tex_coord;
+    }
+
+    fn lerp_corner(&self, from: HalfEdgeId, to: HalfEdgeId, t: f32) -> (Vec3, Vec2) {
+        let (from_normal, from_tex_coord) = self.corner(from);
+        let (to_normal, to_tex_coord) = self.corner(to);
+        (
+            from_normal.lerp(to_normal, t).normalize_or_zero(),
+            from_tex_coord.lerp(to_tex_coord, t),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a unit cube with a quad of its own vertices and normal on every side
+    fn cube() -> SubMesh {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for normal in [
+            Vec3::X,
+            Vec3::NEG_X,
+            Vec3::Y,
+            Vec3::NEG_Y,
+            Vec3::Z,
+            Vec3::NEG_Z,
+        ] {
+            let u = normal.any_orthonormal_vector();
+            let v = normal.cross(u);
+            let first = vertices.len() as u32;
+            for (s, t) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                vertices.push(Vertex {
+                    position: (normal + u * s + v * t) * 0.5,
+                    normal,
+                    tex_coord: Vec2::new(s, t) * 0.5 + 0.5,
+                });
+            }
+            indices.extend([0, 1, 2, 0, 2, 3].map(|corner| first + corner));
+        }
+        SubMesh::new(vertices, indices, None)
+    }
+
+    fn sorted_positions(vertices: &[Vertex]) -> Vec<[u32; 3]> {
+        let mut positions = vertices
+            .iter()
+            .map(|vertex| vertex.position.to_array().map(f32::to_bits))
+            .collect::<Vec<_>>();
+        positions.sort();
+        positions.dedup();
+        positions
+    }
+
+    #[test]
+    fn round_trip_keeps_the_surface() {
+        let submesh = cube();
+        let mesh = HalfEdgeMesh::from_submesh(&submesh).unwrap();
+        // the triangles of the cube share their corners across the seams of the normals,
+        // a closed surface without borders
+        assert_eq!(mesh.vertex_count(), 8);
+        assert_eq!(mesh.edge_count(), 18);
+        assert_eq!(mesh.face_count(), 12);
+        assert!(mesh.face_ids().all(|face| mesh.face_degree(face) == 3));
+        assert!(mesh.edge_ids().all(|edge| !mesh.is_boundary_edge(edge)));
+
+        let round_trip = mesh.to_submesh(None);
+        assert_eq!(round_trip.indices.len(), submesh.indices.len());
+        assert_eq!(
+            sorted_positions(&round_trip.vertices),
+            sorted_positions(&submesh.vertices)
+        );
+
+        let again = HalfEdgeMesh::from_submesh(&round_trip).unwrap();
+        assert_eq!(again.vertex_count(), mesh.vertex_count());
+        assert_eq!(again.edge_count(), mesh.edge_count());
+        assert_eq!(again.face_count(), mesh.face_count());
+        for face in again.face_ids() {
+            let half_edges = again.face_half_edges(face).collect::<Vec<_>>();
+            for &id in &half_edges {
+                let half_edge = again.half_edge(id);
+                assert_eq!(again.half_edge(half_edge.twin).twin, id);
+                assert_eq!(again.half_edge(half_edge.next).prev, id);
+                assert_eq!(half_edge.face, Some(face));
+            }
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use glam::Vec3;
+
+use std::sync::{Arc, OnceLock};
+
+use crate::graphics::{assets::Asset, bounds::Bounds, bvh::Bvh, error::GraphicsError};
+
+pub mod half_edge;
+pub mod optimize;
+pub mod repair;
+pub mod simplify;
+pub mod subdivide;
+pub mod validate;
+
+pub use half_edge::{FaceId, HalfEdgeId, HalfEdgeMesh, VertexId};
+pub use optimize::{OptimizationReport, VertexCacheStats, analyze_vertex_cache};
+pub use repair::{RepairOptions, RepairReport, repair};
+pub use simplify::{Lod, LodChainOptions, Simplified, SimplifyOptions, simplify};
+pub use subdivide::{SubdivisionScheme, subdivide, subdivide_submesh};
+pub use validate::{ValidationReport, validate};
+
+#[derive(Debug, Clone)]
+pub struct Mesh {
+    pub submeshes: Vec<SubMesh>,
+    pub world_transform: glam::Mat4,
+    // local space bounds of all submeshes, refreshed by `geometry_changed`
+    bounds: Bounds,
+    subdivision_level: u32,
+    // the submeshes as they were before subdividing, empty at level zero
+    cage: Vec<SubMesh>,
+    // set for generated meshes, so they can be rebuilt with other parameters
+    pub primitive: Option<crate::graphics::primitives::Primitive>,
+}
+
+impl std::fmt::Display for Mesh {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#?}", self)
+    }
+}
+
+impl Mesh {
+    pub fn new(submeshes: Vec<SubMesh>, world_transform: glam::Mat4) -> Self {
+        let mut mesh = Mesh {
+            submeshes,
+            world_transform,
+            bounds: Bounds::default(),
+            subdivision_level: 0,
+            cage: Vec::new(),
+            primitive: None,
+        };
+        mesh.geometry_changed();
+        mesh
+    }
+
+    pub fn subdivision_level(&self) -> u32 {
+        self.subdivision_level
+    }
+
+    // replaces the submeshes by subdivided copies of the original cage, level zero
+    // restores the cage, edits made to the subdivided submeshes are lost on the next change
+    pub fn set_subdivision_level(&mut self, level: u32) -> Result<(), GraphicsError> {
+        if level == self.subdivision_level {
+            return Ok(());
+        }
+
+        if self.subdivision_level == 0 {
+            self.cage = self.submeshes.clone();
+        }
+
+        if level == 0 {
+            self.submeshes = std::mem::take(&mut self.cage);
+        } else {
+            let subdivided = self
+                .cage
+                .iter()
+                .map(|submesh| subdivide_submesh(submesh, level))
+                .collect::<Result<Vec<_>, _>>();
+
+            match subdivided {
+                Ok(submeshes) => self.submeshes = submeshes,
+                Err(error) => {
+                    if self.subdivision_level == 0 {
+                        self.cage.clear();
+                    }
+                    return Err(error);
+                }
+            }
+        }
+
+        self.subdivision_level = level;
+        self.geometry_changed();
+        Ok(())
+    }
+
+    pub fn bounds(&self) -> &Bounds {
+        &self.bounds
+    }
+
+    pub fn world_bounds(&self) -> Bounds {
+        self.bounds.transform(&self.world_transform)
+    }
+
+    // must be called after the vertices or indices of any submesh were modified
+    pub fn geometry_changed(&mut self) {
+        let positions = self
+            .submeshes
+            .iter_mut()
+            .flat_map(|submesh| {
+                submesh.geometry_changed();
+                submesh.vertices.iter().map(|vertex| vertex.position)
+            })
+            .collect::<Vec<_>>();
+
+        self.bounds = Bounds::from_points(&positions);
+    }
+
+    pub fn generate_lods(&mut self, options: &LodChainOptions) {
+        for submesh in &mut self.submeshes {
+            submesh.generate_lods(options);
+        }
+    }
+
+    // reorders the triangles and vertices of every submesh for the gpu caches
+    pub fn optimize(&mut self) -> Vec<OptimizationReport> {
+        let reports = self
+            .submeshes
+            .iter_mut()
+            .map(optimize::optimize_submesh)
+            .collect();
+        self.geometry_changed();
+        reports
+    }
+
+    pub fn normalize(&mut self) {
+        if self.bounds.is_empty() {
+            return;
+        }
+
+        let bbox_size = self.bounds.aabb.size();
+        let center = self.bounds.aabb.center();
+        let max_side_length = bbox_size.x.max(bbox_size.y).max(bbox_size.z);
+
+        if max_side_length == 0.0 {
+            // Avoid division by zero if all vertices are at the same point
+            return;
+        }
+
+        for submesh in &mut self.submeshes {
+            for vertex in &mut submesh.vertices {
+                vertex.position = (vertex.position - center) / max_side_length;
+            }
+        }
+
+        self.geometry_changed();
+    }
+}
+
+impl Default for Mesh {
+    fn default() -> Self {
+        Mesh::new(Vec::new(), glam::Mat4::IDENTITY)
+    }
+}
+
+// the vertex data of a submesh, shared between all submeshes built from the same data
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Geometry {
+    pub vertices: Vec<crate::graphics::vertex::Vertex>,
+    pub indices: Vec<u32>,
+    // the faces before triangulation, only kept when some of them are not triangles
+    pub polygons: Option<Polygons>,
+    // simplified index lists over `vertices`, from finest to coarsest
+    pub lods: Vec<Lod>,
+}
+
+// derefs to its geometry, mutable access copies geometry that is shared with other submeshes
+#[derive(Debug, Clone)]
+pub struct SubMesh {
+    pub geometry: Asset<Geometry>,
+    // `None` draws with the default material
+    pub material: Option<crate::graphics::material::MaterialHandle>,
+    bounds: Bounds,
+    // triangle hierarchy for ray queries, built on first use
+    bvh: OnceLock<Arc<Bvh>>,
+}
+
+impl std::ops::Deref for SubMesh {
+    type Target = Geometry;
+
+    fn deref(&self) -> &Geometry {
+        &self.geometry
+    }
+}
+
+impl std::ops::DerefMut for SubMesh {
+    fn deref_mut(&mut self) -> &mut Geometry {
+        self.geometry.make_mut()
+    }
+}
+
+impl SubMesh {
+    pub fn new(
+        vertices: Vec<crate::graphics::vertex::Vertex>,
+        indices: Vec<u32>,
+        material: Option<crate::graphics::material::MaterialHandle>,
+    ) -> Self {
+        SubMesh::from_geometry(
+            Asset::new(Geometry {
+                vertices,
+                indices,
+                polygons: None,
+                lods: Vec::new(),
+            }),
+            material,
+        )
+    }
+
+    pub fn from_geometry(
+        geometry: Asset<Geometry>,
+        material: Option<crate::graphics::material::MaterialHandle>,
+    ) -> Self {
+        let mut submesh = SubMesh {
+            geometry,
+            material,
+            bounds: Bounds::default(),
+            bvh: OnceLock::new(),
+        };
+        submesh.geometry_changed();
+        submesh
+    }
+
+    pub fn bounds(&self) -> &Bounds {
+        &self.bounds
+    }
+
+    pub fn world_bounds(&self, world_transform: &glam::Mat4) -> Bounds {
+        self.bounds.transform(world_transform)
+    }
+
+    pub fn bvh(&self) -> &Bvh {
+        self.bvh
+            .get_or_init(|| Arc::new(crate::graphics::raycast::build_submesh_bvh(self)))
+    }
+
+    // the lods only hold indices, so they survive vertex edits but have to be
+    // regenerated after the triangles change
+    pub fn generate_lods(&mut self, options: &LodChainOptions) {
+        let lods = simplify::generate_lod_chain(&self.vertices, &self.indices, options);
+        self.lods = lods;
+    }
+
+    // indices to draw for an object covering `screen_size` of the screen height
+    pub fn select_lod(&self, screen_size: f32) -> &[u32] {
+        self.select_lod_level(screen_size)
+            .map_or(&self.indices, |level| &self.lods[level].indices)
+    }
+
+    // the position in `lods` of the level to draw, `None` for the full detail indices
+    pub fn select_lod_level(&self, screen_size: f32) -> Option<usize> {
+        self.lods
+            .iter()
+            .take_while(|lod| screen_size < lod.screen_size)
+            .count()
+            .checked_sub(1)
+    }
+
+    pub fn geometry_changed(&mut self) {
+        self.bvh = OnceLock::new();
+
+        let positions = self
+            .geometry
+            .vertices
+            .iter()
+            .map(|vertex| vertex.position)
+            .collect::<Vec<Vec3>>();
+
+        self.bounds = Bounds::from_points(&positions);
+    }
+}
+
+// faces with any number of corners, stored back to back in `indices`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Polygons {
+    pub sizes: Vec<u32>,
+    pub indices: Vec<u32>,
+}
+
+impl Polygons {
+    pub fn push(&mut self, corners: &[u32]) {
+        self.sizes.push(corners.len() as u32);
+        self.indices.extend_from_slice(corners);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[u32]> + '_ {
+        self.sizes.iter().scan(0usize, |offset, &size| {
+            let corners = &self.indices[*offset..*offset + size as usize];
+            *offset += size as usize;
+            Some(corners)
+        })
+    }
+
+    pub fn has_non_triangles(&self) -> bool {
+        self.sizes.iter().any(|&size| size != 3)
+    }
+
+    // fan from the first corner of each polygon
+    pub fn triangulate(&self) -> Vec<u32> {
+        let mut indices = Vec::with_capacity(self.indices.len() * 3);
+        for corners in self.iter() {
+            for i in 1..corners.len().saturating_sub(1) {
+                indices.extend_from_slice(&[corners[0], corners[i], corners[i + 1]]);
+            }
+        }
+        indices
+    }
+}
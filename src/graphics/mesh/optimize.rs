@@ -0,0 +1,398 @@
+use std::collections::VecDeque;
+
+use glam::Vec3;
+
+use crate::graphics::{mesh::SubMesh, vertex::Vertex};
+
+// a typical post-transform cache size for the fifo model
+pub const DEFAULT_CACHE_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct VertexCacheStats {
+    // average cache miss ratio, vertex shader invocations per triangle (0.5 - 3.0)
+    pub acmr: f32,
+    // average transform to vertex ratio, vertex shader invocations per vertex (1.0 is ideal)
+    pub atvr: f32,
+}
+
+// simulates a fifo post-transform cache of the given size
+pub fn analyze_vertex_cache(
+    indices: &[u32],
+    vertex_count: usize,
+    cache_size: usize,
+) -> VertexCacheStats {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 || vertex_count == 0 {
+        return VertexCacheStats::default();
+    }
+
+    let mut cache = VecDeque::with_capacity(cache_size);
+    let mut misses = 0usize;
+
+    for &index in indices {
+        if cache.contains(&index) {
+            continue;
+        }
+
+        misses += 1;
+        if cache.len() == cache_size {
+            cache.pop_front();
+        }
+        cache.push_back(index);
+    }
+
+    let used_vertices = {
+        let mut used = vec![false; vertex_count];
+        for &index in indices {
+            if let Some(used) = used.get_mut(index as usize) {
+                *used = true;
+            }
+        }
+        used.iter().filter(|used| **used).count().max(1)
+    };
+
+    VertexCacheStats {
+        acmr: misses as f32 / triangle_count as f32,
+        atvr: misses as f32 / used_vertices as f32,
+    }
+}
+
+// result of the tipsify pass: the reordered triangles and the triangle offsets where
+// the fanning had to jump to an unrelated part of the mesh
+struct TipsifyResult {
+    indices: Vec<u32>,
+    cluster_starts: Vec<usize>,
+}
+
+// Sander, Nehab and Barczak, "Fast Triangle Reordering for Vertex Locality and
+// Reduced Overdraw" (2007)
+fn tipsify(indices: &[u32], vertex_count: usize, cache_size: usize) -> TipsifyResult {
+    let triangle_count = indices.len() / 3;
+
+    let mut vertex_triangles = vec![Vec::<u32>::new(); vertex_count];
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        for &index in corners {
+            vertex_triangles[index as usize].push(triangle as u32);
+        }
+    }
+
+    let mut live_triangles = vertex_triangles
+        .iter()
+        .map(|triangles| triangles.len())
+        .collect::<Vec<_>>();
+    let mut cache_time = vec![0usize; vertex_count];
+    let mut emitted = vec![false; triangle_count];
+    let mut dead_end = Vec::<u32>::new();
+
+    let mut output = Vec::with_capacity(indices.len());
+    let mut cluster_starts = vec![0];
+    let mut time = cache_size + 1;
+    let mut cursor = 0usize;
+
+    let mut fanning = (0..vertex_count).find(|&vertex| live_triangles[vertex] > 0);
+
+    while let Some(vertex) = fanning {
+        let mut candidates = Vec::new();
+
+        for &triangle in &vertex_triangles[vertex] {
+            if emitted[triangle as usize] {
+                continue;
+            }
+            emitted[triangle as usize] = true;
+
+            for &index in &indices[triangle as usize * 3..triangle as usize * 3 + 3] {
+                output.push(index);
+                dead_end.push(index);
+                candidates.push(index);
+                live_triangles[index as usize] -= 1;
+
+                if time - cache_time[index as usize] > cache_size {
+                    cache_time[index as usize] = time;
+                    time += 1;
+                }
+            }
+        }
+
+        // prefer the candidate that is oldest in the cache but will not fall out of it
+        // while its remaining triangles are emitted
+        let next = candidates
+            .iter()
+            .filter(|&&candidate| live_triangles[candidate as usize] > 0)
+            .map(|&candidate| {
+                let age = time - cache_time[candidate as usize];
+                let priority = if age + 2 * live_triangles[candidate as usize] <= cache_size {
+                    age
+                } else {
+                    0
+                };
+                (candidate, priority)
+            })
+            .max_by_key(|&(_, priority)| priority)
+            .map(|(candidate, _)| candidate as usize);
+
+        fanning = match next {
+            Some(next) => Some(next),
+            None => {
+                // dead end, continue from recently used vertices or scan for any live one
+                let mut next = None;
+                while let Some(candidate) = dead_end.pop() {
+                    if live_triangles[candidate as usize] > 0 {
+                        next = Some(candidate as usize);
+                        break;
+                    }
+                }
+
+                if next.is_none() {
+                    while cursor < vertex_count {
+                        if live_triangles[cursor] > 0 {
+                            next = Some(cursor);
+                            break;
+                        }
+                        cursor += 1;
+                    }
+                }
+
+                if next.is_some() && output.len() / 3 > *cluster_starts.last().unwrap_or(&0) {
+                    cluster_starts.push(output.len() / 3);
+                }
+                next
+            }
+        };
+    }
+
+    TipsifyResult {
+        indices: output,
+        cluster_starts,
+    }
+}
+
+// reorders triangles for post-transform vertex cache efficiency
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize, cache_size: usize) -> Vec<u32> {
+    tipsify(indices, vertex_count, cache_size).indices
+}
+
+// reorders triangles for the vertex cache, then sorts the resulting clusters so the
+// ones facing away from the mesh center, which tend to occlude the rest, come first
+pub fn optimize_vertex_cache_and_overdraw(
+    vertices: &[Vertex],
+    indices: &[u32],
+    cache_size: usize,
+) -> Vec<u32> {
+    let tipsified = tipsify(indices, vertices.len(), cache_size);
+    let triangles = tipsified.indices.chunks_exact(3).collect::<Vec<_>>();
+
+    let triangle_data = triangles
+        .iter()
+        .map(|triangle| {
+            let p0 = vertices[triangle[0] as usize].position;
+            let p1 = vertices[triangle[1] as usize].position;
+            let p2 = vertices[triangle[2] as usize].position;
+            // the cross product carries the area as its length
+            ((p0 + p1 + p2) / 3.0, (p1 - p0).cross(p2 - p0))
+        })
+        .collect::<Vec<(Vec3, Vec3)>>();
+
+    let total_area = triangle_data
+        .iter()
+        .map(|(_, normal)| normal.length())
+        .sum::<f32>();
+    if total_area <= 0.0 {
+        return tipsified.indices;
+    }
+    let mesh_centroid = triangle_data
+        .iter()
+        .map(|(centroid, normal)| *centroid * normal.length())
+        .sum::<Vec3>()
+        / total_area;
+
+    let mut cluster_ends = tipsified.cluster_starts[1..].to_vec();
+    cluster_ends.push(triangles.len());
+
+    let mut clusters = tipsified
+        .cluster_starts
+        .iter()
+        .zip(cluster_ends)
+        .filter(|(start, end)| *start < end)
+        .map(|(&start, end)| {
+            let (area, centroid, normal) = triangle_data[start..end].iter().fold(
+                (0.0f32, Vec3::ZERO, Vec3::ZERO),
+                |(area, centroid, normal), (triangle_centroid, triangle_normal)| {
+                    let triangle_area = triangle_normal.length();
+                    (
+                        area + triangle_area,
+                        centroid + *triangle_centroid * triangle_area,
+                        normal + *triangle_normal,
+                    )
+                },
+            );
+            let centroid = if area > 0.0 {
+                centroid / area
+            } else {
+                mesh_centroid
+            };
+            let occlusion = (centroid - mesh_centroid).dot(normal.normalize_or_zero());
+            (start..end, occlusion)
+        })
+        .collect::<Vec<_>>();
+
+    clusters.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    clusters
+        .into_iter()
+        .flat_map(|(range, _)| triangles[range].iter().flat_map(|triangle| triangle.iter()))
+        .copied()
+        .collect()
+}
+
+// reorders the vertices in the order they are first referenced and drops unreferenced
+// ones, returns the remap table from old to new vertex index (u32::MAX for dropped ones)
+pub fn optimize_vertex_fetch(vertices: &mut Vec<Vertex>, indices: &mut [u32]) -> Vec<u32> {
+    let mut remap = vec![u32::MAX; vertices.len()];
+    let mut reordered = Vec::with_capacity(vertices.len());
+
+    for index in indices.iter_mut() {
+        let old = *index as usize;
+        if remap[old] == u32::MAX {
+            remap[old] = reordered.len() as u32;
+            reordered.push(vertices[old]);
+        }
+        *index = remap[old];
+    }
+
+    *vertices = reordered;
+    remap
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OptimizationReport {
+    pub before: VertexCacheStats,
+    pub after: VertexCacheStats,
+}
+
+impl std::fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ACMR {:.3} -> {:.3}, ATVR {:.3} -> {:.3}",
+            self.before.acmr, self.after.acmr, self.before.atvr, self.after.atvr
+        )
+    }
+}
+
+// runs the full pass on a submesh: triangle order for cache and overdraw, then vertex
+// order for fetch locality, the lod index lists are reordered and remapped as well
+pub fn optimize_submesh(submesh: &mut SubMesh) -> OptimizationReport {
+    let vertex_count = submesh.vertices.len();
+    if submesh.indices.len() < 3
+        || submesh
+            .indices
+            .iter()
+            .any(|&index| index as usize >= vertex_count)
+    {
+        return OptimizationReport::default();
+    }
+
+    let before = analyze_vertex_cache(&submesh.indices, vertex_count, DEFAULT_CACHE_SIZE);
+
+    submesh.indices =
+        optimize_vertex_cache_and_overdraw(&submesh.vertices, &submesh.indices, DEFAULT_CACHE_SIZE);
+    for lod in &mut submesh.lods {
+        lod.indices = optimize_vertex_cache(&lod.indices, vertex_count, DEFAULT_CACHE_SIZE);
+    }
+
+    let geometry = &mut **submesh;
+    let remap = optimize_vertex_fetch(&mut geometry.vertices, &mut geometry.indices);
+    for lod in &mut submesh.lods {
+        // lods are built from the same vertices, so every index they use is still mapped
+        for index in &mut lod.indices {
+            *index = remap[*index as usize];
+        }
+    }
+    if let Some(polygons) = &mut submesh.polygons {
+        for index in &mut polygons.indices {
+            *index = remap[*index as usize];
+        }
+    }
+
+    submesh.geometry_changed();
+
+    OptimizationReport {
+        before,
+        after: analyze_vertex_cache(&submesh.indices, submesh.vertices.len(), DEFAULT_CACHE_SIZE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::primitives;
+
+    // the triangles of an index list by their positions, each rotated to start at its
+    // smallest corner so the winding is kept
+    fn triangle_set(vertices: &[Vertex], indices: &[u32]) -> Vec<[[u32; 3]; 3]> {
+        let mut triangles = indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let corners = [0, 1, 2].map(|corner| {
+                    let position = vertices[triangle[corner] as usize].position;
+                    position.to_array().map(f32::to_bits)
+                });
+                let start = (0..3).min_by_key(|&corner| corners[corner]).unwrap();
+                [0, 1, 2].map(|offset| corners[(start + offset) % 3])
+            })
+            .collect::<Vec<_>>();
+        triangles.sort();
+        triangles
+    }
+
+    // the triangles of a grid in a scrambled order, bad for any cache
+    fn scrambled_plane() -> SubMesh {
+        let mut submesh = primitives::create_plane(1.0, 1.0, 24).submeshes.remove(0);
+        let mut triangles = submesh
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect::<Vec<_>>();
+        let mut state = 12345u32;
+        for i in (1..triangles.len()).rev() {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            triangles.swap(i, state as usize % (i + 1));
+        }
+        submesh.indices = triangles.concat();
+        submesh
+    }
+
+    #[test]
+    fn optimize_submesh_keeps_the_triangles_and_lowers_the_acmr() {
+        let mut submesh = scrambled_plane();
+        let triangles = triangle_set(&submesh.vertices, &submesh.indices);
+
+        let report = optimize_submesh(&mut submesh);
+
+        assert_eq!(triangle_set(&submesh.vertices, &submesh.indices), triangles);
+        assert!(report.after.acmr < report.before.acmr * 0.6);
+        assert!(report.after.atvr >= 1.0);
+    }
+
+    #[test]
+    fn vertex_fetch_orders_vertices_by_first_use() {
+        let mut vertices = (0..5)
+            .map(|i| Vertex {
+                position: Vec3::splat(i as f32),
+                normal: Vec3::Z,
+                tex_coord: glam::Vec2::ZERO,
+            })
+            .collect::<Vec<_>>();
+        let mut indices = vec![3, 1, 4, 4, 1, 0];
+
+        let remap = optimize_vertex_fetch(&mut vertices, &mut indices);
+
+        assert_eq!(indices, vec![0, 1, 2, 2, 1, 3]);
+        assert_eq!(remap, vec![3, 1, u32::MAX, 0, 2]);
+        let positions = vertices
+            .iter()
+            .map(|vertex| vertex.position.x)
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![3.0, 1.0, 4.0, 0.0]);
+    }
+}
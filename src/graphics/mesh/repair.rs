@@ -0,0 +1,418 @@
+use std::collections::{HashMap, VecDeque};
+
+use glam::{Vec2, Vec3};
+
+use crate::graphics::{
+    mesh::{
+        SubMesh,
+        validate::{is_degenerate, is_finite, weld_positions},
+    },
+    vertex::Vertex,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct RepairOptions {
+    // merge vertices whose attributes all lie within this distance of each other
+    pub weld: bool,
+    pub weld_epsilon: f32,
+    pub remove_degenerates: bool,
+    // holes outlined by at most this many edges get triangulated, zero disables filling
+    pub max_hole_edges: usize,
+    pub unify_winding: bool,
+}
+
+impl Default for RepairOptions {
+    fn default() -> Self {
+        RepairOptions {
+            weld: true,
+            weld_epsilon: 1e-6,
+            remove_degenerates: true,
+            max_hole_edges: 8,
+            unify_winding: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RepairReport {
+    pub removed_invalid_triangles: usize,
+    pub sanitized_vertices: usize,
+    pub welded_vertices: usize,
+    pub removed_degenerate_triangles: usize,
+    pub flipped_triangles: usize,
+    pub filled_holes: usize,
+    pub removed_unused_vertices: usize,
+}
+
+impl std::fmt::Display for RepairReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "removed {} invalid and {} degenerate triangles, sanitized {} vertices, \
+             welded {} vertices, flipped {} triangles, filled {} holes, \
+             removed {} unused vertices",
+            self.removed_invalid_triangles,
+            self.removed_degenerate_triangles,
+            self.sanitized_vertices,
+            self.welded_vertices,
+            self.flipped_triangles,
+            self.filled_holes,
+            self.removed_unused_vertices
+        )
+    }
+}
+
+// fixes what `validate` reports, the lods are dropped since the triangles change, the
+// polygons are remapped when only vertices were merged and dropped when triangles were
+// removed, flipped or added
+pub fn repair(submesh: &mut SubMesh, options: &RepairOptions) -> RepairReport {
+    let mut report = RepairReport::default();
+    let mut polygons = submesh.polygons.take();
+    let geometry = &mut **submesh;
+    let vertices = &mut geometry.vertices;
+
+    // triangles that can not be drawn at all: out of range indices, a trailing
+    // incomplete triangle or a corner without a usable position
+    let mut triangles = Vec::with_capacity(geometry.indices.len() / 3);
+    for triangle in geometry.indices.chunks(3) {
+        let valid = triangle.len() == 3
+            && triangle.iter().all(|&index| {
+                vertices
+                    .get(index as usize)
+                    .is_some_and(|vertex| vertex.position.is_finite())
+            });
+
+        if valid {
+            triangles.push([triangle[0], triangle[1], triangle[2]]);
+        } else {
+            report.removed_invalid_triangles += 1;
+        }
+    }
+
+    // a broken normal or uv alone does not hide the triangle, so zero it instead
+    for vertex in vertices.iter_mut().filter(|vertex| !is_finite(vertex)) {
+        if !vertex.normal.is_finite() {
+            vertex.normal = Vec3::ZERO;
+        }
+        if !vertex.tex_coord.is_finite() {
+            vertex.tex_coord = Vec2::ZERO;
+        }
+        report.sanitized_vertices += 1;
+    }
+
+    if options.weld {
+        let remap = weld(vertices, options.weld_epsilon);
+        for triangle in &mut triangles {
+            for index in triangle.iter_mut() {
+                *index = remap[*index as usize];
+            }
+        }
+        if let Some(polygons) = &mut polygons {
+            for index in &mut polygons.indices {
+                *index = remap[*index as usize];
+            }
+        }
+        report.welded_vertices = remap
+            .iter()
+            .enumerate()
+            .filter(|&(i, &index)| i as u32 != index)
+            .count();
+    }
+
+    if options.remove_degenerates {
+        let before = triangles.len();
+        triangles.retain(|triangle| !is_degenerate(vertices, triangle));
+        report.removed_degenerate_triangles = before - triangles.len();
+    }
+
+    if options.unify_winding {
+        report.flipped_triangles = unify_winding(vertices, &mut triangles);
+    }
+
+    if options.max_hole_edges >= 3 {
+        report.filled_holes = fill_holes(vertices, &mut triangles, options.max_hole_edges);
+    }
+
+    geometry.indices = triangles.into_iter().flatten().collect();
+
+    let before = geometry.vertices.len();
+    let remap =
+        super::optimize::optimize_vertex_fetch(&mut geometry.vertices, &mut geometry.indices);
+    report.removed_unused_vertices = before - geometry.vertices.len();
+
+    // the triangles still fan the polygons as long as none of them were touched
+    let topology_changed = report.removed_invalid_triangles
+        + report.removed_degenerate_triangles
+        + report.flipped_triangles
+        + report.filled_holes
+        > 0;
+    submesh.polygons = polygons
+        .filter(|_| !topology_changed)
+        .map(|mut polygons| {
+            for index in &mut polygons.indices {
+                *index = remap[*index as usize];
+            }
+            polygons
+        })
+        // a polygon with less than three corners has no triangles to keep its vertices
+        .filter(|polygons| polygons.indices.iter().all(|&index| index != u32::MAX));
+    submesh.lods.clear();
+    submesh.geometry_changed();
+
+    report
+}
+
+// merges vertices with matching attributes, returns the first vertex each one matches
+fn weld(vertices: &[Vertex], epsilon: f32) -> Vec<u32> {
+    let scale = 1.0 / epsilon.max(f32::MIN_POSITIVE);
+    let quantize = |value: f32| (value * scale).round() as i64;
+
+    let mut first_with = HashMap::<[i64; 8], u32>::new();
+    vertices
+        .iter()
+        .enumerate()
+        .map(|(i, vertex)| {
+            let key = [
+                quantize(vertex.position.x),
+                quantize(vertex.position.y),
+                quantize(vertex.position.z),
+                quantize(vertex.normal.x),
+                quantize(vertex.normal.y),
+                quantize(vertex.normal.z),
+                quantize(vertex.tex_coord.x),
+                quantize(vertex.tex_coord.y),
+            ];
+            *first_with.entry(key).or_insert(i as u32)
+        })
+        .collect()
+}
+
+// makes neighboring triangles walk their shared edge in opposite directions, then turns
+// closed parts inside out if they enclose a negative volume, returns the flipped count
+fn unify_winding(vertices: &[Vertex], triangles: &mut [[u32; 3]]) -> usize {
+    let welded = weld_positions(vertices);
+    let corners = |triangle: &[u32; 3]| triangle.map(|index| welded[index as usize]);
+
+    let mut edge_triangles = HashMap::<(u32, u32), Vec<usize>>::new();
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        let c = corners(triangle);
+        for corner in 0..3 {
+            let (a, b) = (c[corner], c[(corner + 1) % 3]);
+            edge_triangles
+                .entry((a.min(b), a.max(b)))
+                .or_default()
+                .push(triangle_index);
+        }
+    }
+
+    let mut visited = vec![false; triangles.len()];
+    let mut flipped = vec![false; triangles.len()];
+
+    for seed in 0..triangles.len() {
+        if visited[seed] {
+            continue;
+        }
+
+        let mut component = vec![seed];
+        let mut queue = VecDeque::from([seed]);
+        visited[seed] = true;
+        let mut closed = true;
+
+        while let Some(current) = queue.pop_front() {
+            let c = corners(&triangles[current]);
+            for corner in 0..3 {
+                let (a, b) = (c[corner], c[(corner + 1) % 3]);
+                let shared = &edge_triangles[&(a.min(b), a.max(b))];
+
+                // orientation is only defined across manifold edges
+                if shared.len() != 2 {
+                    closed = false;
+                    continue;
+                }
+
+                let neighbor = if shared[0] == current {
+                    shared[1]
+                } else {
+                    shared[0]
+                };
+                if visited[neighbor] {
+                    continue;
+                }
+
+                // a consistent neighbor walks the edge from b to a
+                let n = corners(&triangles[neighbor]);
+                let same_direction = (0..3).any(|k| n[k] == a && n[(k + 1) % 3] == b);
+                if same_direction {
+                    triangles[neighbor].swap(1, 2);
+                    flipped[neighbor] = !flipped[neighbor];
+                }
+
+                visited[neighbor] = true;
+                component.push(neighbor);
+                queue.push_back(neighbor);
+            }
+        }
+
+        if closed {
+            let volume = component
+                .iter()
+                .map(|&triangle| {
+                    let [p0, p1, p2] =
+                        triangles[triangle].map(|index| vertices[index as usize].position);
+                    p0.dot(p1.cross(p2))
+                })
+                .sum::<f32>();
+
+            if volume < 0.0 {
+                for &triangle in &component {
+                    triangles[triangle].swap(1, 2);
+                    flipped[triangle] = !flipped[triangle];
+                }
+            }
+        }
+    }
+
+    flipped.iter().filter(|flipped| **flipped).count()
+}
+
+// closes boundary loops of at most `max_edges` edges with a triangle fan
+fn fill_holes(vertices: &[Vertex], triangles: &mut Vec<[u32; 3]>, max_edges: usize) -> usize {
+    let welded = weld_positions(vertices);
+
+    let mut directed = HashMap::<(u32, u32), u32>::new();
+    for triangle in triangles.iter() {
+        for corner in 0..3 {
+            let a = welded[triangle[corner] as usize];
+            let b = welded[triangle[(corner + 1) % 3] as usize];
+            *directed.entry((a, b)).or_default() += 1;
+        }
+    }
+
+    // boundary half edges keyed by their start, remembering the actual vertex used there
+    let mut boundary = HashMap::<u32, Vec<(u32, u32)>>::new();
+    for triangle in triangles.iter() {
+        for corner in 0..3 {
+            let (va, vb) = (triangle[corner], triangle[(corner + 1) % 3]);
+            let (a, b) = (welded[va as usize], welded[vb as usize]);
+            if !directed.contains_key(&(b, a)) && directed[&(a, b)] == 1 {
+                boundary.entry(a).or_default().push((b, va));
+            }
+        }
+    }
+
+    let mut filled = 0;
+    let starts = {
+        let mut starts = boundary.keys().copied().collect::<Vec<_>>();
+        starts.sort_unstable();
+        starts
+    };
+
+    for start in starts {
+        let Some(&(next, start_vertex)) = boundary.get(&start).and_then(|edges| edges.first())
+        else {
+            continue;
+        };
+
+        // walk the loop, giving up on branching or overly long boundaries
+        let mut loop_vertices = vec![start_vertex];
+        let mut loop_positions = vec![start];
+        let mut current = next;
+        let mut valid = true;
+        while current != start {
+            match boundary.get(&current).map(|edges| edges.as_slice()) {
+                Some([(following, vertex)]) if loop_vertices.len() < max_edges => {
+                    loop_vertices.push(*vertex);
+                    loop_positions.push(current);
+                    current = *following;
+                }
+                _ => {
+                    valid = false;
+                    break;
+                }
+            }
+        }
+
+        for position in &loop_positions {
+            boundary.remove(position);
+        }
+
+        if !valid || loop_vertices.len() < 3 {
+            continue;
+        }
+
+        // the loop follows the existing triangles, the patch has to run the other way
+        for i in 1..loop_vertices.len() - 1 {
+            triangles.push([loop_vertices[0], loop_vertices[i + 1], loop_vertices[i]]);
+        }
+        filled += 1;
+    }
+
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::{
+        mesh::{Polygons, validate},
+        primitives,
+    };
+
+    #[test]
+    fn repair_fixes_what_validate_reports() {
+        let mut submesh = primitives::create_icosphere(1.0, 2).submeshes.remove(0);
+        let triangle_count = submesh.indices.len() / 3;
+        {
+            let indices = &mut submesh.indices;
+            // a hole, a flipped triangle, a degenerate one and one past the vertices
+            indices.drain(0..3);
+            indices.swap(3, 4);
+            indices.extend_from_slice(&[5, 5, 6]);
+            indices.extend_from_slice(&[0, 1, 100_000]);
+        }
+        assert!(!validate(&submesh).is_valid());
+
+        let report = repair(&mut submesh, &RepairOptions::default());
+
+        assert_eq!(report.removed_invalid_triangles, 1);
+        assert_eq!(report.removed_degenerate_triangles, 1);
+        assert_eq!(report.flipped_triangles, 1);
+        assert_eq!(report.filled_holes, 1);
+        let validation = validate(&submesh);
+        assert!(validation.is_valid(), "{}", validation);
+        assert_eq!(validation.boundary_edges, 0);
+        assert_eq!(submesh.indices.len() / 3, triangle_count);
+    }
+
+    #[test]
+    fn welding_keeps_the_polygons() {
+        // a 3x3 grid of quads, every quad with its own four corners, the border is too long
+        // to be taken for a hole
+        let mut vertices = Vec::new();
+        let mut polygons = Polygons::default();
+        for x in 0..3 {
+            for z in 0..3 {
+                let start = vertices.len() as u32;
+                for (dx, dz) in [(0, 0), (0, 1), (1, 1), (1, 0)] {
+                    let position = Vec3::new((x + dx) as f32, 0.0, (z + dz) as f32);
+                    vertices.push(Vertex {
+                        position,
+                        normal: Vec3::Y,
+                        tex_coord: Vec2::new(position.x, position.z),
+                    });
+                }
+                polygons.push(&[start, start + 1, start + 2, start + 3]);
+            }
+        }
+        let mut submesh = SubMesh::new(vertices, polygons.triangulate(), None);
+        submesh.polygons = Some(polygons);
+
+        let report = repair(&mut submesh, &RepairOptions::default());
+
+        assert_eq!(report.welded_vertices, 36 - 16);
+        assert_eq!(submesh.vertices.len(), 16);
+        let polygons = submesh.polygons.as_ref().expect("the quads are kept");
+        assert_eq!(polygons.len(), 9);
+        assert_eq!(polygons.triangulate(), submesh.indices);
+    }
+}
@@ -0,0 +1,568 @@
+use std::{cmp::Ordering, collections::BinaryHeap, collections::HashMap};
+
+use glam::{DVec3, Vec3};
+
+use crate::graphics::vertex::Vertex;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SimplifyOptions {
+    // stop once the triangle count is at or below this
+    pub target_triangle_count: usize,
+    // stop before a collapse would move the surface further than this, in object space units
+    pub max_error: f32,
+}
+
+impl Default for SimplifyOptions {
+    fn default() -> Self {
+        SimplifyOptions {
+            target_triangle_count: 0,
+            max_error: f32::MAX,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Simplified {
+    // triangles over the original vertex buffer
+    pub indices: Vec<u32>,
+    // largest geometric error introduced by any collapse
+    pub error: f32,
+}
+
+// Garland-Heckbert quadric error metric simplification by half edge collapses.
+// Vertices are only removed, never moved, so the result keeps using the original
+// vertex buffer. Vertices on an edge shared by anything other than exactly two
+// triangles are locked, which keeps open borders as well as uv and normal seams
+// (seams are split vertices, so they show up as borders in the index topology).
+pub fn simplify(vertices: &[Vertex], indices: &[u32], options: &SimplifyOptions) -> Simplified {
+    let mut triangles = indices
+        .chunks_exact(3)
+        .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+        .filter(|triangle| {
+            triangle
+                .iter()
+                .all(|&index| (index as usize) < vertices.len())
+                && triangle[0] != triangle[1]
+                && triangle[1] != triangle[2]
+                && triangle[2] != triangle[0]
+        })
+        .collect::<Vec<[u32; 3]>>();
+
+    let mut live_triangles = triangles.len();
+    if live_triangles <= options.target_triangle_count {
+        return Simplified {
+            indices: triangles.into_iter().flatten().collect(),
+            error: 0.0,
+        };
+    }
+
+    let positions = vertices
+        .iter()
+        .map(|vertex| vertex.position.as_dvec3())
+        .collect::<Vec<_>>();
+
+    let mut quadrics = vec![Quadric::default(); vertices.len()];
+    let mut vertex_triangles = vec![Vec::<u32>::new(); vertices.len()];
+    let mut edge_uses = HashMap::<(u32, u32), u32>::new();
+
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        let quadric = Quadric::from_triangle(
+            positions[triangle[0] as usize],
+            positions[triangle[1] as usize],
+            positions[triangle[2] as usize],
+        );
+
+        for corner in 0..3 {
+            let a = triangle[corner];
+            let b = triangle[(corner + 1) % 3];
+            quadrics[a as usize].add(&quadric);
+            vertex_triangles[a as usize].push(triangle_index as u32);
+            *edge_uses.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+
+    let mut locked = vec![false; vertices.len()];
+    for (&(a, b), &uses) in &edge_uses {
+        if uses != 2 {
+            locked[a as usize] = true;
+            locked[b as usize] = true;
+        }
+    }
+
+    let mut removed_triangles = vec![false; triangles.len()];
+    let mut removed_vertices = vec![false; vertices.len()];
+    let mut versions = vec![0u32; vertices.len()];
+    let mut heap = BinaryHeap::new();
+
+    for &(a, b) in edge_uses.keys() {
+        push_collapse(&mut heap, a, b, &locked, &quadrics, &positions, &versions);
+        push_collapse(&mut heap, b, a, &locked, &quadrics, &positions, &versions);
+    }
+
+    let max_cost = (options.max_error as f64).powi(2);
+    let mut error = 0.0f64;
+
+    while live_triangles > options.target_triangle_count {
+        let Some(collapse) = heap.pop() else {
+            break;
+        };
+
+        let (from, to) = (collapse.from as usize, collapse.to as usize);
+        if removed_vertices[from]
+            || removed_vertices[to]
+            || versions[from] != collapse.from_version
+            || versions[to] != collapse.to_version
+        {
+            continue; // stale entry
+        }
+
+        if collapse.cost > max_cost {
+            break;
+        }
+
+        if !can_collapse(
+            from,
+            to,
+            &triangles,
+            &removed_triangles,
+            &vertex_triangles,
+            &positions,
+        ) {
+            continue;
+        }
+
+        // apply the collapse
+        let from_triangles = std::mem::take(&mut vertex_triangles[from]);
+        for &triangle_index in &from_triangles {
+            if removed_triangles[triangle_index as usize] {
+                continue;
+            }
+
+            let triangle = &mut triangles[triangle_index as usize];
+            if triangle.contains(&(to as u32)) {
+                removed_triangles[triangle_index as usize] = true;
+                live_triangles -= 1;
+            } else {
+                for index in triangle.iter_mut() {
+                    if *index == from as u32 {
+                        *index = to as u32;
+                    }
+                }
+                vertex_triangles[to].push(triangle_index);
+            }
+        }
+        vertex_triangles[to].retain(|&triangle| !removed_triangles[triangle as usize]);
+
+        let from_quadric = quadrics[from];
+        quadrics[to].add(&from_quadric);
+        removed_vertices[from] = true;
+        error = error.max(collapse.cost);
+
+        // only the quadric of the surviving vertex changed, so only its edges need new costs
+        versions[to] += 1;
+        for neighbor in neighbors(to, &triangles, &vertex_triangles) {
+            push_collapse(
+                &mut heap, neighbor, to as u32, &locked, &quadrics, &positions, &versions,
+            );
+            push_collapse(
+                &mut heap, to as u32, neighbor, &locked, &quadrics, &positions, &versions,
+            );
+        }
+    }
+
+    Simplified {
+        indices: triangles
+            .iter()
+            .zip(&removed_triangles)
+            .filter(|(_, removed)| !**removed)
+            .flat_map(|(triangle, _)| *triangle)
+            .collect(),
+        error: error.sqrt() as f32,
+    }
+}
+
+// symmetric 4x4 matrix, stored as the upper triangle, plus the total area of its planes
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric {
+    a: [f64; 10],
+    weight: f64,
+}
+
+impl Quadric {
+    // plane quadric of the triangle, weighted by its area
+    fn from_triangle(p0: DVec3, p1: DVec3, p2: DVec3) -> Self {
+        let cross = (p1 - p0).cross(p2 - p0);
+        let area = cross.length() * 0.5;
+        let Some(normal) = cross.try_normalize() else {
+            return Quadric::default();
+        };
+        let d = -normal.dot(p0);
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+
+        Quadric {
+            a: [
+                a * a * area,
+                a * b * area,
+                a * c * area,
+                a * d * area,
+                b * b * area,
+                b * c * area,
+                b * d * area,
+                c * c * area,
+                c * d * area,
+                d * d * area,
+            ],
+            weight: area,
+        }
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.a.iter_mut().zip(other.a) {
+            *a += b;
+        }
+        self.weight += other.weight;
+    }
+
+    // area weighted mean of the squared distances of the point to the planes
+    fn evaluate(&self, p: DVec3) -> f64 {
+        let q = &self.a;
+        let (x, y, z) = (p.x, p.y, p.z);
+        let value = q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9];
+        if self.weight > 0.0 {
+            (value / self.weight).max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    from_version: u32,
+    to_version: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // reversed so the binary heap pops the cheapest collapse first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| other.from.cmp(&self.from))
+            .then_with(|| other.to.cmp(&self.to))
+    }
+}
+
+fn push_collapse(
+    heap: &mut BinaryHeap<Collapse>,
+    from: u32,
+    to: u32,
+    locked: &[bool],
+    quadrics: &[Quadric],
+    positions: &[DVec3],
+    versions: &[u32],
+) {
+    if locked[from as usize] {
+        return;
+    }
+
+    let mut quadric = quadrics[from as usize];
+    quadric.add(&quadrics[to as usize]);
+
+    heap.push(Collapse {
+        cost: quadric.evaluate(positions[to as usize]),
+        from,
+        to,
+        from_version: versions[from as usize],
+        to_version: versions[to as usize],
+    });
+}
+
+fn neighbors(vertex: usize, triangles: &[[u32; 3]], vertex_triangles: &[Vec<u32>]) -> Vec<u32> {
+    let mut neighbors = vertex_triangles[vertex]
+        .iter()
+        .flat_map(|&triangle| triangles[triangle as usize])
+        .filter(|&index| index as usize != vertex)
+        .collect::<Vec<_>>();
+    neighbors.sort_unstable();
+    neighbors.dedup();
+    neighbors
+}
+
+fn can_collapse(
+    from: usize,
+    to: usize,
+    triangles: &[[u32; 3]],
+    removed_triangles: &[bool],
+    vertex_triangles: &[Vec<u32>],
+    positions: &[DVec3],
+) -> bool {
+    let live = |triangle: &&u32| !removed_triangles[**triangle as usize];
+
+    // the vertices opposite to the collapsed edge
+    let mut opposite = vertex_triangles[from]
+        .iter()
+        .filter(live)
+        .map(|&triangle| triangles[triangle as usize])
+        .filter(|triangle| triangle.contains(&(to as u32)))
+        .flat_map(|triangle| triangle.into_iter())
+        .filter(|&index| index as usize != from && index as usize != to)
+        .collect::<Vec<_>>();
+
+    if opposite.is_empty() {
+        return false; // no longer an edge
+    }
+    opposite.sort_unstable();
+    opposite.dedup();
+
+    // link condition: the only shared neighbors may be the opposite vertices,
+    // otherwise the collapse would pinch the surface into a non-manifold edge
+    let from_neighbors = neighbors(from, triangles, vertex_triangles);
+    let to_neighbors = neighbors(to, triangles, vertex_triangles);
+    let shared = from_neighbors
+        .iter()
+        .filter(|neighbor| to_neighbors.binary_search(neighbor).is_ok())
+        .count();
+    if shared != opposite.len() {
+        return false;
+    }
+
+    // reject collapses that flip or degenerate one of the remaining triangles
+    for &triangle_index in vertex_triangles[from].iter().filter(live) {
+        let triangle = triangles[triangle_index as usize];
+        if triangle.contains(&(to as u32)) {
+            continue;
+        }
+
+        let corner = |index: u32| positions[index as usize];
+        let before = (corner(triangle[1]) - corner(triangle[0]))
+            .cross(corner(triangle[2]) - corner(triangle[0]));
+
+        let moved = triangle.map(|index| {
+            if index as usize == from {
+                positions[to]
+            } else {
+                corner(index)
+            }
+        });
+        let after = (moved[1] - moved[0]).cross(moved[2] - moved[0]);
+
+        if after.length_squared() <= f64::EPSILON * before.length_squared()
+            || before.dot(after) <= 0.0
+        {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lod {
+    pub indices: Vec<u32>,
+    pub error: f32,
+    // the level is used once the object covers less than this fraction of the screen height
+    pub screen_size: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LodChainOptions {
+    pub levels: usize,
+    // fraction of triangles kept from one level to the next
+    pub reduction: f32,
+    pub max_error: f32,
+    // pixels of screen height the errors of the levels are measured against, a level is
+    // used once its error covers less than a pixel
+    pub screen_height: f32,
+}
+
+impl Default for LodChainOptions {
+    fn default() -> Self {
+        LodChainOptions {
+            levels: 4,
+            reduction: 0.5,
+            max_error: f32::MAX,
+            screen_height: 1080.0,
+        }
+    }
+}
+
+// each level is simplified from the previous one, stops early once a level
+// no longer removes any triangles
+pub fn generate_lod_chain(
+    vertices: &[Vertex],
+    indices: &[u32],
+    options: &LodChainOptions,
+) -> Vec<Lod> {
+    let mut lods = Vec::<Lod>::new();
+    let mut source = indices.to_vec();
+    let positions = vertices
+        .iter()
+        .map(|vertex| vertex.position)
+        .collect::<Vec<_>>();
+    let radius = crate::graphics::bounds::BoundingSphere::from_points(&positions).radius;
+
+    for _ in 0..options.levels {
+        let triangle_count = source.len() / 3;
+        let target_triangle_count = (triangle_count as f32 * options.reduction) as usize;
+
+        let simplified = simplify(
+            vertices,
+            &source,
+            &SimplifyOptions {
+                target_triangle_count,
+                max_error: options.max_error,
+            },
+        );
+
+        if simplified.indices.len() >= source.len() || simplified.indices.is_empty() {
+            break;
+        }
+
+        let error = simplified
+            .error
+            .max(lods.last().map_or(0.0, |lod: &Lod| lod.error));
+        let screen_size = lod_screen_size(error, radius, options.screen_height)
+            .min(lods.last().map_or(1.0, |lod| lod.screen_size));
+
+        source = simplified.indices.clone();
+        lods.push(Lod {
+            indices: simplified.indices,
+            error,
+            screen_size,
+        });
+    }
+
+    lods
+}
+
+// the fraction of the screen height below which an error of `error` spans less than a
+// pixel, on an object of bounding radius `radius` that covers the fraction with its
+// diameter, see `projected_screen_size`
+pub fn lod_screen_size(error: f32, radius: f32, screen_height: f32) -> f32 {
+    if error <= 0.0 {
+        return 1.0;
+    }
+
+    (2.0 * radius / (error * screen_height)).min(1.0)
+}
+
+// fraction of the screen height covered by a bounding sphere seen from the camera
+pub fn projected_screen_size(
+    center: Vec3,
+    radius: f32,
+    camera: &crate::graphics::camera::Camera,
+) -> f32 {
+    let distance = center.distance(camera.position);
+    if distance <= radius {
+        return 1.0;
+    }
+
+    radius / (distance * (camera.fov.to_radians() * 0.5).tan())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::primitives;
+
+    #[test]
+    fn simplify_reaches_the_target_on_the_original_vertices() {
+        let mesh = primitives::create_icosphere(1.0, 3);
+        let submesh = &mesh.submeshes[0];
+        let target_triangle_count = submesh.indices.len() / 3 / 4;
+
+        let simplified = simplify(
+            &submesh.vertices,
+            &submesh.indices,
+            &SimplifyOptions {
+                target_triangle_count,
+                ..Default::default()
+            },
+        );
+
+        assert!(simplified.indices.len() / 3 <= target_triangle_count);
+        assert!(!simplified.indices.is_empty());
+        assert!(
+            simplified
+                .indices
+                .iter()
+                .all(|&index| (index as usize) < submesh.vertices.len())
+        );
+        assert!(
+            simplified
+                .indices
+                .chunks_exact(3)
+                .all(|triangle| triangle[0] != triangle[1]
+                    && triangle[1] != triangle[2]
+                    && triangle[2] != triangle[0])
+        );
+        assert!(simplified.error > 0.0 && simplified.error < 1.0);
+    }
+
+    #[test]
+    fn max_error_stops_the_collapses() {
+        let mesh = primitives::create_icosphere(1.0, 3);
+        let submesh = &mesh.submeshes[0];
+
+        let simplified = simplify(
+            &submesh.vertices,
+            &submesh.indices,
+            &SimplifyOptions {
+                target_triangle_count: 0,
+                max_error: 0.01,
+            },
+        );
+
+        assert!(simplified.error <= 0.01);
+        assert!(simplified.indices.len() > 12 * 3);
+    }
+
+    #[test]
+    fn levels_switch_where_their_error_drops_below_a_pixel() {
+        let mesh = primitives::create_icosphere(2.0, 4);
+        let submesh = &mesh.submeshes[0];
+        let options = LodChainOptions::default();
+
+        let lods = generate_lod_chain(&submesh.vertices, &submesh.indices, &options);
+
+        assert!(lods.len() >= 2);
+        for pair in lods.windows(2) {
+            assert!(pair[1].indices.len() < pair[0].indices.len());
+            assert!(pair[1].error >= pair[0].error);
+            assert!(pair[1].screen_size <= pair[0].screen_size);
+        }
+        for lod in &lods {
+            // the error in pixels on an object of this screen size, the diameter covers
+            // the fraction of the screen height
+            let pixels = lod.screen_size * lod.error / (2.0 * 2.0) * options.screen_height;
+            assert!(lod.screen_size == 1.0 || (pixels - 1.0).abs() < 1e-3);
+        }
+    }
+}
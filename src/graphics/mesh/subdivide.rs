@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+
+use glam::{Vec2, Vec3};
+
+use crate::graphics::{
+    error::GraphicsError,
+    mesh::{
+        Polygons, SubMesh,
+        half_edge::{FaceId, HalfEdgeId, HalfEdgeMesh, VertexId},
+    },
+    vertex::Vertex,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubdivisionScheme {
+    // triangles only, every triangle becomes four
+    Loop,
+    // any polygons, every face becomes one quad per corner
+    CatmullClark,
+}
+
+impl SubdivisionScheme {
+    pub fn for_mesh(mesh: &HalfEdgeMesh) -> Self {
+        if mesh.face_ids().all(|face| mesh.face_degree(face) == 3) {
+            SubdivisionScheme::Loop
+        } else {
+            SubdivisionScheme::CatmullClark
+        }
+    }
+}
+
+// one subdivision step, positions are smoothed while texture coordinates are
+// interpolated linearly within each face so uv seams stay intact, borders are
+// treated as creases and smoothed along themselves only
+pub fn subdivide(
+    mesh: &HalfEdgeMesh,
+    scheme: SubdivisionScheme,
+) -> Result<HalfEdgeMesh, GraphicsError> {
+    match scheme {
+        SubdivisionScheme::Loop => subdivide_loop(mesh),
+        SubdivisionScheme::CatmullClark => subdivide_catmull_clark(mesh),
+    }
+}
+
+// picks the scheme from the faces of the submesh and gives the result smooth normals
+pub fn subdivide_submesh(submesh: &SubMesh, levels: u32) -> Result<SubMesh, GraphicsError> {
+    let mut mesh = HalfEdgeMesh::from_submesh(submesh)?;
+    for _ in 0..levels {
+        mesh = subdivide(&mesh, SubdivisionScheme::for_mesh(&mesh))?;
+    }
+    if levels > 0 {
+        mesh.compute_smooth_normals();
+    }
+
+    Ok(mesh.to_submesh(submesh.material))
+}
+
+fn subdivide_loop(mesh: &HalfEdgeMesh) -> Result<HalfEdgeMesh, GraphicsError> {
+    let vertex_points = mesh
+        .vertex_ids()
+        .map(|vertex| {
+            let position = mesh.vertex(vertex).position;
+            let point = if mesh.is_boundary_vertex(vertex) {
+                match border_neighbors(mesh, vertex) {
+                    Some((prev, next)) => position * 0.75 + (prev + next) * 0.125,
+                    None => position,
+                }
+            } else {
+                let (sum, valence) = neighbor_sum(mesh, vertex);
+                let beta = if valence == 3 {
+                    3.0 / 16.0
+                } else {
+                    3.0 / (8.0 * valence as f32)
+                };
+                position * (1.0 - valence as f32 * beta) + sum * beta
+            };
+            (vertex, point)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let edge_points = mesh
+        .edge_ids()
+        .map(|edge| {
+            let (a, b) = edge_positions(mesh, edge);
+            let point = if mesh.is_boundary_edge(edge) {
+                (a + b) * 0.5
+            } else {
+                let twin = mesh.half_edge(edge).twin;
+                let c = mesh.vertex(opposite(mesh, edge)).position;
+                let d = mesh.vertex(opposite(mesh, twin)).position;
+                (a + b) * 0.375 + (c + d) * 0.125
+            };
+            (edge, point)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut refined = Refined::default();
+    for face in mesh.face_ids() {
+        let corners = mesh.face_half_edges(face).collect::<Vec<_>>();
+        if corners.len() != 3 {
+            return Err(GraphicsError::InvalidMeshTopology(format!(
+                "loop subdivision needs triangles, face {} has {} corners",
+                face.0,
+                corners.len()
+            )));
+        }
+
+        let vertex = |i: usize| {
+            let half_edge = mesh.half_edge(corners[i]);
+            (vertex_points[&half_edge.origin], half_edge.tex_coord)
+        };
+        let edge = |i: usize| {
+            (
+                edge_points[&edge_key(mesh, corners[i])],
+                mesh.half_edge(corners[i])
+                    .tex_coord
+                    .lerp(mesh.half_edge(corners[(i + 1) % 3]).tex_coord, 0.5),
+            )
+        };
+
+        refined.push_face(&[vertex(0), edge(0), edge(2)]);
+        refined.push_face(&[edge(0), vertex(1), edge(1)]);
+        refined.push_face(&[edge(2), edge(1), vertex(2)]);
+        refined.push_face(&[edge(0), edge(1), edge(2)]);
+    }
+
+    refined.build()
+}
+
+fn subdivide_catmull_clark(mesh: &HalfEdgeMesh) -> Result<HalfEdgeMesh, GraphicsError> {
+    let face_points = mesh
+        .face_ids()
+        .map(|face| (face, mesh.face_centroid(face)))
+        .collect::<HashMap<FaceId, Vec3>>();
+
+    let edge_points = mesh
+        .edge_ids()
+        .map(|edge| {
+            let (a, b) = edge_positions(mesh, edge);
+            let twin = mesh.half_edge(edge).twin;
+            let point = match (mesh.half_edge(edge).face, mesh.half_edge(twin).face) {
+                (Some(left), Some(right)) => {
+                    (a + b + face_points[&left] + face_points[&right]) * 0.25
+                }
+                _ => (a + b) * 0.5,
+            };
+            (edge, point)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let vertex_points = mesh
+        .vertex_ids()
+        .map(|vertex| {
+            let position = mesh.vertex(vertex).position;
+            let point = if mesh.is_boundary_vertex(vertex) {
+                match border_neighbors(mesh, vertex) {
+                    Some((prev, next)) => (prev + position * 6.0 + next) * 0.125,
+                    None => position,
+                }
+            } else {
+                let (neighbors, valence) = neighbor_sum(mesh, vertex);
+                let n = valence as f32;
+                let faces = mesh
+                    .vertex_faces(vertex)
+                    .map(|face| face_points[&face])
+                    .sum::<Vec3>()
+                    / n;
+                let edge_midpoints = (position * n + neighbors) * 0.5 / n;
+                (faces + edge_midpoints * 2.0 + position * (n - 3.0)) / n
+            };
+            (vertex, point)
+        })
+        .collect::<HashMap<VertexId, Vec3>>();
+
+    let mut refined = Refined::default();
+    for face in mesh.face_ids() {
+        let corners = mesh.face_half_edges(face).collect::<Vec<_>>();
+        let count = corners.len();
+        let tex_coords = corners
+            .iter()
+            .map(|&corner| mesh.half_edge(corner).tex_coord)
+            .collect::<Vec<_>>();
+        let center = (
+            face_points[&face],
+            tex_coords.iter().sum::<Vec2>() / count as f32,
+        );
+
+        let edge = |i: usize| {
+            (
+                edge_points[&edge_key(mesh, corners[i])],
+                tex_coords[i].lerp(tex_coords[(i + 1) % count], 0.5),
+            )
+        };
+
+        for i in 0..count {
+            let vertex = (
+                vertex_points[&mesh.half_edge(corners[i]).origin],
+                tex_coords[i],
+            );
+            refined.push_face(&[vertex, edge(i), center, edge((i + count - 1) % count)]);
+        }
+    }
+
+    refined.build()
+}
+
+// faces of the next level, each corner keeps its own texture coordinate and
+// `HalfEdgeMesh::from_faces` merges the corners sharing a position
+#[derive(Default)]
+struct Refined {
+    vertices: Vec<Vertex>,
+    polygons: Polygons,
+}
+
+impl Refined {
+    fn push_face(&mut self, corners: &[(Vec3, Vec2)]) {
+        let first = self.vertices.len() as u32;
+        self.vertices
+            .extend(corners.iter().map(|&(position, tex_coord)| Vertex {
+                position,
+                normal: Vec3::ZERO,
+                tex_coord,
+            }));
+        self.polygons
+            .push(&(first..self.vertices.len() as u32).collect::<Vec<_>>());
+    }
+
+    fn build(self) -> Result<HalfEdgeMesh, GraphicsError> {
+        HalfEdgeMesh::from_faces(&self.vertices, self.polygons.iter())
+    }
+}
+
+// the edges are keyed by their lower half edge, like `HalfEdgeMesh::edge_ids`
+fn edge_key(mesh: &HalfEdgeMesh, half_edge: HalfEdgeId) -> HalfEdgeId {
+    half_edge.min(mesh.half_edge(half_edge).twin)
+}
+
+fn edge_positions(mesh: &HalfEdgeMesh, edge: HalfEdgeId) -> (Vec3, Vec3) {
+    (
+        mesh.vertex(mesh.half_edge(edge).origin).position,
+        mesh.vertex(mesh.target(edge)).position,
+    )
+}
+
+// the corner of a triangle across from the half edge
+fn opposite(mesh: &HalfEdgeMesh, half_edge: HalfEdgeId) -> VertexId {
+    mesh.half_edge(mesh.half_edge(half_edge).prev).origin
+}
+
+fn neighbor_sum(mesh: &HalfEdgeMesh, vertex: VertexId) -> (Vec3, usize) {
+    mesh.vertex_neighbors(vertex)
+        .fold((Vec3::ZERO, 0), |(sum, count), neighbor| {
+            (sum + mesh.vertex(neighbor).position, count + 1)
+        })
+}
+
+// the two neighbors along the border, the stored half edge of a border vertex is the
+// border half edge leaving it
+fn border_neighbors(mesh: &HalfEdgeMesh, vertex: VertexId) -> Option<(Vec3, Vec3)> {
+    let half_edge = mesh.vertex(vertex).half_edge?;
+    if mesh.half_edge(half_edge).face.is_some() {
+        return None;
+    }
+
+    let prev = mesh.half_edge(mesh.half_edge(half_edge).prev).origin;
+    let next = mesh.target(half_edge);
+    Some((mesh.vertex(prev).position, mesh.vertex(next).position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::primitives;
+
+    // a cube from -1 to 1 with one quad per side sharing the eight corners
+    fn quad_cube() -> SubMesh {
+        let vertices = (0..8)
+            .map(|corner| Vertex {
+                position: Vec3::new(
+                    if corner & 1 == 0 { -1.0 } else { 1.0 },
+                    if corner & 2 == 0 { -1.0 } else { 1.0 },
+                    if corner & 4 == 0 { -1.0 } else { 1.0 },
+                ),
+                normal: Vec3::ZERO,
+                tex_coord: Vec2::ZERO,
+            })
+            .collect();
+        let mut polygons = Polygons::default();
+        for face in [
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ] {
+            polygons.push(&face);
+        }
+        let mut submesh = SubMesh::new(vertices, polygons.triangulate(), None);
+        submesh.polygons = Some(polygons);
+        submesh
+    }
+
+    #[test]
+    fn loop_splits_every_triangle_into_four() {
+        let submesh = primitives::create_icosphere(1.0, 0).submeshes.remove(0);
+        let mesh = HalfEdgeMesh::from_submesh(&submesh).unwrap();
+        assert_eq!(SubdivisionScheme::for_mesh(&mesh), SubdivisionScheme::Loop);
+
+        let subdivided = subdivide(&mesh, SubdivisionScheme::Loop).unwrap();
+
+        assert_eq!(
+            subdivided.vertex_count(),
+            mesh.vertex_count() + mesh.edge_count()
+        );
+        assert_eq!(subdivided.face_count(), mesh.face_count() * 4);
+        assert_eq!(
+            subdivided.edge_count(),
+            mesh.edge_count() * 2 + mesh.face_count() * 3
+        );
+        assert!(
+            subdivided
+                .edge_ids()
+                .all(|edge| !subdivided.is_boundary_edge(edge))
+        );
+        // smoothing pulls the points inside the convex icosahedron
+        assert!(subdivided.vertex_ids().all(|vertex| {
+            let length = subdivided.vertex(vertex).position.length();
+            length > 0.7 && length <= 1.0 + 1e-5
+        }));
+    }
+
+    #[test]
+    fn catmull_clark_moves_the_cube_corners_to_five_ninths() {
+        let mesh = HalfEdgeMesh::from_submesh(&quad_cube()).unwrap();
+        assert_eq!(
+            SubdivisionScheme::for_mesh(&mesh),
+            SubdivisionScheme::CatmullClark
+        );
+
+        let subdivided = subdivide(&mesh, SubdivisionScheme::CatmullClark).unwrap();
+
+        assert_eq!(subdivided.vertex_count(), 8 + 12 + 6);
+        assert_eq!(subdivided.edge_count(), 48);
+        assert_eq!(subdivided.face_count(), 24);
+        assert!(
+            subdivided
+                .face_ids()
+                .all(|face| subdivided.face_degree(face) == 4)
+        );
+        let corners = subdivided
+            .vertex_ids()
+            .map(|vertex| subdivided.vertex(vertex).position)
+            .filter(|position| position.x.abs() == position.y.abs())
+            .filter(|position| position.y.abs() == position.z.abs())
+            .collect::<Vec<_>>();
+        assert_eq!(corners.len(), 8);
+        assert!(
+            corners
+                .iter()
+                .all(|corner| corner.abs().abs_diff_eq(Vec3::splat(5.0 / 9.0), 1e-5))
+        );
+    }
+
+    #[test]
+    fn subdivided_submeshes_keep_their_quads() {
+        let subdivided = subdivide_submesh(&quad_cube(), 2).unwrap();
+
+        let polygons = subdivided.polygons.as_ref().expect("quads stay polygons");
+        assert_eq!(polygons.len(), 6 * 4 * 4);
+        assert!(polygons.iter().all(|corners| corners.len() == 4));
+        assert_eq!(subdivided.indices.len(), polygons.len() * 2 * 3);
+        assert!(
+            subdivided
+                .vertices
+                .iter()
+                .all(|vertex| (vertex.normal.length() - 1.0).abs() < 1e-4)
+        );
+    }
+}
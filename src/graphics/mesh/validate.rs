@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use crate::graphics::{mesh::SubMesh, vertex::Vertex};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    // vertices with a NaN or infinite position, normal or texture coordinate
+    pub non_finite_vertices: Vec<u32>,
+    // positions in the index buffer that point past the vertex buffer
+    pub out_of_range_indices: Vec<usize>,
+    // the index count is not a multiple of three
+    pub incomplete_triangle: bool,
+    // triangles with a repeated vertex or zero area
+    pub degenerate_triangles: Vec<u32>,
+    // edges shared by more than two triangles
+    pub non_manifold_edges: Vec<(u32, u32)>,
+    // edges whose two triangles walk them in the same direction, i.e. one of them is flipped
+    pub inconsistent_winding_edges: Vec<(u32, u32)>,
+    // edges used by a single triangle, these outline holes or open borders
+    pub boundary_edges: usize,
+    pub unused_vertices: usize,
+}
+
+impl ValidationReport {
+    // boundary edges and unused vertices are fine for open meshes, everything else renders wrong
+    pub fn is_valid(&self) -> bool {
+        self.non_finite_vertices.is_empty()
+            && self.out_of_range_indices.is_empty()
+            && !self.incomplete_triangle
+            && self.degenerate_triangles.is_empty()
+            && self.non_manifold_edges.is_empty()
+            && self.inconsistent_winding_edges.is_empty()
+    }
+}
+
+impl std::fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} vertices, {} triangles: {} non-finite vertices, {} out of range indices, \
+             {} degenerate triangles, {} non-manifold edges, {} inconsistent winding edges, \
+             {} boundary edges, {} unused vertices{}",
+            self.vertex_count,
+            self.triangle_count,
+            self.non_finite_vertices.len(),
+            self.out_of_range_indices.len(),
+            self.degenerate_triangles.len(),
+            self.non_manifold_edges.len(),
+            self.inconsistent_winding_edges.len(),
+            self.boundary_edges,
+            self.unused_vertices,
+            if self.incomplete_triangle {
+                ", trailing incomplete triangle"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+pub fn validate(submesh: &SubMesh) -> ValidationReport {
+    validate_geometry(&submesh.vertices, &submesh.indices)
+}
+
+pub fn validate_geometry(vertices: &[Vertex], indices: &[u32]) -> ValidationReport {
+    let mut report = ValidationReport {
+        vertex_count: vertices.len(),
+        triangle_count: indices.len() / 3,
+        incomplete_triangle: !indices.len().is_multiple_of(3),
+        ..Default::default()
+    };
+
+    report.non_finite_vertices = vertices
+        .iter()
+        .enumerate()
+        .filter(|(_, vertex)| !is_finite(vertex))
+        .map(|(i, _)| i as u32)
+        .collect();
+
+    report.out_of_range_indices = indices
+        .iter()
+        .enumerate()
+        .filter(|(_, index)| **index as usize >= vertices.len())
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut used = vec![false; vertices.len()];
+    for &index in indices {
+        if let Some(used) = used.get_mut(index as usize) {
+            *used = true;
+        }
+    }
+    report.unused_vertices = used.iter().filter(|used| !**used).count();
+
+    let welded = weld_positions(vertices);
+
+    // directed edge counts over welded positions, so uv and normal seams are not borders
+    let mut directed_edges = HashMap::<(u32, u32), u32>::new();
+
+    for (triangle_index, triangle) in indices.chunks_exact(3).enumerate() {
+        if triangle
+            .iter()
+            .any(|&index| index as usize >= vertices.len())
+        {
+            continue;
+        }
+
+        if is_degenerate(vertices, triangle) {
+            report.degenerate_triangles.push(triangle_index as u32);
+            continue;
+        }
+
+        let corners = [
+            welded[triangle[0] as usize],
+            welded[triangle[1] as usize],
+            welded[triangle[2] as usize],
+        ];
+        for corner in 0..3 {
+            let edge = (corners[corner], corners[(corner + 1) % 3]);
+            *directed_edges.entry(edge).or_default() += 1;
+        }
+    }
+
+    let mut visited = std::collections::HashSet::new();
+    for (&(a, b), &count) in &directed_edges {
+        let key = (a.min(b), a.max(b));
+        if !visited.insert(key) {
+            continue;
+        }
+
+        let reverse = directed_edges.get(&(b, a)).copied().unwrap_or(0);
+        let forward = if a == key.0 { count } else { reverse };
+        let backward = if a == key.0 { reverse } else { count };
+
+        match forward + backward {
+            1 => report.boundary_edges += 1,
+            2 if forward == 2 || backward == 2 => report.inconsistent_winding_edges.push(key),
+            2 => {}
+            _ => report.non_manifold_edges.push(key),
+        }
+    }
+
+    report.non_manifold_edges.sort_unstable();
+    report.inconsistent_winding_edges.sort_unstable();
+
+    report
+}
+
+pub(crate) fn is_finite(vertex: &Vertex) -> bool {
+    vertex.position.is_finite() && vertex.normal.is_finite() && vertex.tex_coord.is_finite()
+}
+
+pub(crate) fn is_degenerate(vertices: &[Vertex], triangle: &[u32]) -> bool {
+    if triangle[0] == triangle[1] || triangle[1] == triangle[2] || triangle[2] == triangle[0] {
+        return true;
+    }
+
+    let p0 = vertices[triangle[0] as usize].position;
+    let p1 = vertices[triangle[1] as usize].position;
+    let p2 = vertices[triangle[2] as usize].position;
+
+    let area = (p1 - p0).cross(p2 - p0).length();
+    let longest_edge = (p1 - p0)
+        .length_squared()
+        .max((p2 - p1).length_squared())
+        .max((p0 - p2).length_squared());
+
+    // relative to the triangle size, so tiny but well shaped triangles are kept,
+    // a NaN area counts as degenerate too
+    area.is_nan() || area <= longest_edge * 1e-7
+}
+
+// maps every vertex to the first vertex with the exact same position
+pub(crate) fn weld_positions(vertices: &[Vertex]) -> Vec<u32> {
+    let mut first_at = HashMap::<[u32; 3], u32>::new();
+    vertices
+        .iter()
+        .enumerate()
+        .map(|(i, vertex)| {
+            // +0.0 and -0.0 are the same position
+            let key = vertex.position.to_array().map(|c| (c + 0.0).to_bits());
+            *first_at.entry(key).or_insert(i as u32)
+        })
+        .collect()
+}
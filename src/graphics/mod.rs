@@ -1,9 +1,20 @@
+pub mod arena;
+pub mod assets;
 pub mod backend;
+pub mod bounds;
+pub mod bvh;
 pub mod camera;
 pub mod error;
+pub mod frustum;
 pub mod light;
 pub mod material;
 pub mod mesh;
+pub mod path_tracer;
 pub mod primitives;
+pub mod raycast;
+pub mod rendered_image;
 pub mod scene;
+pub mod shadow;
+pub mod text;
+pub mod texture;
 pub mod vertex;
@@ -0,0 +1,466 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::thread::JoinHandle;
+
+use glam::{Vec2, Vec3};
+
+use crate::graphics::bvh::Bvh;
+use crate::graphics::camera::Camera;
+use crate::graphics::light::Light;
+use crate::graphics::material::GpuMaterials;
+use crate::graphics::raycast::Hit;
+use crate::graphics::rendered_image::RenderedImage;
+use crate::graphics::scene::{Scene, SceneRevision};
+
+// rays leaving a surface start this far above it so they do not hit it again
+const RAY_OFFSET: f32 = 1e-4;
+// bounces before russian roulette may end a path
+const MIN_BOUNCES: u32 = 3;
+
+// an offline renderer for reference images, every pass adds one sample per pixel to
+// the average, so the image gets less noisy the longer it runs
+#[derive(Debug, Clone)]
+pub struct PathTracer {
+    pub width: u32,
+    pub height: u32,
+    // bounces after the camera ray before a path ends
+    pub max_bounces: u32,
+    // the sum of the samples of every pixel, rows from top to bottom
+    accumulated: Vec<Vec3>,
+    samples: u32,
+    // stays valid until an object is added, removed or moved, see `reset`
+    bvh: Bvh,
+}
+
+impl PathTracer {
+    pub fn new(scene: &Scene, width: u32, height: u32) -> Self {
+        PathTracer {
+            width,
+            height,
+            max_bounces: 8,
+            accumulated: vec![Vec3::ZERO; width as usize * height as usize],
+            samples: 0,
+            bvh: scene.build_bvh(),
+        }
+    }
+
+    // starts over, after the scene changed
+    pub fn reset(&mut self, scene: &Scene) {
+        self.accumulated.fill(Vec3::ZERO);
+        self.samples = 0;
+        self.bvh = scene.build_bvh();
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    // traces one path per pixel, the rows are split between the available threads
+    pub fn render_pass(&mut self, scene: &Scene, camera: &Camera) {
+        let width = self.width as usize;
+        if width == 0 || self.height == 0 {
+            return;
+        }
+
+        let tracer = Tracer {
+            scene,
+            bvh: &self.bvh,
+            max_bounces: self.max_bounces,
+        };
+        let aspect_ratio = self.width as f32 / self.height as f32;
+        let inverse_view_projection = camera.view_projection_matrix(aspect_ratio).inverse();
+        let size = Vec2::new(self.width as f32, self.height as f32);
+        let sample = self.samples;
+
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        let rows_per_thread = (self.height as usize).div_ceil(threads);
+
+        std::thread::scope(|scope| {
+            for (band, pixels) in self
+                .accumulated
+                .chunks_mut(rows_per_thread * width)
+                .enumerate()
+            {
+                let tracer = &tracer;
+                scope.spawn(move || {
+                    for (offset, pixel) in pixels.iter_mut().enumerate() {
+                        let index = band * rows_per_thread * width + offset;
+                        let mut random = Random::new(index as u64, sample);
+
+                        // jittered inside the pixel, the top row is at -1 like in vulkan
+                        let position = Vec2::new((index % width) as f32, (index / width) as f32)
+                            + Vec2::new(random.next_f32(), random.next_f32());
+                        let ndc = position / size * 2.0 - 1.0;
+                        let near = inverse_view_projection.project_point3(ndc.extend(0.0));
+                        let far = inverse_view_projection.project_point3(ndc.extend(1.0));
+
+                        *pixel += tracer.trace(near, far - near, &mut random);
+                    }
+                });
+            }
+        });
+
+        self.samples += 1;
+    }
+
+    // the average of all passes so far, linear like the scene
+    pub fn image(&self) -> RenderedImage {
+        let scale = 1.0 / self.samples.max(1) as f32;
+        RenderedImage {
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .accumulated
+                .iter()
+                .map(|sum| (*sum * scale).extend(1.0).to_array())
+                .collect(),
+        }
+    }
+}
+
+// runs the passes of a path tracer on its own thread over a copy of the scene, so the
+// editor only polls how far it got instead of tracing while it draws
+#[derive(Debug)]
+pub struct BackgroundRender {
+    // the scene the copy was taken at, the render is out of date once it changes
+    pub revision: SceneRevision,
+    // the view it is traced from, moving or switching the camera also makes it out of date
+    pub camera: Camera,
+    pub target_samples: u32,
+    samples: Arc<AtomicU32>,
+    cancel: Arc<AtomicBool>,
+    worker: Option<JoinHandle<RenderedImage>>,
+}
+
+impl BackgroundRender {
+    pub fn start(
+        scene: &Scene,
+        camera: &Camera,
+        width: u32,
+        height: u32,
+        target_samples: u32,
+    ) -> Self {
+        let samples = Arc::new(AtomicU32::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let worker = {
+            let scene = scene.clone();
+            let camera = camera.clone();
+            let samples = samples.clone();
+            let cancel = cancel.clone();
+            std::thread::spawn(move || {
+                let mut tracer = PathTracer::new(&scene, width, height);
+                while tracer.samples() < target_samples && !cancel.load(Ordering::Relaxed) {
+                    tracer.render_pass(&scene, &camera);
+                    samples.store(tracer.samples(), Ordering::Relaxed);
+                }
+                tracer.image()
+            })
+        };
+
+        BackgroundRender {
+            revision: scene.revision(),
+            camera: camera.clone(),
+            target_samples,
+            samples,
+            cancel,
+            worker: Some(worker),
+        }
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples.load(Ordering::Relaxed)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.worker
+            .as_ref()
+            .is_none_or(|worker| worker.is_finished())
+    }
+
+    // waits for the worker and takes its image, none if it panicked
+    pub fn finish(mut self) -> Option<RenderedImage> {
+        self.worker.take()?.join().ok()
+    }
+}
+
+impl Drop for BackgroundRender {
+    // a dropped render stops after the pass it is in, nobody waits for it
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+struct Tracer<'a> {
+    scene: &'a Scene,
+    bvh: &'a Bvh,
+    max_bounces: u32,
+}
+
+impl Tracer<'_> {
+    // the light arriving along the ray, the lights are sampled at every bounce and
+    // emissive surfaces count when a path hits them
+    fn trace(&self, origin: Vec3, direction: Vec3, random: &mut Random) -> Vec3 {
+        let mut radiance = Vec3::ZERO;
+        let mut throughput = Vec3::ONE;
+        let mut origin = origin;
+        let mut direction = direction;
+
+        for bounce in 0..=self.max_bounces {
+            let Some(hit) = self.scene.raycast_with_bvh(self.bvh, origin, direction) else {
+                break; // nothing lights the scene from outside
+            };
+            let surface = Surface::at(self.scene, &hit);
+            radiance += throughput * surface.emission;
+
+            let direction_to_eye = -direction.normalize();
+            // the triangles are double sided
+            let normal = if hit.normal.dot(direction_to_eye) < 0.0 {
+                -hit.normal
+            } else {
+                hit.normal
+            };
+            let position = hit.position + normal * RAY_OFFSET;
+
+            radiance += throughput
+                * self.direct_light(&surface, position, normal, direction_to_eye, random);
+
+            let Some((next, weight)) = surface.sample(normal, direction_to_eye, random) else {
+                break;
+            };
+            throughput *= weight;
+
+            if bounce >= MIN_BOUNCES {
+                let survival = throughput.max_element().min(0.95);
+                if random.next_f32() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+
+            origin = position;
+            direction = next;
+        }
+
+        radiance
+    }
+
+    // the light reaching the point straight from every light, one sample per area light
+    fn direct_light(
+        &self,
+        surface: &Surface,
+        position: Vec3,
+        normal: Vec3,
+        direction_to_eye: Vec3,
+        random: &mut Random,
+    ) -> Vec3 {
+        let mut total = Vec3::ZERO;
+
+        for light in self.scene.lights.values() {
+            let (to_light, distance, incoming) = match light {
+                Light::Point(point) => {
+                    let to_light = point.position - position;
+                    let distance = to_light.length();
+                    (
+                        to_light,
+                        distance,
+                        point.color * point.intensity / (distance * distance),
+                    )
+                }
+                Light::Spot(spot) => {
+                    let to_light = spot.position - position;
+                    let distance = to_light.length();
+                    (
+                        to_light,
+                        distance,
+                        spot.color
+                            * spot.intensity
+                            * spot.cone_falloff(-to_light)
+                            * spot.range_falloff(distance)
+                            / (distance * distance),
+                    )
+                }
+                Light::Directional(directional) => (
+                    -directional.direction,
+                    f32::MAX,
+                    directional.color * directional.intensity,
+                ),
+                Light::Area(area) => {
+                    let (corner, width, height) = area.edges();
+                    let point = corner + width * random.next_f32() + height * random.next_f32();
+                    let to_light = point - position;
+                    let distance = to_light.length();
+                    // only the front side shines
+                    let cosine = area
+                        .direction
+                        .normalize_or_zero()
+                        .dot(-to_light / distance)
+                        .max(0.0);
+                    let light_area = width.cross(height).length();
+                    (
+                        to_light,
+                        distance,
+                        area.color * area.intensity * cosine * light_area / (distance * distance),
+                    )
+                }
+            };
+
+            let Some(light_direction) = to_light.try_normalize() else {
+                continue;
+            };
+            let cosine = normal.dot(light_direction);
+            if cosine <= 0.0 || incoming == Vec3::ZERO || !distance.is_finite() {
+                continue;
+            }
+            // lights can be told not to cast shadows, like in the rasterizers
+            let casts_shadows = match light {
+                Light::Directional(directional) => directional.cast_shadows,
+                Light::Point(point) => point.cast_shadows,
+                _ => true,
+            };
+            if casts_shadows && !self.visible(position, light_direction, distance) {
+                continue;
+            }
+
+            total +=
+                incoming * surface.evaluate(normal, direction_to_eye, light_direction) * cosine;
+        }
+
+        total
+    }
+
+    fn visible(&self, origin: Vec3, direction: Vec3, distance: f32) -> bool {
+        self.scene
+            .raycast_with_bvh(self.bvh, origin, direction)
+            .is_none_or(|hit| hit.distance + RAY_OFFSET >= distance)
+    }
+}
+
+// a diffuse and a glossy lobe from the material of the real-time renderers, scaled
+// down where the two together would reflect more light than arrives
+struct Surface {
+    diffuse: Vec3,
+    specular: Vec3,
+    exponent: f32,
+    emission: Vec3,
+}
+
+impl Surface {
+    fn at(scene: &Scene, hit: &Hit) -> Self {
+        let material = scene
+            .objects
+            .get(hit.object)
+            .and_then(|object| object.submeshes.get(hit.submesh_index))
+            .and_then(|submesh| submesh.material)
+            .and_then(|material| scene.materials.get(material));
+        let properties = material.map_or_else(GpuMaterials::default, |material| {
+            material.properties.clone()
+        });
+
+        let reflected = (properties.diffuse_color + properties.specular_color).max_element();
+        let scale = if reflected > 1.0 {
+            1.0 / reflected
+        } else {
+            1.0
+        };
+        Surface {
+            diffuse: properties.diffuse_color.max(Vec3::ZERO) * scale,
+            specular: properties.specular_color.max(Vec3::ZERO) * scale,
+            exponent: properties.specular_exponent.max(0.0),
+            emission: material.map_or(Vec3::ZERO, |material| material.emission),
+        }
+    }
+
+    // lambert and normalized phong
+    fn evaluate(&self, normal: Vec3, direction_to_eye: Vec3, light_direction: Vec3) -> Vec3 {
+        let mirrored = normal * 2.0 * normal.dot(direction_to_eye) - direction_to_eye;
+        let glossy = mirrored.dot(light_direction).max(0.0).powf(self.exponent);
+
+        self.diffuse * std::f32::consts::FRAC_1_PI
+            + self.specular * (self.exponent + 2.0) * 0.5 * std::f32::consts::FRAC_1_PI * glossy
+    }
+
+    // picks one of the lobes by how much they reflect, returns the new direction and
+    // the weight of the path, `None` when the path ends here
+    fn sample(
+        &self,
+        normal: Vec3,
+        direction_to_eye: Vec3,
+        random: &mut Random,
+    ) -> Option<(Vec3, Vec3)> {
+        let diffuse = self.diffuse.max_element();
+        let specular = self.specular.max_element();
+        if diffuse + specular <= 0.0 {
+            return None;
+        }
+        let specular_probability = specular / (diffuse + specular);
+
+        if random.next_f32() >= specular_probability {
+            // cosine weighted, the cosine and the pdf cancel out
+            let direction = around(normal, cosine_hemisphere(random));
+            return Some((direction, self.diffuse / (1.0 - specular_probability)));
+        }
+
+        let mirrored = normal * 2.0 * normal.dot(direction_to_eye) - direction_to_eye;
+        let cosine = random.next_f32().powf(1.0 / (self.exponent + 1.0));
+        let sine = (1.0 - cosine * cosine).max(0.0).sqrt();
+        let angle = 2.0 * std::f32::consts::PI * random.next_f32();
+        let direction = around(
+            mirrored,
+            Vec3::new(sine * angle.cos(), sine * angle.sin(), cosine),
+        );
+
+        let cosine_to_normal = normal.dot(direction);
+        if cosine_to_normal <= 0.0 {
+            return None;
+        }
+        let weight =
+            self.specular * (self.exponent + 2.0) / (self.exponent + 1.0) * cosine_to_normal;
+        Some((direction, weight / specular_probability))
+    }
+}
+
+fn cosine_hemisphere(random: &mut Random) -> Vec3 {
+    let radius = random.next_f32().sqrt();
+    let angle = 2.0 * std::f32::consts::PI * random.next_f32();
+    Vec3::new(
+        radius * angle.cos(),
+        radius * angle.sin(),
+        (1.0 - radius * radius).max(0.0).sqrt(),
+    )
+}
+
+// turns a direction around +z into one around the axis
+fn around(axis: Vec3, local: Vec3) -> Vec3 {
+    let (tangent, bitangent) = axis.any_orthonormal_pair();
+    (tangent * local.x + bitangent * local.y + axis * local.z).normalize()
+}
+
+// pcg32, seeded from the pixel and the pass so every pass gets other samples
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(pixel: u64, pass: u32) -> Self {
+        let mut random = Random {
+            state: pixel.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ ((pass as u64) << 32 | pass as u64),
+        };
+        random.next_u32();
+        random
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        xorshifted.rotate_right((state >> 59) as u32)
+    }
+
+    // uniform in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+}
@@ -1,9 +1,182 @@
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+use std::sync::Arc;
+
+use glam::{Vec2, Vec3};
+
 use crate::graphics::{
-    material::Material,
-    mesh::{Mesh, SubMesh},
+    mesh::{Mesh, SubMesh, optimize::optimize_vertex_fetch, validate::is_degenerate},
+    text::{TextOptions, create_text},
     vertex::Vertex,
 };
 
+// parameters of a generated mesh, kept on the mesh so the editor can regenerate it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Primitive {
+    Sphere {
+        radius: f32,
+        segments: u32,
+    },
+    Cube {
+        size: Vec3,
+    },
+    Plane {
+        width: f32,
+        depth: f32,
+        subdivisions: u32,
+    },
+    Cylinder {
+        radius: f32,
+        height: f32,
+        segments: u32,
+    },
+    Cone {
+        radius: f32,
+        height: f32,
+        segments: u32,
+    },
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    },
+    Capsule {
+        radius: f32,
+        // length of the cylindrical part between the two caps
+        height: f32,
+        segments: u32,
+        rings: u32,
+    },
+    Icosphere {
+        radius: f32,
+        subdivisions: u32,
+    },
+    Disc {
+        radius: f32,
+        segments: u32,
+    },
+    Terrain(TerrainOptions),
+    Text(TextOptions),
+}
+
+impl std::fmt::Display for Primitive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Primitive {
+    // every primitive with its default parameters
+    pub const ALL: [Primitive; 11] = [
+        Primitive::Cube { size: Vec3::ONE },
+        Primitive::Plane {
+            width: 2.0,
+            depth: 2.0,
+            subdivisions: 1,
+        },
+        Primitive::Sphere {
+            radius: 1.0,
+            segments: 32,
+        },
+        Primitive::Icosphere {
+            radius: 1.0,
+            subdivisions: 2,
+        },
+        Primitive::Cylinder {
+            radius: 0.5,
+            height: 1.0,
+            segments: 32,
+        },
+        Primitive::Cone {
+            radius: 0.5,
+            height: 1.0,
+            segments: 32,
+        },
+        Primitive::Torus {
+            major_radius: 0.75,
+            minor_radius: 0.25,
+            major_segments: 48,
+            minor_segments: 24,
+        },
+        Primitive::Capsule {
+            radius: 0.5,
+            height: 1.0,
+            segments: 32,
+            rings: 8,
+        },
+        Primitive::Disc {
+            radius: 1.0,
+            segments: 32,
+        },
+        Primitive::Terrain(TerrainOptions::DEFAULT),
+        Primitive::Text(TextOptions::DEFAULT),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Primitive::Sphere { .. } => "Sphere",
+            Primitive::Cube { .. } => "Cube",
+            Primitive::Plane { .. } => "Plane",
+            Primitive::Cylinder { .. } => "Cylinder",
+            Primitive::Cone { .. } => "Cone",
+            Primitive::Torus { .. } => "Torus",
+            Primitive::Capsule { .. } => "Capsule",
+            Primitive::Icosphere { .. } => "Icosphere",
+            Primitive::Disc { .. } => "Disc",
+            Primitive::Terrain(_) => "Terrain",
+            Primitive::Text(_) => "Text",
+        }
+    }
+
+    // centered on the origin with y up
+    pub fn create_mesh(&self) -> Mesh {
+        let mut mesh = match *self {
+            Primitive::Sphere { radius, segments } => {
+                return create_sphere(Vec3::ZERO, radius, segments.max(3)).mesh;
+            }
+            Primitive::Terrain(ref options) => create_terrain(options),
+            Primitive::Text(ref options) => create_text(options),
+            Primitive::Cube { size } => create_cube(size),
+            Primitive::Plane {
+                width,
+                depth,
+                subdivisions,
+            } => create_plane(width, depth, subdivisions),
+            Primitive::Cylinder {
+                radius,
+                height,
+                segments,
+            } => create_cylinder(radius, height, segments),
+            Primitive::Cone {
+                radius,
+                height,
+                segments,
+            } => create_cone(radius, height, segments),
+            Primitive::Torus {
+                major_radius,
+                minor_radius,
+                major_segments,
+                minor_segments,
+            } => create_torus(major_radius, minor_radius, major_segments, minor_segments),
+            Primitive::Capsule {
+                radius,
+                height,
+                segments,
+                rings,
+            } => create_capsule(radius, height, segments, rings),
+            Primitive::Icosphere {
+                radius,
+                subdivisions,
+            } => create_icosphere(radius, subdivisions),
+            Primitive::Disc { radius, segments } => create_disc(radius, segments),
+        };
+
+        mesh.primitive = Some(self.clone());
+        mesh
+    }
+}
+
 pub struct Sphere {
     pub mesh: Mesh,
     pub position: glam::Vec3,
@@ -44,28 +217,581 @@ pub fn create_sphere(position: glam::Vec3, radius: f32, segments: u32) -> Sphere
             let first = (i * (horizontal_segments + 1)) + j;
             let second = first + horizontal_segments + 1;
 
+            // counter clockwise seen from outside
             indices.push(first as u32);
-            indices.push(second as u32);
             indices.push(first as u32 + 1);
+            indices.push(second as u32);
 
             indices.push(second as u32);
-            indices.push(second as u32 + 1);
             indices.push(first as u32 + 1);
+            indices.push(second as u32 + 1);
         }
     }
 
-    let submesh = SubMesh {
-        vertices,
-        indices,
-        material: Material::default(),
-    };
+    let submesh = SubMesh::new(vertices, indices, None);
+    let mut mesh = Mesh::new(vec![submesh], glam::Mat4::IDENTITY);
+    mesh.primitive = Some(Primitive::Sphere { radius, segments });
 
     Sphere {
-        mesh: Mesh {
-            submeshes: vec![submesh],
-            world_transform: glam::Mat4::IDENTITY,
-        },
+        mesh,
         position,
         radius,
     }
 }
+
+pub fn create_cube(size: Vec3) -> Mesh {
+    let mut geometry = Geometry::default();
+    let half = size * 0.5;
+
+    // each face with the direction that is up in its texture
+    let faces = [
+        (Vec3::X, Vec3::Y),
+        (Vec3::NEG_X, Vec3::Y),
+        (Vec3::Y, Vec3::NEG_Z),
+        (Vec3::NEG_Y, Vec3::Z),
+        (Vec3::Z, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::Y),
+    ];
+
+    for (normal, up) in faces {
+        let right = up.cross(normal);
+        geometry.push_grid(1, &[0.0, 1.0], |u, v| {
+            let position = normal + right * (u * 2.0 - 1.0) + up * (1.0 - v * 2.0);
+            (position * half, normal)
+        });
+    }
+
+    geometry.into_mesh()
+}
+
+// in the xz plane facing up
+pub fn create_plane(width: f32, depth: f32, subdivisions: u32) -> Mesh {
+    let mut geometry = Geometry::default();
+    let subdivisions = subdivisions.max(1);
+
+    geometry.push_grid(subdivisions, &uniform_rows(subdivisions), |u, v| {
+        let position = Vec3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth);
+        (position, Vec3::Y)
+    });
+
+    geometry.into_mesh()
+}
+
+pub fn create_cylinder(radius: f32, height: f32, segments: u32) -> Mesh {
+    let mut geometry = Geometry::default();
+    let segments = segments.max(3);
+    let half_height = height * 0.5;
+
+    geometry.push_grid(segments, &[0.0, 1.0], |u, v| {
+        let direction = around(u);
+        let position = direction * radius + Vec3::Y * (half_height - v * height);
+        (position, direction)
+    });
+    geometry.push_disc(half_height, radius, segments, true);
+    geometry.push_disc(-half_height, radius, segments, false);
+
+    geometry.into_mesh()
+}
+
+// apex at the top, base at the bottom
+pub fn create_cone(radius: f32, height: f32, segments: u32) -> Mesh {
+    let mut geometry = Geometry::default();
+    let segments = segments.max(3);
+    let half_height = height * 0.5;
+
+    geometry.push_grid(segments, &[0.0, 1.0], |u, v| {
+        let direction = around(u);
+        let position = direction * (radius * v) + Vec3::Y * (half_height - v * height);
+        let normal = (direction * height + Vec3::Y * radius).normalize_or_zero();
+        (position, normal)
+    });
+    geometry.push_disc(-half_height, radius, segments, false);
+
+    geometry.into_mesh()
+}
+
+// lying in the xz plane around the y axis
+pub fn create_torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+) -> Mesh {
+    let mut geometry = Geometry::default();
+    let minor_segments = minor_segments.max(3);
+
+    // the texture runs around the ring and down the outside of the tube, starting on top
+    geometry.push_grid(
+        major_segments.max(3),
+        &uniform_rows(minor_segments),
+        |u, v| {
+            let direction = around(u);
+            let angle = v.fract() * TAU;
+            let normal = direction * angle.sin() + Vec3::Y * angle.cos();
+            (direction * major_radius + normal * minor_radius, normal)
+        },
+    );
+
+    geometry.into_mesh()
+}
+
+// a cylinder of `height` capped by two hemispheres, `rings` rows per hemisphere
+pub fn create_capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Mesh {
+    let mut geometry = Geometry::default();
+    let rings = rings.max(2);
+    let half_height = height * 0.5;
+
+    // rows are spaced by arc length so the texture is not stretched along the caps
+    let cap_length = radius * PI * 0.5;
+    let total_length = cap_length * 2.0 + height;
+    let mut rows = Vec::with_capacity(rings as usize * 2 + 2);
+    for ring in 0..=rings {
+        rows.push(cap_length * ring as f32 / rings as f32 / total_length);
+    }
+    for ring in 0..=rings {
+        rows.push((cap_length + height + cap_length * ring as f32 / rings as f32) / total_length);
+    }
+
+    geometry.push_grid(segments.max(3), &rows, |u, v| {
+        let length = v * total_length;
+        let radius_length = radius.max(f32::EPSILON);
+
+        // angles are measured from the nearer pole so both poles collapse exactly
+        let (center, normal) = if length <= cap_length {
+            let polar = length / radius_length;
+            (half_height, around(u) * polar.sin() + Vec3::Y * polar.cos())
+        } else if length < cap_length + height {
+            (half_height - (length - cap_length), around(u))
+        } else {
+            let polar = (total_length - length) / radius_length;
+            (
+                -half_height,
+                around(u) * polar.sin() - Vec3::Y * polar.cos(),
+            )
+        };
+
+        (Vec3::Y * center + normal * radius, normal)
+    });
+
+    geometry.into_mesh()
+}
+
+// recursively split icosahedron, evenly distributed triangles unlike `create_sphere`
+pub fn create_icosphere(radius: f32, subdivisions: u32) -> Mesh {
+    let t = (1.0 + 5.0f32.sqrt()) * 0.5;
+    let mut points = [
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ]
+    .map(|point| Vec3::from(point).normalize())
+    .to_vec();
+
+    let mut triangles = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    // every subdivision splits each triangle into four, up to 7 keeps the index count sane
+    for _ in 0..subdivisions.min(7) {
+        let mut midpoints = HashMap::<(u32, u32), u32>::new();
+        let mut midpoint = |a: u32, b: u32| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                points.push(((points[a as usize] + points[b as usize]) * 0.5).normalize());
+                points.len() as u32 - 1
+            })
+        };
+
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    // the texture wraps around the y axis, triangles crossing the seam get their own
+    // vertices with the texture coordinate continued past one
+    let tex_coord = |point: Vec3| {
+        Vec2::new(
+            0.5 + point.x.atan2(point.z) / TAU,
+            point.y.clamp(-1.0, 1.0).acos() / PI,
+        )
+    };
+
+    let mut geometry = Geometry::default();
+    for triangle in triangles {
+        let corners = triangle.map(|index| points[index as usize]);
+        let mut tex_coords = corners.map(tex_coord);
+
+        let max_u = tex_coords.iter().fold(f32::MIN, |max, uv| max.max(uv.x));
+        for uv in &mut tex_coords {
+            if max_u - uv.x > 0.5 {
+                uv.x += 1.0;
+            }
+        }
+
+        // the poles have no longitude, take the one of the opposite edge
+        for corner in 0..3 {
+            if corners[corner].x.abs() < 1e-6 && corners[corner].z.abs() < 1e-6 {
+                tex_coords[corner].x =
+                    (tex_coords[(corner + 1) % 3].x + tex_coords[(corner + 2) % 3].x) * 0.5;
+            }
+        }
+
+        let indices = [0, 1, 2].map(|corner| {
+            geometry.push_vertex(Vertex {
+                position: corners[corner] * radius,
+                normal: corners[corner],
+                tex_coord: tex_coords[corner],
+            })
+        });
+        geometry.indices.extend_from_slice(&indices);
+    }
+
+    geometry.into_mesh()
+}
+
+// in the xz plane facing up
+pub fn create_disc(radius: f32, segments: u32) -> Mesh {
+    let mut geometry = Geometry::default();
+    geometry.push_disc(0.0, radius, segments.max(3), true);
+    geometry.into_mesh()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseKind {
+    // fractal brownian motion, rolling hills
+    Fbm,
+    // inverted absolute noise, sharp mountain ridges
+    Ridged,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseOptions {
+    pub kind: NoiseKind,
+    pub seed: u32,
+    pub octaves: u32,
+    // features across the whole terrain for the first octave
+    pub frequency: f32,
+    // frequency multiplier from one octave to the next
+    pub lacunarity: f32,
+    // amplitude multiplier from one octave to the next
+    pub gain: f32,
+}
+
+impl NoiseOptions {
+    pub const DEFAULT: NoiseOptions = NoiseOptions {
+        kind: NoiseKind::Fbm,
+        seed: 0,
+        octaves: 6,
+        frequency: 3.0,
+        lacunarity: 2.0,
+        gain: 0.5,
+    };
+
+    // value in [0, 1] at the given point
+    pub fn sample(&self, point: Vec2) -> f32 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut total_amplitude = 0.0;
+        let mut sum = 0.0;
+        // ridges are weighted by the octave before so the detail gathers on the ridges
+        let mut weight = 1.0;
+
+        for octave in 0..self.octaves.max(1) {
+            let noise = gradient_noise(point * frequency, self.seed.wrapping_add(octave));
+            let value = match self.kind {
+                NoiseKind::Fbm => noise * 0.5 + 0.5,
+                NoiseKind::Ridged => {
+                    let ridge = (1.0 - noise.abs()).powi(2) * weight;
+                    weight = (ridge * 2.0).clamp(0.0, 1.0);
+                    ridge
+                }
+            };
+
+            sum += value * amplitude;
+            total_amplitude += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+
+        (sum / total_amplitude).clamp(0.0, 1.0)
+    }
+}
+
+impl Default for NoiseOptions {
+    fn default() -> Self {
+        NoiseOptions::DEFAULT
+    }
+}
+
+// grayscale samples in [0, 1], row by row starting at the top of the image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heightmap {
+    pub width: u32,
+    pub height: u32,
+    pub samples: Vec<f32>,
+}
+
+impl Heightmap {
+    // bilinear lookup, (0, 0) is the top left and (1, 1) the bottom right corner
+    pub fn sample(&self, uv: Vec2) -> f32 {
+        if self.width == 0 || self.height == 0 {
+            return 0.0;
+        }
+
+        let x = uv.x.clamp(0.0, 1.0) * (self.width - 1) as f32;
+        let y = uv.y.clamp(0.0, 1.0) * (self.height - 1) as f32;
+        let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let at = |x: u32, y: u32| self.samples[(y * self.width + x) as usize];
+
+        let (tx, ty) = (x.fract(), y.fract());
+        let top = at(x0, y0) + (at(x1, y0) - at(x0, y0)) * tx;
+        let bottom = at(x0, y1) + (at(x1, y1) - at(x0, y1)) * tx;
+        top + (bottom - top) * ty
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeightSource {
+    Noise(NoiseOptions),
+    // shared so regenerating the terrain does not copy the image
+    Heightmap(Arc<Heightmap>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainOptions {
+    // length of the square terrain along x and z
+    pub size: f32,
+    // quads along each side
+    pub resolution: u32,
+    // height of a full white or maximum noise sample
+    pub height_scale: f32,
+    pub source: HeightSource,
+}
+
+impl TerrainOptions {
+    pub const DEFAULT: TerrainOptions = TerrainOptions {
+        size: 10.0,
+        resolution: 128,
+        height_scale: 2.0,
+        source: HeightSource::Noise(NoiseOptions::DEFAULT),
+    };
+}
+
+impl Default for TerrainOptions {
+    fn default() -> Self {
+        TerrainOptions::DEFAULT
+    }
+}
+
+// square grid in the xz plane centered on the origin, the top of the heightmap faces -z
+pub fn create_terrain(options: &TerrainOptions) -> Mesh {
+    let resolution = options.resolution.clamp(1, 4096);
+    let side = resolution as usize + 1;
+
+    let heights = (0..side * side)
+        .map(|i| {
+            let uv = Vec2::new((i % side) as f32, (i / side) as f32) / resolution as f32;
+            let height = match &options.source {
+                HeightSource::Noise(noise) => noise.sample(uv),
+                HeightSource::Heightmap(heightmap) => heightmap.sample(uv),
+            };
+            height * options.height_scale
+        })
+        .collect::<Vec<_>>();
+
+    let spacing = options.size / resolution as f32;
+    let height_at =
+        |column: usize, row: usize| heights[row.min(side - 1) * side + column.min(side - 1)];
+
+    let mut geometry = Geometry::default();
+    geometry.push_grid(resolution, &uniform_rows(resolution), |u, v| {
+        let column = (u * resolution as f32).round() as usize;
+        let row = (v * resolution as f32).round() as usize;
+
+        // central differences, one sided along the border
+        let (left, right) = (column.saturating_sub(1), column + 1);
+        let (up, down) = (row.saturating_sub(1), row + 1);
+        let dx = (height_at(right.min(side - 1), row) - height_at(left, row))
+            / ((right.min(side - 1) - left) as f32 * spacing);
+        let dz = (height_at(column, down.min(side - 1)) - height_at(column, up))
+            / ((down.min(side - 1) - up) as f32 * spacing);
+
+        let position = Vec3::new(
+            (u - 0.5) * options.size,
+            height_at(column, row),
+            (v - 0.5) * options.size,
+        );
+        (position, Vec3::new(-dx, 1.0, -dz).normalize())
+    });
+
+    geometry.into_mesh()
+}
+
+// 2d gradient noise in about [-1, 1], the gradients come from hashing the lattice
+// points with the seed so no permutation table is needed
+fn gradient_noise(point: Vec2, seed: u32) -> f32 {
+    let cell = point.floor();
+    let offset = point - cell;
+    let (x, y) = (cell.x as i32, cell.y as i32);
+
+    let gradient = |dx: i32, dy: i32| {
+        let angle =
+            hash(x.wrapping_add(dx), y.wrapping_add(dy), seed) as f32 / u32::MAX as f32 * TAU;
+        Vec2::new(angle.cos(), angle.sin()).dot(offset - Vec2::new(dx as f32, dy as f32))
+    };
+
+    // quintic fade so the derivatives are continuous across cells
+    let fade = offset * offset * offset * (offset * (offset * 6.0 - 15.0) + 10.0);
+    let top = gradient(0, 0) + (gradient(1, 0) - gradient(0, 0)) * fade.x;
+    let bottom = gradient(0, 1) + (gradient(1, 1) - gradient(0, 1)) * fade.x;
+    (top + (bottom - top) * fade.y) * std::f32::consts::SQRT_2
+}
+
+fn hash(x: i32, y: i32, seed: u32) -> u32 {
+    let mut hash =
+        seed ^ (x as u32).wrapping_mul(0x27d4_eb2d) ^ (y as u32).wrapping_mul(0x1656_67b1);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+// point on the unit circle in the xz plane, turning right when seen from outside
+// starting at +z
+fn around(u: f32) -> Vec3 {
+    // the last column lands exactly on the first one
+    let angle = u.fract() * TAU;
+    Vec3::new(angle.sin(), 0.0, angle.cos())
+}
+
+fn uniform_rows(rows: u32) -> Vec<f32> {
+    (0..=rows).map(|row| row as f32 / rows as f32).collect()
+}
+
+// vertex and index lists that merge identical vertices, shared by the generators
+#[derive(Default)]
+pub(crate) struct Geometry {
+    pub(crate) vertices: Vec<Vertex>,
+    pub(crate) indices: Vec<u32>,
+    lookup: HashMap<Vertex, u32>,
+}
+
+impl Geometry {
+    pub(crate) fn push_vertex(&mut self, vertex: Vertex) -> u32 {
+        *self.lookup.entry(vertex).or_insert_with(|| {
+            self.vertices.push(vertex);
+            self.vertices.len() as u32 - 1
+        })
+    }
+
+    // surface over u going right and v going down as seen from the front, `point`
+    // gives the position and normal, rows that collapse into a point (poles, apex)
+    // produce no triangles
+    fn push_grid(&mut self, columns: u32, rows: &[f32], point: impl Fn(f32, f32) -> (Vec3, Vec3)) {
+        let grid = rows
+            .iter()
+            .map(|&v| {
+                (0..=columns)
+                    .map(|column| {
+                        let u = column as f32 / columns as f32;
+                        let (position, normal) = point(u, v);
+                        self.push_vertex(Vertex {
+                            position,
+                            normal,
+                            tex_coord: Vec2::new(u, v),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        for row in grid.windows(2) {
+            let (top, bottom) = (&row[0], &row[1]);
+            for column in 0..columns as usize {
+                let triangles = [
+                    [top[column], bottom[column], bottom[column + 1]],
+                    [top[column], bottom[column + 1], top[column + 1]],
+                ];
+                for triangle in triangles {
+                    if !is_degenerate(&self.vertices, &triangle) {
+                        self.indices.extend_from_slice(&triangle);
+                    }
+                }
+            }
+        }
+    }
+
+    // triangle fan facing up or down at the given height
+    fn push_disc(&mut self, height: f32, radius: f32, segments: u32, facing_up: bool) {
+        let normal = if facing_up { Vec3::Y } else { Vec3::NEG_Y };
+        // seen from the facing side the texture is upright and not mirrored
+        let tex_coord = |position: Vec3| {
+            let z = if facing_up { position.z } else { -position.z };
+            Vec2::new(0.5 + position.x * 0.5, 0.5 + z * 0.5)
+        };
+
+        let center = self.push_vertex(Vertex {
+            position: Vec3::Y * height,
+            normal,
+            tex_coord: Vec2::splat(0.5),
+        });
+        let ring = (0..=segments)
+            .map(|segment| {
+                let direction = around(segment as f32 / segments as f32);
+                self.push_vertex(Vertex {
+                    position: direction * radius + Vec3::Y * height,
+                    normal,
+                    tex_coord: tex_coord(direction),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for pair in ring.windows(2) {
+            if facing_up {
+                self.indices.extend_from_slice(&[center, pair[0], pair[1]]);
+            } else {
+                self.indices.extend_from_slice(&[center, pair[1], pair[0]]);
+            }
+        }
+    }
+
+    pub(crate) fn into_mesh(mut self) -> Mesh {
+        // collapsed rows leave some vertices unused
+        optimize_vertex_fetch(&mut self.vertices, &mut self.indices);
+        let submesh = SubMesh::new(self.vertices, self.indices, None);
+        Mesh::new(vec![submesh], glam::Mat4::IDENTITY)
+    }
+}
@@ -0,0 +1,234 @@
+use glam::{Mat4, Vec3};
+
+use crate::graphics::{
+    arena::Handle,
+    bounds::Aabb,
+    bvh::Bvh,
+    mesh::{Mesh, SubMesh},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+    pub inverse_direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Ray {
+            origin,
+            direction,
+            inverse_direction: direction.recip(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    // the direction is not renormalized, so distances along the transformed ray
+    // stay the same as along the original one
+    pub fn transform(&self, transform: &Mat4) -> Ray {
+        Ray::new(
+            transform.transform_point3(self.origin),
+            transform.transform_vector3(self.direction),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub object: Handle<Mesh>,
+    pub submesh_index: usize,
+    pub triangle_index: usize,
+    // weights of the three triangle vertices at the hit point
+    pub barycentrics: Vec3,
+    pub distance: f32,
+    pub position: Vec3,
+    // interpolated vertex normal in world space
+    pub normal: Vec3,
+}
+
+// hit against a single submesh in its local space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriangleHit {
+    pub triangle_index: usize,
+    pub barycentrics: Vec3,
+    pub distance: f32,
+    pub normal: Vec3,
+}
+
+// Moller-Trumbore, double sided, returns the distance and the barycentrics of v1 and v2
+pub fn intersect_triangle(ray: &Ray, v0: Vec3, v1: Vec3, v2: Vec3) -> Option<(f32, f32, f32)> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let p = ray.direction.cross(edge2);
+    let determinant = edge1.dot(p);
+
+    if determinant.abs() < f32::EPSILON * edge1.length() * edge2.length() {
+        return None; // ray is parallel to the triangle
+    }
+
+    let inverse_determinant = 1.0 / determinant;
+    let s = ray.origin - v0;
+    let u = s.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+    let v = ray.direction.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = edge2.dot(q) * inverse_determinant;
+    if distance <= 0.0 {
+        return None;
+    }
+
+    Some((distance, u, v))
+}
+
+pub(crate) fn build_submesh_bvh(submesh: &SubMesh) -> Bvh {
+    let triangle_bounds = submesh
+        .indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            Aabb::from_points(triangle.iter().map(|&index| {
+                submesh
+                    .vertices
+                    .get(index as usize)
+                    .map(|vertex| vertex.position)
+                    .unwrap_or(Vec3::ZERO)
+            }))
+        })
+        .collect::<Vec<_>>();
+
+    Bvh::build(&triangle_bounds)
+}
+
+impl SubMesh {
+    // ray in the local space of the submesh
+    pub fn raycast(&self, ray: &Ray, max_distance: f32) -> Option<TriangleHit> {
+        let mut closest = None;
+
+        self.bvh()
+            .traverse(ray, max_distance, |triangle, max_distance| {
+                let triangle = triangle as usize;
+                let indices = &self.indices[triangle * 3..triangle * 3 + 3];
+                let vertices = [
+                    self.vertices.get(indices[0] as usize)?,
+                    self.vertices.get(indices[1] as usize)?,
+                    self.vertices.get(indices[2] as usize)?,
+                ];
+
+                let (distance, u, v) = intersect_triangle(
+                    ray,
+                    vertices[0].position,
+                    vertices[1].position,
+                    vertices[2].position,
+                )?;
+
+                if distance >= max_distance {
+                    return None;
+                }
+
+                let barycentrics = Vec3::new(1.0 - u - v, u, v);
+                let interpolated = vertices[0].normal * barycentrics.x
+                    + vertices[1].normal * barycentrics.y
+                    + vertices[2].normal * barycentrics.z;
+
+                // fall back to the face normal when the vertices have no normals
+                let normal = interpolated.try_normalize().unwrap_or_else(|| {
+                    (vertices[1].position - vertices[0].position)
+                        .cross(vertices[2].position - vertices[0].position)
+                        .normalize_or_zero()
+                });
+
+                closest = Some(TriangleHit {
+                    triangle_index: triangle,
+                    barycentrics,
+                    distance,
+                    normal,
+                });
+
+                Some(distance)
+            });
+
+        closest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::primitives;
+    use crate::graphics::scene::Scene;
+
+    // the closest hit of every triangle of every object in world space, without any
+    // hierarchy
+    fn brute_force(scene: &Scene, origin: Vec3, direction: Vec3) -> Option<f32> {
+        let ray = Ray::new(origin, direction.normalize());
+        scene
+            .objects
+            .values()
+            .flat_map(|object| {
+                object.submeshes.iter().flat_map(|submesh| {
+                    submesh.indices.chunks_exact(3).filter_map(|triangle| {
+                        let [v0, v1, v2] = [0, 1, 2].map(|corner| {
+                            object.world_transform.transform_point3(
+                                submesh.vertices[triangle[corner] as usize].position,
+                            )
+                        });
+                        intersect_triangle(&ray, v0, v1, v2).map(|(distance, _, _)| distance)
+                    })
+                })
+            })
+            .min_by(f32::total_cmp)
+    }
+
+    #[test]
+    fn bvh_raycast_matches_brute_force() {
+        let mut scene = Scene::new();
+        let placed = [
+            (1.0, Vec3::new(-2.0, 0.0, 0.0)),
+            (0.8, Vec3::new(1.5, 0.5, -1.0)),
+            (0.5, Vec3::new(0.0, -1.0, 2.0)),
+            (0.7, Vec3::new(2.0, 1.0, 2.0)),
+        ];
+        for (index, (radius, position)) in placed.into_iter().enumerate() {
+            let mut mesh =
+                primitives::create_sphere(Vec3::ZERO, radius, 12 + index as u32 * 4).mesh;
+            mesh.world_transform =
+                Mat4::from_rotation_y(index as f32) * Mat4::from_translation(position);
+            scene
+                .add_object(format!("Object {}", index), mesh, None)
+                .unwrap();
+        }
+
+        let bvh = scene.build_bvh();
+        let mut hits = 0;
+        for x in -10..=10 {
+            for y in -10..=10 {
+                let origin = Vec3::new(6.0, y as f32 * 0.3, 5.0);
+                let target = Vec3::new(x as f32 * 0.3, y as f32 * 0.2, x as f32 * 0.1);
+                let direction = target - origin;
+
+                let expected = brute_force(&scene, origin, direction);
+                let hit = scene.raycast_with_bvh(&bvh, origin, direction);
+                match (hit, expected) {
+                    (Some(hit), Some(expected)) => {
+                        assert!((hit.distance - expected).abs() < 1e-4);
+                        hits += 1;
+                    }
+                    (None, None) => {}
+                    (hit, expected) => panic!("{:?} != {:?}", hit, expected),
+                }
+            }
+        }
+        // the rays have to reach the objects for the comparison to mean anything
+        assert!(hits > 50);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::reader::error::FileError;
+
+// a rendered frame in linear color, rows from top to bottom
+#[derive(Clone, PartialEq)]
+pub struct RenderedImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl RenderedImage {
+    // transparent black
+    pub fn new(width: u32, height: u32) -> Self {
+        RenderedImage {
+            width,
+            height,
+            pixels: vec![[0.0; 4]; width as usize * height as usize],
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [f32; 4] {
+        self.pixels[y as usize * self.width as usize + x as usize]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [f32; 4]) {
+        self.pixels[y as usize * self.width as usize + x as usize] = color;
+    }
+
+    // srgb encoded like the swapchain does it, alpha stays linear
+    pub fn to_rgba8(&self) -> image::RgbaImage {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 4);
+        for [r, g, b, a] in &self.pixels {
+            for channel in [
+                linear_to_srgb(*r),
+                linear_to_srgb(*g),
+                linear_to_srgb(*b),
+                *a,
+            ] {
+                bytes.push((channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+        image::RgbaImage::from_raw(self.width, self.height, bytes)
+            .expect("pixel count matches the size")
+    }
+
+    pub fn to_rgba32f(&self) -> image::Rgba32FImage {
+        image::Rgba32FImage::from_raw(self.width, self.height, self.pixels.concat())
+            .expect("pixel count matches the size")
+    }
+
+    // the format follows the extension, exr keeps the linear values, anything else is
+    // written with 8 bits per channel
+    pub fn save(&self, path: &str) -> Result<(), FileError> {
+        let is_exr = std::path::Path::new(path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("exr"));
+
+        let result = if is_exr {
+            self.to_rgba32f().save(path)
+        } else {
+            self.to_rgba8().save(path)
+        };
+        result.map_err(|e| FileError::InvalidFormat(format!("{}: {}", path, e)))
+    }
+
+    // reads an image written by `save`, 8 bit images are decoded from srgb
+    pub fn load(path: &str) -> Result<Self, FileError> {
+        let image =
+            image::open(path).map_err(|e| FileError::InvalidFormat(format!("{}: {}", path, e)))?;
+        let is_linear = matches!(
+            image.color(),
+            image::ColorType::Rgb32F | image::ColorType::Rgba32F
+        );
+
+        let image = image.to_rgba32f();
+        let pixels = image
+            .pixels()
+            .map(|&image::Rgba([r, g, b, a])| {
+                if is_linear {
+                    [r, g, b, a]
+                } else {
+                    [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+                }
+            })
+            .collect();
+        Ok(RenderedImage {
+            width: image.width(),
+            height: image.height(),
+            pixels,
+        })
+    }
+
+    // compares the srgb encoded channels like they are seen, `None` when the sizes differ
+    pub fn difference(&self, other: &RenderedImage, tolerance: f32) -> Option<ImageDifference> {
+        if self.width != other.width || self.height != other.height {
+            return None;
+        }
+
+        let mut difference = ImageDifference::default();
+        for (a, b) in self.pixels.iter().zip(&other.pixels) {
+            let pixel = (0..4)
+                .map(|channel| {
+                    let (a, b) = if channel < 3 {
+                        (linear_to_srgb(a[channel]), linear_to_srgb(b[channel]))
+                    } else {
+                        (a[channel], b[channel])
+                    };
+                    (a.clamp(0.0, 1.0) - b.clamp(0.0, 1.0)).abs()
+                })
+                .fold(0.0, f32::max);
+            difference.max = difference.max.max(pixel);
+            difference.mean += pixel;
+            if pixel > tolerance {
+                difference.outliers += 1.0;
+            }
+        }
+        let count = self.pixels.len().max(1) as f32;
+        difference.mean /= count;
+        difference.outliers /= count;
+        Some(difference)
+    }
+}
+
+// how far two images are apart, in the largest channel difference of every pixel
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ImageDifference {
+    pub max: f32,
+    pub mean: f32,
+    // the share of the pixels further apart than the tolerance
+    pub outliers: f32,
+}
+
+impl std::fmt::Display for ImageDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "max {:.4}, mean {:.4}, {:.2}% outliers",
+            self.max,
+            self.mean,
+            self.outliers * 100.0
+        )
+    }
+}
+
+// the pixels would flood every debug print
+impl std::fmt::Debug for RenderedImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RenderedImage")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient() -> RenderedImage {
+        let mut image = RenderedImage::new(8, 4);
+        for y in 0..4 {
+            for x in 0..8 {
+                image.set_pixel(x, y, [x as f32 / 7.0, y as f32 / 3.0, 0.25, 1.0]);
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn saved_images_load_back() {
+        let image = gradient();
+        let directory = std::env::temp_dir();
+        let id = std::process::id();
+
+        let png = directory.join(format!("atom_rendered_image_{}.png", id));
+        image.save(png.to_str().unwrap()).unwrap();
+        let loaded = RenderedImage::load(png.to_str().unwrap()).unwrap();
+        // one step of the 8 bit encoding
+        let difference = loaded.difference(&image, 0.5 / 255.0 + 1e-6).unwrap();
+        assert_eq!(difference.outliers, 0.0, "{}", difference);
+
+        let exr = directory.join(format!("atom_rendered_image_{}.exr", id));
+        image.save(exr.to_str().unwrap()).unwrap();
+        let loaded = RenderedImage::load(exr.to_str().unwrap()).unwrap();
+        assert_eq!(loaded, image);
+
+        let _ = std::fs::remove_file(png);
+        let _ = std::fs::remove_file(exr);
+    }
+
+    #[test]
+    fn difference_counts_the_pixels_past_the_tolerance() {
+        let image = gradient();
+        assert_eq!(
+            image.difference(&image, 0.0),
+            Some(ImageDifference::default())
+        );
+        assert_eq!(image.difference(&RenderedImage::new(4, 8), 0.0), None);
+
+        let mut changed = image.clone();
+        changed.set_pixel(0, 0, [1.0, 0.0, 0.25, 1.0]);
+        changed.set_pixel(1, 0, [0.15, 0.0, 0.25, 1.0]);
+        let difference = changed.difference(&image, 0.1).unwrap();
+        assert!((difference.max - 1.0).abs() < 1e-5);
+        assert_eq!(difference.outliers, 1.0 / 32.0);
+        assert!(difference.mean > 1.0 / 32.0);
+    }
+}
@@ -1,41 +1,640 @@
-use glam::Vec3;
+use std::collections::HashMap;
+
+use glam::{Mat4, Vec3};
+
+use crate::graphics::{
+    arena::{Arena, Handle},
+    assets::{Asset, next_revision},
+    bounds::Aabb,
+    bvh::Bvh,
+    camera::Camera,
+    error::GraphicsError,
+    light::Light,
+    material::Material,
+    mesh::Mesh,
+    primitives,
+    raycast::{Hit, Ray},
+    shadow::ShadowSettings,
+};
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub name: String,
+    // relative to the parent, or to the world for root nodes
+    pub local_transform: Mat4,
+    // refreshed from the local transforms by `Scene::update_world_transforms`
+    world_transform: Mat4,
+    parent: Option<Handle<Node>>,
+    children: Vec<Handle<Node>>,
+    // the mesh placed by this node
+    pub object: Option<Handle<Mesh>>,
+}
+
+impl Node {
+    pub fn world_transform(&self) -> Mat4 {
+        self.world_transform
+    }
+
+    pub fn parent(&self) -> Option<Handle<Node>> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[Handle<Node>] {
+        &self.children
+    }
+}
+
+// what a renderer has to upload again, see `Scene::mark_changed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneChange {
+    // objects were added or removed, or their submeshes were replaced
+    Objects,
+    Transforms,
+    Materials,
+    Lights,
+}
+
+// a new number is drawn for every change, so a renderer that remembers the revision
+// it uploaded can tell what changed since, geometry has its own `Asset::revision`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SceneRevision {
+    pub objects: u64,
+    pub transforms: u64,
+    pub materials: u64,
+    pub lights: u64,
+}
+
+impl SceneRevision {
+    fn new() -> Self {
+        let revision = next_revision();
+        SceneRevision {
+            objects: revision,
+            transforms: revision,
+            materials: revision,
+            lights: revision,
+        }
+    }
+}
 
-use crate::graphics::{self, primitives};
 #[derive(Debug, Clone)]
 pub struct Scene {
-    pub objects: Vec<graphics::mesh::Mesh>,
-    pub cameras: Vec<graphics::camera::Camera>,
-    pub lights: Vec<graphics::light::Light>,
-    pub main_camera_index: usize,
+    pub objects: Arena<Mesh>,
+    pub cameras: Arena<Camera>,
+    pub lights: Arena<Light>,
+    // shared by the submeshes, which fall back to the default material without one
+    pub materials: Arena<Asset<Material>>,
+    pub main_camera: Option<Handle<Camera>>,
+    // how the lights casting shadows render them
+    pub shadows: ShadowSettings,
+    // the hierarchy placing the objects, an object's `world_transform` is overwritten
+    // from its node, objects without a node keep their own
+    nodes: Arena<Node>,
+    revision: SceneRevision,
 }
 
 impl Scene {
     pub fn new() -> Self {
         Scene {
-            objects: Vec::new(),
-            cameras: Vec::new(),
-            lights: Vec::new(),
-            main_camera_index: 0,
+            objects: Arena::new(),
+            cameras: Arena::new(),
+            lights: Arena::new(),
+            materials: Arena::new(),
+            main_camera: None,
+            shadows: ShadowSettings::default(),
+            nodes: Arena::new(),
+            revision: SceneRevision::new(),
         }
     }
 
+    pub fn revision(&self) -> SceneRevision {
+        self.revision
+    }
+
+    // the methods of the scene mark their own changes, this is for edits made directly
+    // to the objects, lights and materials
+    pub fn mark_changed(&mut self, change: SceneChange) {
+        let revision = next_revision();
+        match change {
+            SceneChange::Objects => self.revision.objects = revision,
+            SceneChange::Transforms => self.revision.transforms = revision,
+            SceneChange::Materials => self.revision.materials = revision,
+            SceneChange::Lights => self.revision.lights = revision,
+        }
+    }
+
+    // the other scene's roots become roots of this one, its main camera only becomes
+    // the main camera here if there was none
     pub fn merge(&mut self, other: Scene) {
-        self.objects.extend(other.objects);
-        self.cameras.extend(other.cameras);
-        self.lights.extend(other.lights);
+        // materials this scene already shares with the other one are not added twice
+        let materials = other
+            .materials
+            .iter()
+            .map(|(handle, material)| {
+                let existing = self
+                    .materials
+                    .iter()
+                    .find(|(_, existing)| existing.ptr_eq(material))
+                    .map(|(existing, _)| existing);
+                (
+                    handle,
+                    existing.unwrap_or_else(|| self.materials.insert(material.clone())),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        let objects = other
+            .objects
+            .iter()
+            .map(|(handle, object)| {
+                let mut object = object.clone();
+                for submesh in &mut object.submeshes {
+                    submesh.material = submesh
+                        .material
+                        .and_then(|material| materials.get(&material).copied());
+                }
+                (handle, self.objects.insert(object))
+            })
+            .collect::<HashMap<_, _>>();
+
+        // inserted first and linked up afterwards, children may come before their parents
+        let nodes = other
+            .nodes
+            .iter()
+            .map(|(handle, node)| (handle, self.nodes.insert(node.clone())))
+            .collect::<HashMap<_, _>>();
+        for &node in nodes.values() {
+            let node = self
+                .nodes
+                .get_mut(node)
+                .expect("the node was just inserted");
+            node.parent = node.parent.and_then(|parent| nodes.get(&parent).copied());
+            node.children = node
+                .children
+                .iter()
+                .filter_map(|child| nodes.get(child).copied())
+                .collect();
+            node.object = node.object.and_then(|object| objects.get(&object).copied());
+        }
+
+        for camera in other.cameras.values() {
+            let camera = self.cameras.insert(camera.clone());
+            self.main_camera.get_or_insert(camera);
+        }
+        for light in other.lights.values() {
+            self.lights.insert(light.clone());
+        }
+
+        self.revision = SceneRevision::new();
+    }
+
+    pub fn main_camera(&self) -> Option<&Camera> {
+        self.cameras.get(self.main_camera?)
+    }
+
+    pub fn main_camera_mut(&mut self) -> Option<&mut Camera> {
+        self.cameras.get_mut(self.main_camera?)
+    }
+
+    // the main camera moves on to another camera if there is one
+    pub fn remove_camera(&mut self, camera: Handle<Camera>) -> Option<Camera> {
+        let removed = self.cameras.remove(camera)?;
+        if self.main_camera == Some(camera) {
+            self.main_camera = self.cameras.handles().next();
+        }
+        Some(removed)
+    }
+
+    // also clears it from the node placing it, the node itself stays
+    pub fn remove_object(&mut self, object: Handle<Mesh>) -> Option<Mesh> {
+        let removed = self.objects.remove(object)?;
+        self.mark_changed(SceneChange::Objects);
+        for node in self.nodes.values_mut() {
+            if node.object == Some(object) {
+                node.object = None;
+            }
+        }
+        Some(removed)
+    }
+
+    pub fn nodes(&self) -> &Arena<Node> {
+        &self.nodes
+    }
+
+    pub fn node(&self, node: Handle<Node>) -> Option<&Node> {
+        self.nodes.get(node)
+    }
+
+    pub fn node_mut(&mut self, node: Handle<Node>) -> Option<&mut Node> {
+        self.nodes.get_mut(node)
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = Handle<Node>> + '_ {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(handle, _)| handle)
+    }
+
+    // the node placing the object, if any
+    pub fn object_node(&self, object: Handle<Mesh>) -> Option<Handle<Node>> {
+        self.nodes
+            .iter()
+            .find(|(_, node)| node.object == Some(object))
+            .map(|(handle, _)| handle)
+    }
+
+    pub fn add_node(
+        &mut self,
+        name: impl Into<String>,
+        local_transform: Mat4,
+        parent: Option<Handle<Node>>,
+    ) -> Result<Handle<Node>, GraphicsError> {
+        if let Some(parent) = parent
+            && !self.nodes.contains(parent)
+        {
+            return Err(GraphicsError::InvalidNodeHierarchy(format!(
+                "parent node {} does not exist",
+                parent
+            )));
+        }
+
+        let node = self.nodes.insert(Node {
+            name: name.into(),
+            local_transform,
+            world_transform: local_transform,
+            parent,
+            children: Vec::new(),
+            object: None,
+        });
+
+        if let Some(parent) = parent.and_then(|parent| self.nodes.get_mut(parent)) {
+            parent.children.push(node);
+        }
+        self.update_world_transforms();
+
+        Ok(node)
+    }
+
+    // adds the mesh under a new node, its current world transform becomes the local one
+    pub fn add_object(
+        &mut self,
+        name: impl Into<String>,
+        object: Mesh,
+        parent: Option<Handle<Node>>,
+    ) -> Result<Handle<Node>, GraphicsError> {
+        let node = self.add_node(name, object.world_transform, parent)?;
+        let object = self.objects.insert(object);
+        if let Some(node) = self.nodes.get_mut(node) {
+            node.object = Some(object);
+        }
+        self.mark_changed(SceneChange::Objects);
+        self.update_world_transforms();
+
+        Ok(node)
+    }
+
+    // removes the node with its whole subtree and the objects placed by it
+    pub fn remove_node(&mut self, node: Handle<Node>) -> Result<(), GraphicsError> {
+        let parent = self
+            .nodes
+            .get(node)
+            .ok_or_else(|| {
+                GraphicsError::InvalidNodeHierarchy(format!("node {} does not exist", node))
+            })?
+            .parent;
+
+        if let Some(parent) = parent.and_then(|parent| self.nodes.get_mut(parent)) {
+            parent.children.retain(|&child| child != node);
+        }
+
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            if let Some(removed) = self.nodes.remove(node) {
+                if let Some(object) = removed.object {
+                    self.objects.remove(object);
+                }
+                stack.extend(removed.children);
+            }
+        }
+        self.mark_changed(SceneChange::Objects);
+
+        Ok(())
+    }
+
+    // moves the node below another one or to the root, the world transform is kept
+    // by adjusting the local one, a node can not be moved below its own subtree
+    pub fn set_parent(
+        &mut self,
+        node: Handle<Node>,
+        parent: Option<Handle<Node>>,
+    ) -> Result<(), GraphicsError> {
+        if !self.nodes.contains(node) || parent.is_some_and(|parent| !self.nodes.contains(parent)) {
+            return Err(GraphicsError::InvalidNodeHierarchy(format!(
+                "node {} or its new parent {:?} does not exist",
+                node, parent
+            )));
+        }
+
+        if self.is_in_subtree(parent, node) {
+            return Err(GraphicsError::InvalidNodeHierarchy(format!(
+                "node {} can not be moved below itself",
+                node
+            )));
+        }
+
+        self.update_world_transforms();
+
+        if let Some(old_parent) = self.nodes[node].parent {
+            self.nodes[old_parent]
+                .children
+                .retain(|&child| child != node);
+        }
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(node);
+        }
+
+        let parent_world =
+            parent.map_or(Mat4::IDENTITY, |parent| self.nodes[parent].world_transform);
+        let node = &mut self.nodes[node];
+        node.parent = parent;
+        node.local_transform = parent_world.inverse() * node.world_transform;
+
+        Ok(())
+    }
+
+    // whether the node is the root of the subtree or lies anywhere below it
+    pub fn is_in_subtree(&self, node: Option<Handle<Node>>, root: Handle<Node>) -> bool {
+        let mut current = node;
+        while let Some(node) = current {
+            if node == root {
+                return true;
+            }
+            current = self.nodes.get(node).and_then(|node| node.parent);
+        }
+        false
+    }
+
+    // changes the local transform so the node ends up at the given world transform
+    pub fn set_world_transform(&mut self, node: Handle<Node>, world_transform: Mat4) {
+        let Some(parent) = self.nodes.get(node).map(|node| node.parent) else {
+            return;
+        };
+
+        let parent_world = parent
+            .and_then(|parent| self.nodes.get(parent))
+            .map_or(Mat4::IDENTITY, |parent| parent.world_transform);
+        self.nodes[node].local_transform = parent_world.inverse() * world_transform;
+        self.update_world_transforms();
+    }
+
+    // walks the hierarchy from the roots and hands the world transforms down to the objects,
+    // must be called after local transforms were edited, only marks a change if an object moved
+    pub fn update_world_transforms(&mut self) {
+        let mut moved = false;
+        let mut stack = self
+            .roots()
+            .map(|root| (root, Mat4::IDENTITY))
+            .collect::<Vec<_>>();
+
+        while let Some((node, parent_world)) = stack.pop() {
+            let Some(node) = self.nodes.get_mut(node) else {
+                continue;
+            };
+            let world_transform = parent_world * node.local_transform;
+            node.world_transform = world_transform;
+            stack.extend(node.children.iter().map(|&child| (child, world_transform)));
+
+            if let Some(object) = node.object.and_then(|object| self.objects.get_mut(object))
+                && object.world_transform != world_transform
+            {
+                object.world_transform = world_transform;
+                moved = true;
+            }
+        }
+
+        if moved {
+            self.mark_changed(SceneChange::Transforms);
+        }
+    }
+
+    // the world space box around all objects, empty without any
+    pub fn bounds(&self) -> Aabb {
+        self.objects.values().fold(Aabb::EMPTY, |bounds, object| {
+            bounds.union(&object.world_bounds().aabb)
+        })
+    }
+
+    // top level hierarchy over the world space bounds of all objects
+    pub fn build_bvh(&self) -> Bvh {
+        // indexed by object slot, free slots get an empty box that no ray reaches
+        let object_bounds = (0..self.objects.slot_count())
+            .map(|index| {
+                self.objects
+                    .handle_at(index)
+                    .and_then(|handle| self.objects.get(handle))
+                    .map_or(Aabb::EMPTY, |object| object.world_bounds().aabb)
+            })
+            .collect::<Vec<_>>();
+
+        Bvh::build(&object_bounds)
+    }
+
+    // closest hit of the ray against all objects in the scene
+    pub fn raycast(&self, origin: Vec3, direction: Vec3) -> Option<Hit> {
+        self.raycast_with_bvh(&self.build_bvh(), origin, direction)
+    }
+
+    // same as `raycast` but reuses a top level hierarchy from `build_bvh`,
+    // which stays valid until an object is added, removed or moved
+    pub fn raycast_with_bvh(&self, bvh: &Bvh, origin: Vec3, direction: Vec3) -> Option<Hit> {
+        let direction = direction.try_normalize()?;
+        let ray = Ray::new(origin, direction);
+
+        let mut closest = None;
+
+        bvh.traverse(&ray, f32::MAX, |object_index, max_distance| {
+            let object_handle = self.objects.handle_at(object_index as usize)?;
+            let object = self.objects.get(object_handle)?;
+            let local_ray = ray.transform(&object.world_transform.inverse());
+            let normal_matrix = glam::Mat3::from_mat4(object.world_transform)
+                .inverse()
+                .transpose();
+
+            let mut object_hit = None;
+            let mut max_distance = max_distance;
+
+            for (submesh_index, submesh) in object.submeshes.iter().enumerate() {
+                if let Some(hit) = submesh.raycast(&local_ray, max_distance) {
+                    max_distance = hit.distance;
+                    object_hit = Some(Hit {
+                        object: object_handle,
+                        submesh_index,
+                        triangle_index: hit.triangle_index,
+                        barycentrics: hit.barycentrics,
+                        distance: hit.distance,
+                        position: ray.at(hit.distance),
+                        normal: (normal_matrix * hit.normal).normalize_or_zero(),
+                    });
+                }
+            }
+
+            closest = object_hit.or(closest);
+            object_hit.map(|hit| hit.distance)
+        });
+
+        closest
     }
 }
 
 impl Default for Scene {
     fn default() -> Self {
-        let cameras = vec![graphics::camera::Camera::default()];
-        let lights = vec![graphics::light::Light::default()];
+        let mut scene = Scene::new();
+        scene.main_camera = Some(scene.cameras.insert(Camera::default()));
+        scene.lights.insert(Light::default());
+
         let default_object = primitives::create_sphere(Vec3::ZERO, 1.0, 32).mesh;
-        Self {
-            objects: vec![default_object],
-            cameras,
-            lights,
-            main_camera_index: 0,
-        }
+        scene
+            .add_object("Sphere", default_object, None)
+            .expect("root nodes can always be added");
+        scene
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translation(x: f32) -> Mat4 {
+        Mat4::from_translation(Vec3::new(x, 0.0, 0.0))
+    }
+
+    #[test]
+    fn objects_follow_their_nodes() {
+        let mut scene = Scene::new();
+        let parent = scene.add_node("parent", translation(1.0), None).unwrap();
+        let child = scene
+            .add_object(
+                "child",
+                Mesh::new(Vec::new(), translation(2.0)),
+                Some(parent),
+            )
+            .unwrap();
+        let object = scene.nodes[child].object.unwrap();
+
+        assert_eq!(scene.objects[object].world_transform, translation(3.0));
+
+        scene.nodes[parent].local_transform = translation(-1.0);
+        scene.update_world_transforms();
+        assert_eq!(scene.objects[object].world_transform, translation(1.0));
+    }
+
+    #[test]
+    fn set_parent_keeps_the_world_transform_and_rejects_cycles() {
+        let mut scene = Scene::new();
+        let a = scene.add_node("a", translation(1.0), None).unwrap();
+        let b = scene.add_node("b", translation(2.0), Some(a)).unwrap();
+        let c = scene.add_node("c", translation(4.0), None).unwrap();
+
+        scene.set_parent(b, Some(c)).unwrap();
+        assert_eq!(scene.nodes[b].parent(), Some(c));
+        assert!(scene.nodes[a].children().is_empty());
+        assert_eq!(scene.nodes[c].children(), &[b]);
+        assert_eq!(scene.nodes[b].world_transform(), translation(3.0));
+        assert_eq!(scene.nodes[b].local_transform, translation(-1.0));
+
+        // below itself or below its own child
+        assert!(scene.set_parent(c, Some(c)).is_err());
+        assert!(scene.set_parent(c, Some(b)).is_err());
+        assert_eq!(scene.nodes[c].parent(), None);
+        assert_eq!(scene.nodes[b].parent(), Some(c));
+
+        scene.set_parent(b, None).unwrap();
+        assert_eq!(scene.roots().count(), 3);
+        assert_eq!(scene.nodes[b].local_transform, translation(3.0));
+    }
+
+    #[test]
+    fn remove_node_takes_the_subtree_and_its_objects() {
+        let mut scene = Scene::new();
+        let root = scene.add_node("root", Mat4::IDENTITY, None).unwrap();
+        let child = scene
+            .add_object("child", Mesh::new(Vec::new(), Mat4::IDENTITY), Some(root))
+            .unwrap();
+        let grandchild = scene
+            .add_object(
+                "grandchild",
+                Mesh::new(Vec::new(), Mat4::IDENTITY),
+                Some(child),
+            )
+            .unwrap();
+        let other = scene
+            .add_object("other", Mesh::new(Vec::new(), Mat4::IDENTITY), None)
+            .unwrap();
+
+        scene.remove_node(child).unwrap();
+
+        assert!(!scene.nodes.contains(child) && !scene.nodes.contains(grandchild));
+        assert!(scene.nodes[root].children().is_empty());
+        assert_eq!(scene.objects.len(), 1);
+        assert_eq!(
+            scene.object_node(scene.objects.handles().next().unwrap()),
+            Some(other)
+        );
+    }
+
+    #[test]
+    fn merge_remaps_the_handles_of_the_other_scene() {
+        let mut scene = Scene::new();
+        let shared = Asset::new(Material::default());
+        let kept = scene.materials.insert(shared.clone());
+        scene
+            .add_object("kept", Mesh::new(Vec::new(), Mat4::IDENTITY), None)
+            .unwrap();
+
+        let mut other = Scene::new();
+        // a removed node leaves a newer generation in the first slot
+        let removed = other.add_node("removed", Mat4::IDENTITY, None).unwrap();
+        other.remove_node(removed).unwrap();
+        let own_material = other.materials.insert(Asset::new(Material::default()));
+        let other_shared = other.materials.insert(shared.clone());
+        let parent = other.add_node("parent", translation(1.0), None).unwrap();
+        let mesh = Mesh::new(
+            vec![
+                crate::graphics::mesh::SubMesh::new(Vec::new(), Vec::new(), Some(own_material)),
+                crate::graphics::mesh::SubMesh::new(Vec::new(), Vec::new(), Some(other_shared)),
+            ],
+            translation(2.0),
+        );
+        let child = other.add_object("child", mesh, Some(parent)).unwrap();
+        // turned around, so the child now comes before its parent in slot order
+        other.set_parent(child, None).unwrap();
+        other.set_parent(parent, Some(child)).unwrap();
+
+        scene.merge(other);
+
+        assert_eq!(scene.materials.len(), 2);
+        let (_, child) = scene
+            .nodes
+            .iter()
+            .find(|(_, node)| node.name == "child")
+            .unwrap();
+        let (parent, _) = scene
+            .nodes
+            .iter()
+            .find(|(_, node)| node.name == "parent")
+            .unwrap();
+        assert_eq!(child.children(), &[parent]);
+        let child_handle = scene.nodes[parent].parent().unwrap();
+        assert_eq!(scene.nodes[child_handle].name, "child");
+
+        let object = &scene.objects[child.object.unwrap()];
+        let materials = object
+            .submeshes
+            .iter()
+            .map(|submesh| submesh.material.unwrap())
+            .collect::<Vec<_>>();
+        assert_ne!(materials[0], kept);
+        assert!(scene.materials.contains(materials[0]));
+        assert_eq!(materials[1], kept);
     }
 }
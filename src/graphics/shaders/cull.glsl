@@ -0,0 +1,106 @@
+#version 460
+layout(local_size_x=64)in;
+
+// one submesh instance, drawn when its world space bounds touch the frustum
+struct Candidate{
+    vec4 sphere;
+    vec4 aabb_min;
+    vec4 aabb_max;
+    uint group;// the draw of its geometry and level of detail
+    uint slot;
+};
+
+// laid out like VkDrawIndexedIndirectCommand
+struct DrawCommand{
+    uint index_count;
+    uint instance_count;
+    uint first_index;
+    int vertex_offset;
+    uint first_instance;
+};
+
+layout(set=0,binding=0)readonly buffer Candidates{
+    Candidate candidate[];
+}candidates;
+
+// one instanced draw per group, written without instances before the dispatch
+layout(set=0,binding=1)buffer Groups{
+    DrawCommand command[];
+}groups;
+
+// the instance slots of every group from its first instance on, read by the vertex shader
+layout(set=0,binding=2)writeonly buffer InstanceSlots{
+    uint slot[];
+}instances;
+
+// the surviving candidates, read back for the stats, and the packed draws, which the
+// indirect draw reads its count from
+layout(set=0,binding=3)buffer Counts{
+    uint visible;
+    uint draws;
+}counts;
+
+// the groups that kept any instance, packed at the front
+layout(set=0,binding=4)writeonly buffer Draws{
+    DrawCommand command[];
+}draws;
+
+// frustum planes with inward normals, the candidates are culled first, then a second
+// dispatch packs the groups once their instances are counted
+layout(push_constant)uniform Cull{
+    vec4 planes[6];
+    uint candidate_count;
+    uint group_count;
+    uint pack;
+}cull;
+
+bool visible(Candidate candidate){
+    for(int i=0;i<6;i++){
+        vec4 plane=cull.planes[i];
+        if(dot(plane.xyz,candidate.sphere.xyz)+plane.w<-candidate.sphere.w){
+            return false;
+        }
+        // the corner furthest along the plane normal
+        vec3 corner=mix(candidate.aabb_min.xyz,candidate.aabb_max.xyz,greaterThanEqual(plane.xyz,vec3(0.)));
+        if(dot(plane.xyz,corner)+plane.w<0.){
+            return false;
+        }
+    }
+    return true;
+}
+
+void cull_candidate(uint index){
+    if(index>=cull.candidate_count){
+        return;
+    }
+
+    Candidate candidate=candidates.candidate[index];
+    if(!visible(candidate)){
+        return;
+    }
+
+    // the surviving instances of a group are packed at the front of its slots
+    uint instance=atomicAdd(groups.command[candidate.group].instance_count,1);
+    instances.slot[groups.command[candidate.group].first_instance+instance]=candidate.slot;
+    atomicAdd(counts.visible,1);
+}
+
+void pack_group(uint index){
+    if(index>=cull.group_count){
+        return;
+    }
+
+    DrawCommand command=groups.command[index];
+    if(command.instance_count==0){
+        return;
+    }
+    draws.command[atomicAdd(counts.draws,1)]=command;
+}
+
+void main(){
+    if(cull.pack==0){
+        cull_candidate(gl_GlobalInvocationID.x);
+    }else{
+        pack_group(gl_GlobalInvocationID.x);
+    }
+}
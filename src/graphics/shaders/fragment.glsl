@@ -16,10 +16,14 @@ struct Material{
 
 struct Light{
     vec3 position;
+    float range;// spot: no light past it, 0 for no limit
     vec3 direction;
+    float inner_cone_cos;
     vec3 color;
     float intensity;
-    uint light_type;// 0: point, 1: directional
+    uint light_type;// 0: point, 1: directional, 2: spot
+    float outer_cone_cos;
+    int shadow_index;// first cascade or cube face in the shadow maps, -1 for none
 };
 
 layout(set=0,binding=0)uniform CameraUbo{
@@ -35,6 +39,29 @@ layout(set=0,binding=4)buffer LightBuffer{
     Light lights[];
 }light_buffer;
 
+// the cascades of all shadowed directional lights one light after the other, as many
+// as `shadow::MAX_CASCADES`
+layout(set=0,binding=6)uniform ShadowUbo{
+    mat4 cascade_matrices[8];// view space to the clip space of the shadow map
+    vec4 cascade_splits[8];// x: view depth where the cascade ends, y: texel size
+    uint cascade_count;// per light
+    int pcf_radius;
+    float depth_bias;
+    float normal_bias;// in texels
+}shadows;
+
+// one layer per cascade
+layout(set=0,binding=7)uniform sampler2DArrayShadow shadow_maps;
+
+// the cubes around the shadowed point lights, as many as `shadow::MAX_POINT_SHADOWS`
+layout(set=0,binding=8)uniform PointShadowUbo{
+    mat4 face_matrices[24];// view space to the clip space of a face, +x, -x, +y, -y, +z, -z
+    float texel_scale;// the world size of a texel at a distance of 1 from the light
+}point_shadows;
+
+// six layers per light, one per face
+layout(set=0,binding=9)uniform sampler2DArrayShadow point_shadow_maps;
+
 vec3 ambient_color(vec3 ambient_light,vec3 ambient_material){
     return ambient_light*ambient_material;
 }
@@ -51,6 +78,73 @@ vec3 specular_color(vec3 light_color,vec3 specular_material,vec3 view_dir,vec3 l
     return light_color*specular_material*pow(spec,specular_exponent);
 }
 
+// percentage closer filtering over the texels around the clip space position in the
+// layer, the sampler compares and blends the four nearest of each
+float filtered_shadow(sampler2DArrayShadow maps,vec4 clip,int layer){
+    vec3 coordinates=clip.xyz/clip.w;
+    vec2 uv=coordinates.xy*.5+.5;
+    // nothing past the far plane of the map casts a shadow there
+    float reference=min(coordinates.z-shadows.depth_bias,1.);
+    
+    vec2 texel=1./vec2(textureSize(maps,0).xy);
+    float lit=0.;
+    for(int x=-shadows.pcf_radius;x<=shadows.pcf_radius;x++){
+        for(int y=-shadows.pcf_radius;y<=shadows.pcf_radius;y++){
+            lit+=texture(maps,vec4(uv+vec2(x,y)*texel,float(layer),reference));
+        }
+    }
+    float samples=float((2*shadows.pcf_radius+1)*(2*shadows.pcf_radius+1));
+    return lit/samples;
+}
+
+// how much of the directional light reaches the fragment, from 0 in full shadow to 1
+float directional_shadow(Light light){
+    if(light.shadow_index<0){
+        return 1.;
+    }
+    
+    // the first cascade reaching past the fragment, nothing is shadowed beyond the last
+    float depth=-v_position.z;
+    int cascade=0;
+    while(cascade<int(shadows.cascade_count)&&depth>shadows.cascade_splits[light.shadow_index+cascade].x){
+        cascade++;
+    }
+    if(cascade==int(shadows.cascade_count)){
+        return 1.;
+    }
+    int layer=light.shadow_index+cascade;
+    
+    // moved off the surface so it does not shadow itself
+    vec3 position=v_position+normalize(v_normal)*shadows.normal_bias*shadows.cascade_splits[layer].y;
+    return filtered_shadow(shadow_maps,shadows.cascade_matrices[layer]*vec4(position,1.),layer);
+}
+
+// how much of the point light reaches the fragment, from the face of the cube around the
+// light that the fragment is seen through
+float point_shadow(Light light,vec3 light_pos_view){
+    if(light.shadow_index<0){
+        return 1.;
+    }
+    
+    // the faces are aligned with the world axes
+    vec3 from_light=transpose(mat3(camera.view))*(v_position-light_pos_view);
+    vec3 axis=abs(from_light);
+    int face;
+    if(axis.x>=axis.y&&axis.x>=axis.z){
+        face=from_light.x>0.?0:1;
+    }else if(axis.y>=axis.z){
+        face=from_light.y>0.?2:3;
+    }else{
+        face=from_light.z>0.?4:5;
+    }
+    int layer=light.shadow_index+face;
+    
+    // the texels grow with the distance from the light
+    float texel_size=point_shadows.texel_scale*length(from_light);
+    vec3 position=v_position+normalize(v_normal)*shadows.normal_bias*texel_size;
+    return filtered_shadow(point_shadow_maps,point_shadows.face_matrices[layer]*vec4(position,1.),layer);
+}
+
 void main(){
     Material mat=material_buffer.materials[v_instance_index];
     
@@ -68,9 +162,28 @@ void main(){
             vec4 light_pos_view=camera.view*vec4(light.position,1.);
             light_dir=normalize(light_pos_view.xyz-v_position);
             float attenuation=1./(1.+.09*length(light_pos_view.xyz-v_position)+.032*length(light_pos_view.xyz-v_position)*length(light_pos_view.xyz-v_position));
-            intensity*=attenuation;
+            intensity*=attenuation*point_shadow(light,light_pos_view.xyz);
+        }else if(light.light_type==2){// spot light
+            vec4 light_pos_view=camera.view*vec4(light.position,1.);
+            float distance=length(light_pos_view.xyz-v_position);
+            light_dir=normalize(light_pos_view.xyz-v_position);
+            float attenuation=1./(1.+.09*distance+.032*distance*distance);
+            
+            // full strength inside the inner cone, fading out smoothly until the outer one
+            vec3 spot_dir=normalize(mat3(camera.view)*light.direction);
+            float cone=smoothstep(light.outer_cone_cos,light.inner_cone_cos,dot(-light_dir,spot_dir));
+            
+            // reaches zero at the range without a hard edge
+            float range_falloff=1.;
+            if(light.range>0.){
+                float window=clamp(1.-pow(distance/light.range,4.),0.,1.);
+                range_falloff=window*window;
+            }
+            intensity*=attenuation*cone*range_falloff;
         }else{// directional light
-            light_dir=-normalize(light.direction);
+            // in world space like its shadow maps, the lighting is done in view space
+            light_dir=-normalize(mat3(camera.view)*light.direction);
+            intensity*=directional_shadow(light);
         }
         
         vec3 diffuse=diffuse_color(light.color,mat.diffuse_color,v_normal,light_dir);
@@ -0,0 +1,22 @@
+#version 460
+#extension GL_ARB_shader_draw_parameters:enable
+layout(location=0)in vec3 position;
+
+// world space to the clip space of the shadow map drawn
+layout(push_constant)uniform ShadowPass{
+    mat4 view_projection;
+}shadow_pass;
+
+layout(set=0,binding=0)buffer ModelMatrices{
+    mat4 model[];
+}models;
+
+// the persistent instance slot of every drawn caster
+layout(set=0,binding=1)buffer InstanceSlots{
+    uint slot[];
+}instances;
+
+void main(){
+    mat4 model_matrix=models.model[instances.slot[gl_InstanceIndex]];
+    gl_Position=shadow_pass.view_projection*model_matrix*vec4(position,1.);
+}
@@ -22,15 +22,23 @@ layout(set=0,binding=3)buffer NormalMatrices{
     mat4 normal[];
 }normals;
 
+// the persistent instance slot of every drawn instance
+layout(set=0,binding=5)buffer InstanceSlots{
+    uint slot[];
+}instances;
+
 void main(){
-    mat4 model_matrix=models.model[gl_InstanceIndex];
-    mat4 normal_matrix=normals.normal[gl_InstanceIndex];
+    uint instance=instances.slot[gl_InstanceIndex];
+    mat4 model_matrix=models.model[instance];
+    mat4 normal_matrix=normals.normal[instance];
     
     vec4 temp_position=camera.view*model_matrix*vec4(position,1.);
     
     gl_Position=camera.proj*temp_position;
     v_position=temp_position.xyz;
-    v_normal=normalize(normal_matrix*vec4(normal,0.)).xyz;
+    // the normal matrices are in world space, the view only rotates them, only the upper
+    // 3x3 is used since the inverse transpose moves the translation into the bottom row
+    v_normal=normalize(mat3(camera.view)*mat3(normal_matrix)*normal);
     v_tex_coord=tex_coord;
-    v_instance_index=gl_InstanceIndex;
+    v_instance_index=int(instance);
 }
\ No newline at end of file
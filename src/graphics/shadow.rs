@@ -0,0 +1,362 @@
+use glam::{Mat4, Vec3};
+
+use crate::graphics::bounds::Aabb;
+use crate::graphics::camera::Camera;
+
+// the cascades of all shadowed directional lights together, the shaders have room for
+// this many, lights past it are drawn without shadows
+pub const MAX_CASCADES: usize = 8;
+pub const MAX_CASCADES_PER_LIGHT: u32 = 4;
+// point lights with shadows, each has a cube of six shadow maps around it
+pub const MAX_POINT_SHADOWS: u32 = 4;
+// the closest a caster can be to a point light
+const POINT_SHADOW_NEAR: f32 = 0.1;
+
+// how the lights that cast shadows render them, the same for all of them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowSettings {
+    // the view is split into this many slices along its depth, each with its own shadow map
+    pub cascade_count: u32,
+    // width and height of the shadow map of every cascade
+    pub resolution: u32,
+    // nothing further from the camera is shadowed, the far plane limits it as well
+    pub distance: f32,
+    // 0 places the splits evenly, 1 logarithmically, values in between blend the two
+    pub split_lambda: f32,
+    // texels averaged to each side of the one looked up, 0 for hard edges
+    pub pcf_radius: u32,
+    // subtracted from the depth of a point before it is compared with the shadow map
+    pub depth_bias: f32,
+    // moves the point along its normal before the lookup, in texels of its shadow map
+    pub normal_bias: f32,
+    // how many point lights get shadows, the first ones casting them in the scene
+    pub point_shadow_lights: u32,
+    // width and height of every face of the cubes around point lights
+    pub point_resolution: u32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            cascade_count: 4,
+            resolution: 2048,
+            distance: 50.0,
+            split_lambda: 0.75,
+            pcf_radius: 1,
+            depth_bias: 0.0005,
+            normal_bias: 1.5,
+            point_shadow_lights: 2,
+            point_resolution: 512,
+        }
+    }
+}
+
+// one slice of the view and the shadow map covering it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cascade {
+    // world space to the clip space of the shadow map, with a depth range of 0 to 1
+    pub view_projection: Mat4,
+    // the view depth at which the next cascade takes over
+    pub far: f32,
+    // the world space size of a shadow map texel
+    pub texel_size: f32,
+}
+
+// the six shadow maps around a point light, in the order +x, -x, +y, -y, +z, -z
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CubeShadow {
+    // world space to the clip space of every face, with a depth range of 0 to 1
+    pub faces: [Mat4; 6],
+    // the world space size of a texel at a distance of 1 from the light
+    pub texel_scale: f32,
+}
+
+impl ShadowSettings {
+    pub fn max_point_shadows(&self) -> usize {
+        self.point_shadow_lights.min(MAX_POINT_SHADOWS) as usize
+    }
+
+    // the faces look a little past 90 degrees, so the filter of a lookup close to the
+    // edge of a face stays on it instead of sampling outside, the far plane is as far
+    // as the furthest corner of `scene_bounds`
+    pub fn cube_shadow(&self, position: Vec3, scene_bounds: &Aabb) -> CubeShadow {
+        let resolution = self.point_resolution.max(8) as f32;
+        let margin = (self.pcf_radius + 1) as f32;
+        let tan_half_fov = 1.0 / (1.0 - 2.0 * margin / resolution).max(0.5);
+        let projection = Mat4::perspective_rh(
+            2.0 * tan_half_fov.atan(),
+            1.0,
+            POINT_SHADOW_NEAR,
+            self.point_far(position, scene_bounds),
+        );
+
+        let faces = [
+            (Vec3::X, Vec3::Y),
+            (Vec3::NEG_X, Vec3::Y),
+            (Vec3::Y, Vec3::Z),
+            (Vec3::NEG_Y, Vec3::Z),
+            (Vec3::Z, Vec3::Y),
+            (Vec3::NEG_Z, Vec3::Y),
+        ]
+        .map(|(direction, up)| projection * Mat4::look_at_rh(position, position + direction, up));
+
+        CubeShadow {
+            faces,
+            texel_scale: 2.0 * tan_half_fov / resolution,
+        }
+    }
+
+    fn point_far(&self, position: Vec3, scene_bounds: &Aabb) -> f32 {
+        let furthest = if scene_bounds.is_empty() {
+            0.0
+        } else {
+            scene_bounds
+                .corners()
+                .iter()
+                .map(|corner| corner.distance(position))
+                .fold(0.0, f32::max)
+        };
+        furthest.max(POINT_SHADOW_NEAR * 2.0)
+    }
+
+    pub fn cascade_count(&self) -> u32 {
+        self.cascade_count.clamp(1, MAX_CASCADES_PER_LIGHT)
+    }
+
+    // how many lights fit into the shaders with the current cascade count
+    pub fn max_shadowed_lights(&self) -> usize {
+        MAX_CASCADES / self.cascade_count() as usize
+    }
+
+    // the view depth at which every cascade ends, closer cascades get more of the
+    // resolution the more logarithmic the split is
+    pub fn cascade_splits(&self, camera: &Camera) -> Vec<f32> {
+        let near = camera.near_plane;
+        let far = self.distance.min(camera.far_plane).max(near);
+        let lambda = self.split_lambda.clamp(0.0, 1.0);
+        let count = self.cascade_count();
+
+        (1..=count)
+            .map(|index| {
+                let part = index as f32 / count as f32;
+                let logarithmic = near * (far / near).powf(part);
+                let uniform = near + (far - near) * part;
+                lambda * logarithmic + (1.0 - lambda) * uniform
+            })
+            .collect()
+    }
+
+    // fits an orthographic shadow map around every slice of the view, the maps reach
+    // back towards the light far enough to take in every caster of `scene_bounds`
+    pub fn cascades(
+        &self,
+        camera: &Camera,
+        aspect_ratio: f32,
+        light_direction: Vec3,
+        scene_bounds: &Aabb,
+    ) -> Vec<Cascade> {
+        let direction = light_direction.normalize_or(Vec3::NEG_Y);
+        let up = if direction.y.abs() > 0.99 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+        // turns the world so the light shines down -z, without moving it
+        let rotation = Mat4::look_at_rh(Vec3::ZERO, direction, up);
+        let view = camera.view_matrix();
+        let resolution = self.resolution.max(1) as f32;
+
+        let mut near = camera.near_plane;
+        self.cascade_splits(camera)
+            .into_iter()
+            .map(|far| {
+                let inverse_slice =
+                    (Mat4::perspective_rh(camera.fov.to_radians(), aspect_ratio, near, far) * view)
+                        .inverse();
+                let corners = [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]]
+                    .into_iter()
+                    .flat_map(|[x, y]| [Vec3::new(x, y, 0.0), Vec3::new(x, y, 1.0)])
+                    .map(|corner| inverse_slice.project_point3(corner))
+                    .collect::<Vec<_>>();
+                near = far;
+
+                // a sphere keeps the size of the map when the camera turns, so the
+                // texels do not change size and the edges do not swim
+                let center = corners.iter().sum::<Vec3>() / corners.len() as f32;
+                let radius = corners
+                    .iter()
+                    .map(|corner| corner.distance(center))
+                    .fold(0.0, f32::max);
+                // rounded up, so float noise does not change it from frame to frame
+                let radius = ((radius * 16.0).ceil() / 16.0).max(1.0 / 16.0);
+                let texel_size = 2.0 * radius / resolution;
+
+                // and snapping it to whole texels keeps them in place when it moves
+                let light_space = rotation.transform_point3(center);
+                let snapped = (light_space.truncate() / texel_size).floor() * texel_size;
+                let center = rotation
+                    .inverse()
+                    .transform_point3(snapped.extend(light_space.z));
+
+                let light_view = Mat4::look_at_rh(center, center + direction, up);
+                // distances along the light, casters between the light and the slice
+                // have to be in the map as well
+                let closest = if scene_bounds.is_empty() {
+                    -radius
+                } else {
+                    scene_bounds
+                        .corners()
+                        .iter()
+                        .map(|corner| -light_view.transform_point3(*corner).z)
+                        .fold(-radius, f32::min)
+                };
+                let projection =
+                    Mat4::orthographic_rh(-radius, radius, -radius, radius, closest, radius);
+
+                Cascade {
+                    view_projection: projection * light_view,
+                    far,
+                    texel_size,
+                }
+            })
+            .collect()
+    }
+}
+
+impl CubeShadow {
+    // the face the direction from the light points through, the axis it is longest along
+    pub fn face(direction: Vec3) -> usize {
+        let axis = direction.abs();
+        if axis.x >= axis.y && axis.x >= axis.z {
+            if direction.x > 0.0 { 0 } else { 1 }
+        } else if axis.y >= axis.z {
+            if direction.y > 0.0 { 2 } else { 3 }
+        } else if direction.z > 0.0 {
+            4
+        } else {
+            5
+        }
+    }
+}
+
+impl Cascade {
+    // the shadow map clip space of a view space point, what the shaders look up
+    pub fn from_view_space(&self, view: &Mat4) -> Mat4 {
+        self.view_projection * view.inverse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> Camera {
+        Camera {
+            position: Vec3::new(3.0, 2.0, 8.0),
+            target: Vec3::new(0.0, 0.5, 0.0),
+            ..Camera::default()
+        }
+    }
+
+    fn in_clip_space(view_projection: &Mat4, point: Vec3) -> bool {
+        let clip = view_projection.project_point3(point);
+        clip.x.abs() <= 1.0 + 1e-4
+            && clip.y.abs() <= 1.0 + 1e-4
+            && (-1e-4..=1.0 + 1e-4).contains(&clip.z)
+    }
+
+    #[test]
+    fn splits_increase_up_to_the_shadow_distance() {
+        let camera = camera();
+        for split_lambda in [0.0, 0.5, 1.0] {
+            let settings = ShadowSettings {
+                split_lambda,
+                distance: 40.0,
+                ..ShadowSettings::default()
+            };
+            let splits = settings.cascade_splits(&camera);
+            assert_eq!(splits.len(), 4);
+            assert!(splits[0] > camera.near_plane);
+            assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!((splits[3] - 40.0).abs() < 1e-3);
+        }
+
+        // the far plane ends the last cascade before the distance does
+        let settings = ShadowSettings {
+            split_lambda: 0.0,
+            distance: 500.0,
+            ..ShadowSettings::default()
+        };
+        let splits = settings.cascade_splits(&camera);
+        let step = (camera.far_plane - camera.near_plane) / 4.0;
+        for (index, split) in splits.iter().enumerate() {
+            let expected = camera.near_plane + step * (index + 1) as f32;
+            assert!((split - expected).abs() < 1e-3, "{:?}", splits);
+        }
+    }
+
+    #[test]
+    fn cascades_cover_their_slice_and_the_casters_towards_the_light() {
+        let camera = camera();
+        let aspect_ratio = 16.0 / 9.0;
+        let settings = ShadowSettings::default();
+        let light_direction = Vec3::new(-0.3, -1.0, -0.2);
+        let scene_bounds = Aabb::new(Vec3::splat(-30.0), Vec3::splat(30.0));
+        let cascades = settings.cascades(&camera, aspect_ratio, light_direction, &scene_bounds);
+        assert_eq!(cascades.len(), settings.cascade_count() as usize);
+
+        let view = camera.view_matrix();
+        let mut near = camera.near_plane;
+        for cascade in &cascades {
+            let inverse_slice =
+                (Mat4::perspective_rh(camera.fov.to_radians(), aspect_ratio, near, cascade.far)
+                    * view)
+                    .inverse();
+            for x in [-1.0, 1.0] {
+                for y in [-1.0, 1.0] {
+                    for z in [0.0, 1.0] {
+                        let corner = inverse_slice.project_point3(Vec3::new(x, y, z));
+                        assert!(in_clip_space(&cascade.view_projection, corner));
+                    }
+                }
+            }
+            // a caster high above the slice still lands in front of the map
+            let center = inverse_slice.project_point3(Vec3::new(0.0, 0.0, 0.5));
+            let caster = center - light_direction.normalize() * 20.0;
+            let depth = cascade.view_projection.project_point3(caster).z;
+            assert!(depth >= -1e-4, "caster behind the map at {}", depth);
+            near = cascade.far;
+        }
+        assert!(
+            cascades
+                .windows(2)
+                .all(|pair| pair[0].texel_size <= pair[1].texel_size)
+        );
+    }
+
+    #[test]
+    fn cube_faces_see_the_directions_they_are_picked_for() {
+        let settings = ShadowSettings::default();
+        let position = Vec3::new(1.0, 2.0, 3.0);
+        let scene_bounds = Aabb::new(Vec3::splat(-10.0), Vec3::splat(10.0));
+        let cube = settings.cube_shadow(position, &scene_bounds);
+
+        for direction in [
+            Vec3::new(1.0, 0.9, -0.9),
+            Vec3::new(-1.0, 0.2, 0.3),
+            Vec3::new(0.1, 1.0, -0.99),
+            Vec3::new(0.5, -1.0, 0.5),
+            Vec3::new(-0.7, 0.7, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        ] {
+            let face = CubeShadow::face(direction);
+            let point = position + direction.normalize() * 5.0;
+            assert!(
+                in_clip_space(&cube.faces[face], point),
+                "{} is not on face {}",
+                direction,
+                face
+            );
+        }
+    }
+}
@@ -0,0 +1,645 @@
+use std::borrow::Cow;
+use std::f32::consts::FRAC_PI_2;
+
+use glam::{Vec2, Vec3};
+
+use crate::graphics::{error::GraphicsError, mesh::Mesh, primitives::Geometry, vertex::Vertex};
+
+pub const BUNDLED_FONT: &[u8] = include_bytes!("../../assets/fonts/JetBrainsMono-Regular.ttf");
+
+// neighboring side walls closer than this angle are shaded as one smooth surface
+const SMOOTH_ANGLE_COSINE: f32 = 0.866;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextOptions {
+    // lines are separated by '\n'
+    pub text: Cow<'static, str>,
+    // height of one em
+    pub size: f32,
+    // extrusion along z, zero gives flat single sided text facing +z
+    pub depth: f32,
+    // width of the rounded edge between the faces and the sides, at most half the depth
+    // and smaller on glyphs with strokes too thin for it
+    pub bevel: f32,
+    pub bevel_segments: u32,
+    // largest distance between a curve and its flattened segments, relative to `size`
+    pub curve_tolerance: f32,
+}
+
+impl TextOptions {
+    pub const DEFAULT: TextOptions = TextOptions {
+        text: Cow::Borrowed("Text"),
+        size: 1.0,
+        depth: 0.2,
+        bevel: 0.02,
+        bevel_segments: 2,
+        curve_tolerance: 0.002,
+    };
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions::DEFAULT
+    }
+}
+
+// text set in the bundled font, the first baseline runs along +x through the origin
+pub fn create_text(options: &TextOptions) -> Mesh {
+    create_text_with_font(BUNDLED_FONT, options).expect("the bundled font is valid")
+}
+
+pub fn create_text_with_font(
+    font_data: &[u8],
+    options: &TextOptions,
+) -> Result<Mesh, GraphicsError> {
+    let face = ttf_parser::Face::parse(font_data, 0)
+        .map_err(|e| GraphicsError::FontError(format!("Failed to parse font: {}", e)))?;
+
+    let units_per_em = face.units_per_em() as f32;
+    let scale = options.size / units_per_em;
+    let line_height = (face.height() + face.line_gap()) as f32;
+    let tolerance = options.curve_tolerance.max(1e-5) * units_per_em;
+
+    let mut geometry = Geometry::default();
+    let mut pen = Vec2::ZERO;
+
+    for character in options.text.chars() {
+        if character == '\n' {
+            pen = Vec2::new(0.0, pen.y - line_height);
+            continue;
+        }
+
+        // missing characters show the replacement glyph
+        let glyph = face
+            .glyph_index(character)
+            .unwrap_or(ttf_parser::GlyphId(0));
+
+        let mut outline = Outline {
+            tolerance,
+            offset: pen,
+            contours: Vec::new(),
+            current: Vec::new(),
+        };
+        if face.outline_glyph(glyph, &mut outline).is_some() {
+            ttf_parser::OutlineBuilder::close(&mut outline);
+            push_glyph(&mut geometry, outline.contours, scale, options);
+        }
+
+        pen.x += face.glyph_hor_advance(glyph).unwrap_or(0) as f32;
+    }
+
+    Ok(geometry.into_mesh())
+}
+
+// flattens the glyph curves into closed contours in font units
+struct Outline {
+    tolerance: f32,
+    offset: Vec2,
+    contours: Vec<Vec<Vec2>>,
+    current: Vec<Vec2>,
+}
+
+impl Outline {
+    fn last(&self) -> Vec2 {
+        self.current.last().copied().unwrap_or(self.offset)
+    }
+
+    fn push(&mut self, point: Vec2) {
+        if self.current.last() != Some(&point) {
+            self.current.push(point);
+        }
+    }
+}
+
+impl ttf_parser::OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.close();
+        self.push(self.offset + Vec2::new(x, y));
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.push(self.offset + Vec2::new(x, y));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let p0 = self.last();
+        let p1 = self.offset + Vec2::new(x1, y1);
+        let p2 = self.offset + Vec2::new(x, y);
+
+        // the flattening error of a quadratic shrinks with the square of the segment count
+        let curvature = (p0 - p1 * 2.0 + p2).length();
+        let segments = (curvature / (8.0 * self.tolerance))
+            .sqrt()
+            .ceil()
+            .clamp(1.0, 64.0) as u32;
+
+        for segment in 1..=segments {
+            let t = segment as f32 / segments as f32;
+            let s = 1.0 - t;
+            self.push(p0 * (s * s) + p1 * (2.0 * s * t) + p2 * (t * t));
+        }
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let p0 = self.last();
+        let p1 = self.offset + Vec2::new(x1, y1);
+        let p2 = self.offset + Vec2::new(x2, y2);
+        let p3 = self.offset + Vec2::new(x, y);
+
+        let curvature = (p0 - p1 * 2.0 + p2)
+            .length()
+            .max((p1 - p2 * 2.0 + p3).length());
+        let segments = (curvature * 0.75 / self.tolerance)
+            .sqrt()
+            .ceil()
+            .clamp(1.0, 64.0) as u32;
+
+        for segment in 1..=segments {
+            let t = segment as f32 / segments as f32;
+            let s = 1.0 - t;
+            self.push(
+                p0 * (s * s * s)
+                    + p1 * (3.0 * s * s * t)
+                    + p2 * (3.0 * s * t * t)
+                    + p3 * (t * t * t),
+            );
+        }
+    }
+
+    fn close(&mut self) {
+        let mut contour = std::mem::take(&mut self.current);
+        if contour.len() > 1 && contour.first() == contour.last() {
+            contour.pop();
+        }
+        if contour.len() >= 3 && signed_area(&contour).abs() > f32::EPSILON {
+            self.contours.push(contour);
+        }
+    }
+}
+
+fn push_glyph(
+    geometry: &mut Geometry,
+    mut contours: Vec<Vec<Vec2>>,
+    scale: f32,
+    options: &TextOptions,
+) {
+    let holes = orient_contours(&mut contours);
+
+    for contour in &mut contours {
+        for point in contour.iter_mut() {
+            *point *= scale;
+        }
+    }
+
+    let depth = options.depth.max(0.0);
+    let tex_coord = |point: Vec2| Vec2::new(point.x, -point.y) / options.size.max(f32::EPSILON);
+
+    let sides = contours
+        .iter()
+        .map(|contour| ContourSides::new(contour))
+        .collect::<Vec<_>>();
+    let inset_by = |bevel: f32| {
+        contours
+            .iter()
+            .zip(&sides)
+            .map(|(contour, sides)| {
+                contour
+                    .iter()
+                    .zip(&sides.miters)
+                    .map(|(&point, &miter)| point - miter * bevel)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // the caps are triangulated after the bevel inset, triangles of the full outline
+    // can turn over when their corners move inwards by different amounts, and strokes
+    // too thin for the bevel get a smaller one so the inset outlines do not cross
+    let mut bevel = if depth > 0.0 && options.bevel_segments > 0 {
+        options.bevel.clamp(0.0, depth * 0.5)
+    } else {
+        0.0
+    };
+    let mut inset = inset_by(bevel);
+    for _ in 0..8 {
+        if bevel == 0.0 || !inset_folds(&contours, &inset) {
+            break;
+        }
+        bevel *= 0.5;
+        inset = inset_by(bevel);
+    }
+    if inset_folds(&contours, &inset) {
+        bevel = 0.0;
+        inset = inset_by(bevel);
+    }
+    let triangles = triangulate(&inset, &holes);
+    let inset_points = inset.into_iter().flatten().collect::<Vec<_>>();
+
+    // the front face, and the back face turned around when extruded
+    let caps: &[(f32, Vec3, bool)] = if depth > 0.0 {
+        &[
+            (depth * 0.5, Vec3::Z, false),
+            (-depth * 0.5, Vec3::NEG_Z, true),
+        ]
+    } else {
+        &[(0.0, Vec3::Z, false)]
+    };
+    for &(z, normal, reversed) in caps {
+        let indices = inset_points
+            .iter()
+            .map(|&point| {
+                geometry.push_vertex(Vertex {
+                    position: point.extend(z),
+                    normal,
+                    tex_coord: tex_coord(point),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for &[a, b, c] in &triangles {
+            let (b, c) = if reversed { (c, b) } else { (b, c) };
+            push_triangle(
+                geometry,
+                [
+                    indices[a as usize],
+                    indices[b as usize],
+                    indices[c as usize],
+                ],
+            );
+        }
+    }
+
+    if depth == 0.0 {
+        return;
+    }
+
+    // profile from the front face around the bevel, down the side and around the back
+    // rings: inset from the outline, z, and the outward and z parts of the normal
+    let mut rings = Vec::new();
+    if bevel > 0.0 {
+        let segments = options.bevel_segments;
+        let ring = |segment: u32| {
+            let angle = segment as f32 / segments as f32 * FRAC_PI_2;
+            (
+                bevel * (1.0 - angle.sin()),
+                depth * 0.5 - bevel * (1.0 - angle.cos()),
+                angle.sin(),
+                angle.cos(),
+            )
+        };
+        rings.extend((0..=segments).map(ring));
+        rings.extend(
+            (0..=segments)
+                .rev()
+                .map(ring)
+                .map(|(inset, z, outward, along_z)| (inset, -z, outward, -along_z)),
+        );
+    } else {
+        rings.push((0.0, depth * 0.5, 1.0, 0.0));
+        rings.push((0.0, -depth * 0.5, 1.0, 0.0));
+    }
+
+    for (contour, sides) in contours.iter().zip(&sides) {
+        let count = contour.len();
+        let mut distance = 0.0;
+
+        for start in 0..count {
+            let end = (start + 1) % count;
+            let length = contour[start].distance(contour[end]);
+
+            let mut column = |point: usize, normal: Vec2, u: f32| {
+                rings
+                    .iter()
+                    .map(|&(inset, z, outward, along_z)| Vertex {
+                        position: (contour[point] - sides.miters[point] * inset).extend(z),
+                        normal: (normal * outward).extend(along_z).normalize_or_zero(),
+                        tex_coord: Vec2::new(
+                            u / options.size.max(f32::EPSILON),
+                            (depth * 0.5 - z) / depth,
+                        ),
+                    })
+                    .map(|vertex| geometry.push_vertex(vertex))
+                    .collect::<Vec<_>>()
+            };
+
+            let first = column(start, sides.outgoing[start], distance);
+            let second = column(end, sides.incoming[end], distance + length);
+
+            for ring in 0..rings.len() - 1 {
+                push_triangle(geometry, [first[ring], first[ring + 1], second[ring + 1]]);
+                push_triangle(geometry, [first[ring], second[ring + 1], second[ring]]);
+            }
+
+            distance += length;
+        }
+    }
+}
+
+fn push_triangle(geometry: &mut Geometry, [a, b, c]: [u32; 3]) {
+    if a != b && b != c && c != a {
+        geometry.indices.extend_from_slice(&[a, b, c]);
+    }
+}
+
+// outward normals at the corners of a contour, the solid lies to the left of each edge
+struct ContourSides {
+    // normal of the side wall arriving at and leaving each corner, equal on smooth corners
+    incoming: Vec<Vec2>,
+    outgoing: Vec<Vec2>,
+    // offset that moves both edges at a corner by one unit
+    miters: Vec<Vec2>,
+}
+
+impl ContourSides {
+    fn new(contour: &[Vec2]) -> Self {
+        let count = contour.len();
+        let edge_normals = (0..count)
+            .map(|i| {
+                let direction = contour[(i + 1) % count] - contour[i];
+                Vec2::new(direction.y, -direction.x).normalize_or_zero()
+            })
+            .collect::<Vec<_>>();
+
+        let mut sides = ContourSides {
+            incoming: Vec::with_capacity(count),
+            outgoing: Vec::with_capacity(count),
+            miters: Vec::with_capacity(count),
+        };
+
+        for i in 0..count {
+            let before = edge_normals[(i + count - 1) % count];
+            let after = edge_normals[i];
+            let cosine = before.dot(after);
+
+            if cosine > SMOOTH_ANGLE_COSINE {
+                let smooth = (before + after).normalize_or_zero();
+                sides.incoming.push(smooth);
+                sides.outgoing.push(smooth);
+            } else {
+                sides.incoming.push(before);
+                sides.outgoing.push(after);
+            }
+
+            // limited so hairpin corners do not shoot far out
+            sides
+                .miters
+                .push(((before + after) / (1.0 + cosine).max(1e-3)).clamp_length_max(2.0));
+        }
+
+        sides
+    }
+}
+
+fn signed_area(contour: &[Vec2]) -> f32 {
+    contour
+        .iter()
+        .zip(contour.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum::<f32>()
+        * 0.5
+}
+
+fn contains(contour: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    for (a, b) in contour.iter().zip(contour.iter().cycle().skip(1)) {
+        if (a.y > point.y) != (b.y > point.y) {
+            let x = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if point.x < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+// fonts disagree on the winding of outlines and holes, so the holes are found by
+// nesting depth and every outline is made counter clockwise and every hole clockwise
+fn orient_contours(contours: &mut [Vec<Vec2>]) -> Vec<bool> {
+    let holes = (0..contours.len())
+        .map(|i| {
+            let depth = (0..contours.len())
+                .filter(|&j| j != i && contains(&contours[j], contours[i][0]))
+                .count();
+            depth % 2 == 1
+        })
+        .collect::<Vec<_>>();
+
+    for (contour, &hole) in contours.iter_mut().zip(&holes) {
+        if (signed_area(contour) > 0.0) == hole {
+            contour.reverse();
+        }
+    }
+
+    holes
+}
+
+// ear clipping, holes are joined to their outline by a bridge edge first,
+// returns indices into the contour points laid out one contour after another
+fn triangulate(contours: &[Vec<Vec2>], holes: &[bool]) -> Vec<[u32; 3]> {
+    let points = contours.iter().flatten().copied().collect::<Vec<_>>();
+    let ranges = contours
+        .iter()
+        .scan(0u32, |start, contour| {
+            let range = *start..*start + contour.len() as u32;
+            *start = range.end;
+            Some(range)
+        })
+        .collect::<Vec<_>>();
+
+    let mut triangles = Vec::new();
+
+    for outline in (0..contours.len()).filter(|&i| !holes[i]) {
+        let mut polygon = ranges[outline].clone().collect::<Vec<_>>();
+
+        // holes belong to the smallest outline around them
+        let mut children = (0..contours.len())
+            .filter(|&hole| {
+                holes[hole]
+                    && (0..contours.len())
+                        .filter(|&i| !holes[i] && contains(&contours[i], contours[hole][0]))
+                        .min_by(|&a, &b| {
+                            signed_area(&contours[a])
+                                .abs()
+                                .total_cmp(&signed_area(&contours[b]).abs())
+                        })
+                        == Some(outline)
+            })
+            .collect::<Vec<_>>();
+
+        // bridging from right to left keeps earlier bridges out of the way
+        let max_x = |hole: &usize| contours[*hole].iter().fold(f32::MIN, |max, p| max.max(p.x));
+        children.sort_by(|a, b| max_x(b).total_cmp(&max_x(a)));
+
+        for hole in children {
+            bridge_hole(&mut polygon, ranges[hole].clone(), &points);
+        }
+
+        clip_ears(polygon, &points, &mut triangles);
+    }
+
+    triangles
+}
+
+fn bridge_hole(polygon: &mut Vec<u32>, hole: std::ops::Range<u32>, points: &[Vec2]) {
+    let hole = hole.collect::<Vec<_>>();
+    let (start, &rightmost) = hole
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| points[**a as usize].x.total_cmp(&points[**b as usize].x))
+        .expect("contours have at least three points");
+    let from = points[rightmost as usize];
+
+    // closest outline vertex to the right that can be reached without crossing an edge
+    let mut candidates = (0..polygon.len()).collect::<Vec<_>>();
+    candidates.sort_by(|&a, &b| {
+        let key = |i: usize| {
+            let point = points[polygon[i] as usize];
+            (point.x < from.x, point.distance_squared(from))
+        };
+        let (a, b) = (key(a), key(b));
+        a.0.cmp(&b.0).then(a.1.total_cmp(&b.1))
+    });
+
+    let edges = |indices: &[u32]| {
+        (0..indices.len())
+            .map(|i| {
+                (
+                    points[indices[i] as usize],
+                    points[indices[(i + 1) % indices.len()] as usize],
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut blocking = edges(polygon);
+    blocking.extend(edges(&hole));
+
+    let target = candidates
+        .iter()
+        .copied()
+        .find(|&candidate| {
+            let to = points[polygon[candidate] as usize];
+            blocking.iter().all(|&(a, b)| {
+                a == from || a == to || b == from || b == to || !segments_cross(from, to, a, b)
+            })
+        })
+        .unwrap_or(candidates[0]);
+
+    // outline up to the target, around the hole and back to the target
+    let mut merged = Vec::with_capacity(polygon.len() + hole.len() + 2);
+    merged.extend_from_slice(&polygon[..=target]);
+    merged.extend_from_slice(&hole[start..]);
+    merged.extend_from_slice(&hole[..=start]);
+    merged.extend_from_slice(&polygon[target..]);
+    *polygon = merged;
+}
+
+// whether an inset edge turned around or any two inset edges cross, edges meeting at
+// a corner are not counted
+fn inset_folds(contours: &[Vec<Vec2>], inset: &[Vec<Vec2>]) -> bool {
+    let edge_directions = |contour: &[Vec2]| {
+        contour
+            .iter()
+            .zip(contour.iter().cycle().skip(1))
+            .map(|(&a, &b)| b - a)
+            .collect::<Vec<_>>()
+    };
+    let turned = contours.iter().zip(inset).any(|(contour, inset)| {
+        edge_directions(contour)
+            .iter()
+            .zip(edge_directions(inset))
+            .any(|(before, after)| before.dot(after) <= 0.0)
+    });
+    if turned {
+        return true;
+    }
+
+    let edges = inset
+        .iter()
+        .flat_map(|contour| {
+            contour
+                .iter()
+                .zip(contour.iter().cycle().skip(1))
+                .map(|(&a, &b)| (a, b))
+        })
+        .collect::<Vec<_>>();
+
+    edges.iter().enumerate().any(|(i, &(a, b))| {
+        edges[i + 1..]
+            .iter()
+            .any(|&(c, d)| a != c && a != d && b != c && b != d && segments_cross(a, b, c, d))
+    })
+}
+
+fn segments_cross(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    let ab = b - a;
+    let cd = d - c;
+    (ab.perp_dot(c - a) * ab.perp_dot(d - a) < 0.0)
+        && (cd.perp_dot(a - c) * cd.perp_dot(b - c) < 0.0)
+}
+
+fn clip_ears(mut polygon: Vec<u32>, points: &[Vec2], triangles: &mut Vec<[u32; 3]>) {
+    let position = |index: u32| points[index as usize];
+    let mut start = 0;
+
+    while polygon.len() > 3 {
+        let count = polygon.len();
+        let corner = |i: usize| {
+            (
+                polygon[(i + count - 1) % count],
+                polygon[i],
+                polygon[(i + 1) % count],
+            )
+        };
+
+        let ear = (0..count).map(|k| (start + k) % count).find(|&i| {
+            let (a, b, c) = corner(i);
+            let (pa, pb, pc) = (position(a), position(b), position(c));
+            if (pb - pa).perp_dot(pc - pb) <= 0.0 {
+                return false;
+            }
+
+            // bridges repeat vertices, those copies do not block the ear
+            polygon.iter().all(|&other| {
+                let point = position(other);
+                point == pa || point == pb || point == pc || !in_triangle(point, pa, pb, pc)
+            })
+        });
+
+        match ear {
+            Some(i) => {
+                let (a, b, c) = corner(i);
+                triangles.push([a, b, c]);
+                polygon.remove(i);
+                start = i % polygon.len();
+            }
+            None => {
+                // only flat or self touching leftovers remain, drop the flattest corner
+                let flattest = (0..count)
+                    .min_by(|&x, &y| {
+                        let area = |i: usize| {
+                            let (a, b, c) = corner(i);
+                            (position(b) - position(a))
+                                .perp_dot(position(c) - position(b))
+                                .abs()
+                        };
+                        area(x).total_cmp(&area(y))
+                    })
+                    .unwrap_or(0);
+                polygon.remove(flattest);
+                start = 0;
+            }
+        }
+    }
+
+    if let [a, b, c] = polygon[..]
+        && (position(b) - position(a)).perp_dot(position(c) - position(b)) > 0.0
+    {
+        triangles.push([a, b, c]);
+    }
+}
+
+fn in_triangle(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    (b - a).perp_dot(point - a) >= 0.0
+        && (c - b).perp_dot(point - b) >= 0.0
+        && (a - c).perp_dot(point - c) >= 0.0
+}
@@ -0,0 +1,363 @@
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::graphics::{
+    self,
+    assets::AssetManager,
+    light::{DirectionalLight, Light, PointLight, SpotLight},
+    material::MaterialHandle,
+    vertex::Vertex,
+};
+use crate::reader::ImportOptions;
+use crate::reader::error::FileError;
+
+pub fn read_file(path: &str) -> Result<graphics::scene::Scene, FileError> {
+    read_file_with_options(path, &ImportOptions::default())
+}
+
+pub fn read_file_with_options(
+    path: &str,
+    options: &ImportOptions,
+) -> Result<graphics::scene::Scene, FileError> {
+    read_file_with_assets(path, options, &mut AssetManager::new())
+}
+
+// reads .gltf and .glb files with their meshes, materials and the lights of
+// KHR_lights_punctual, shared with equal assets in `assets` like the OBJ reader does
+pub fn read_file_with_assets(
+    path: &str,
+    options: &ImportOptions,
+    assets: &mut AssetManager,
+) -> Result<graphics::scene::Scene, FileError> {
+    let gltf = gltf::Gltf::open(path).map_err(|e| to_file_error(path, e))?;
+    let base = std::path::Path::new(path).parent();
+    let buffers = gltf::import_buffers(&gltf.document, base, gltf.blob.clone())
+        .map_err(|e| to_file_error(path, e))?;
+
+    parse_document(path, &gltf.document, &buffers, options, assets)
+}
+
+fn to_file_error(path: &str, error: gltf::Error) -> FileError {
+    match error {
+        gltf::Error::Io(error) => FileError::IoError(error),
+        error => FileError::InvalidFormat(format!("{}: {}", path, error)),
+    }
+}
+
+fn parse_document(
+    path: &str,
+    document: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+    options: &ImportOptions,
+    assets: &mut AssetManager,
+) -> Result<graphics::scene::Scene, FileError> {
+    let mut scene = graphics::scene::Scene::new();
+
+    let file_name = std::path::Path::new(path)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or("glTF")
+        .to_string();
+    let base = std::path::Path::new(path)
+        .parent()
+        .unwrap_or(std::path::Path::new(""));
+
+    // every material of the file is added, primitives without one use the default
+    let materials = document
+        .materials()
+        .map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            let [r, g, b, _] = pbr.base_color_factor();
+            let mut properties = graphics::material::GpuMaterials::default();
+            properties.diffuse_color = Vec3::new(r, g, b);
+            // rough surfaces get a wide highlight, smooth ones a sharp one
+            let roughness = pbr.roughness_factor().clamp(0.0, 1.0);
+            properties.specular_exponent = 2.0 + (1.0 - roughness).powi(2) * 254.0;
+
+            let diffuse_texture = pbr.base_color_texture().and_then(|info| {
+                match info.texture().source().source() {
+                    gltf::image::Source::Uri { uri, .. } => {
+                        let texture_path = base.join(uri);
+                        let texture_path = texture_path.to_str().unwrap_or(uri);
+                        assets
+                            .load_texture(texture_path)
+                            .inspect_err(|e| {
+                                println!("Failed to load texture {}: {}", texture_path, e)
+                            })
+                            .ok()
+                    }
+                    // embedded images are not read yet
+                    gltf::image::Source::View { .. } => None,
+                }
+            });
+
+            let material = assets.add_material(graphics::material::Material {
+                name: material.name().unwrap_or("glTF Material").to_string(),
+                properties,
+                diffuse_texture,
+                emission: Vec3::from(material.emissive_factor()),
+            });
+            scene.materials.insert(material)
+        })
+        .collect::<Vec<_>>();
+
+    let to_error = |error: graphics::error::GraphicsError| {
+        FileError::InvalidFormat(format!("{}: {}", path, error))
+    };
+    // the file keeps its own units, unlike OBJ files its lights are placed in them
+    let root = scene
+        .add_node(file_name, Mat4::IDENTITY, None)
+        .map_err(to_error)?;
+
+    let Some(gltf_scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    else {
+        return Ok(scene);
+    };
+
+    // parents are added before their children, so their nodes already exist
+    let mut stack = gltf_scene
+        .nodes()
+        .map(|node| (node, root))
+        .collect::<Vec<_>>();
+    while let Some((node, parent)) = stack.pop() {
+        let name = node
+            .name()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("Node {}", node.index()));
+        let local_transform = Mat4::from_cols_array_2d(&node.transform().matrix());
+
+        let handle = match node.mesh() {
+            Some(mesh) => {
+                let mesh = read_mesh(path, &name, &mesh, buffers, &materials, options)?;
+                let mut mesh = graphics::mesh::Mesh::new(mesh, local_transform);
+                mesh.geometry_changed();
+                if options.optimize_meshes {
+                    for (i, report) in mesh.optimize().iter().enumerate() {
+                        println!("Optimized submesh {} of {}: {}", i, name, report);
+                    }
+                }
+                for submesh in &mut mesh.submeshes {
+                    assets.share_geometry(submesh);
+                }
+                scene.add_object(name, mesh, Some(parent))
+            }
+            None => scene.add_node(name, local_transform, Some(parent)),
+        }
+        .map_err(to_error)?;
+
+        if let Some(light) = node.light() {
+            let world_transform = scene
+                .node(handle)
+                .map_or(local_transform, |node| node.world_transform());
+            scene.lights.insert(read_light(&light, world_transform));
+        }
+
+        stack.extend(node.children().map(|child| (child, handle)));
+    }
+
+    Ok(scene)
+}
+
+// one submesh per primitive, primitives that are not triangle lists are skipped
+fn read_mesh(
+    path: &str,
+    name: &str,
+    mesh: &gltf::Mesh,
+    buffers: &[gltf::buffer::Data],
+    materials: &[MaterialHandle],
+    options: &ImportOptions,
+) -> Result<Vec<graphics::mesh::SubMesh>, FileError> {
+    let mut submeshes = Vec::new();
+    for (i, primitive) in mesh.primitives().enumerate() {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            println!(
+                "Skipping primitive {} of {}: {:?} is not supported",
+                i,
+                name,
+                primitive.mode()
+            );
+            continue;
+        }
+
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &**data));
+        let Some(positions) = reader.read_positions() else {
+            return Err(FileError::InvalidFormat(format!(
+                "{}: primitive {} of {} has no positions",
+                path, i, name
+            )));
+        };
+        let mut normals = reader.read_normals();
+        let mut tex_coords = reader.read_tex_coords(0).map(|coords| coords.into_f32());
+        let vertices = positions
+            .map(|position| Vertex {
+                position: Vec3::from(position),
+                normal: normals
+                    .as_mut()
+                    .and_then(Iterator::next)
+                    .map_or(Vec3::ZERO, Vec3::from),
+                // glTF starts its texture coordinates at the top, OBJ at the bottom
+                tex_coord: tex_coords
+                    .as_mut()
+                    .and_then(Iterator::next)
+                    .map_or(Vec2::ZERO, |[u, v]| Vec2::new(u, 1.0 - v)),
+            })
+            .collect::<Vec<_>>();
+        let indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..vertices.len() as u32).collect(),
+        };
+
+        let material = primitive
+            .material()
+            .index()
+            .and_then(|index| materials.get(index).copied());
+        let mut submesh = graphics::mesh::SubMesh::new(vertices, indices, material);
+
+        if options.repair_meshes {
+            let report = graphics::mesh::repair(&mut submesh, &options.repair_options);
+            println!("Repaired submesh {} of {}: {}", i, name, report);
+        }
+        if options.validate_meshes {
+            let report = graphics::mesh::validate(&submesh);
+            if !report.is_valid() {
+                println!(
+                    "Warning: submesh {} of {} in {} is broken: {}",
+                    i, name, path, report
+                );
+            }
+        }
+        submeshes.push(submesh);
+    }
+    Ok(submeshes)
+}
+
+// punctual lights shine along the -z axis of their node, spot cones are given in radians
+fn read_light(light: &gltf::khr_lights_punctual::Light, world_transform: Mat4) -> Light {
+    let position = world_transform.transform_point3(Vec3::ZERO);
+    let direction = world_transform
+        .transform_vector3(Vec3::NEG_Z)
+        .normalize_or(Vec3::NEG_Z);
+    let color = Vec3::from(light.color());
+    let intensity = light.intensity();
+
+    match light.kind() {
+        gltf::khr_lights_punctual::Kind::Directional => Light::Directional(DirectionalLight {
+            direction,
+            color,
+            intensity,
+            cast_shadows: false,
+        }),
+        gltf::khr_lights_punctual::Kind::Point => Light::Point(PointLight {
+            position,
+            color,
+            intensity,
+            cast_shadows: false,
+        }),
+        gltf::khr_lights_punctual::Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => Light::Spot(SpotLight {
+            position,
+            direction,
+            color,
+            intensity,
+            inner_angle: inner_cone_angle.to_degrees(),
+            outer_angle: outer_cone_angle.to_degrees(),
+            // an unlimited light has no range in the file
+            range: light.range().unwrap_or(0.0),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a spot light pointing down from above a triangle that hangs below it
+    const SPOT_LIGHT_GLTF: &str = r#"{
+        "asset": {"version": "2.0"},
+        "extensionsUsed": ["KHR_lights_punctual"],
+        "extensions": {"KHR_lights_punctual": {"lights": [{
+            "type": "spot",
+            "color": [1.0, 0.5, 0.25],
+            "intensity": 3.0,
+            "range": 10.0,
+            "spot": {"innerConeAngle": 0.2, "outerConeAngle": 0.5}
+        }]}},
+        "scene": 0,
+        "scenes": [{"nodes": [0]}],
+        "nodes": [
+            {
+                "name": "Lamp",
+                "translation": [0.0, 2.0, 0.0],
+                "rotation": [-0.70710677, 0.0, 0.0, 0.70710677],
+                "extensions": {"KHR_lights_punctual": {"light": 0}},
+                "children": [1]
+            },
+            {"name": "Triangle", "mesh": 0}
+        ],
+        "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}],
+        "accessors": [{
+            "bufferView": 0,
+            "componentType": 5126,
+            "count": 3,
+            "type": "VEC3",
+            "min": [0.0, 0.0, 0.0],
+            "max": [1.0, 1.0, 0.0]
+        }],
+        "bufferViews": [{"buffer": 0, "byteLength": 36}],
+        "buffers": [{
+            "byteLength": 36,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"
+        }]
+    }"#;
+
+    fn read(json: &str) -> graphics::scene::Scene {
+        let gltf = gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+        let buffers = gltf::import_buffers(&gltf.document, None, None).unwrap();
+        parse_document(
+            "test.gltf",
+            &gltf.document,
+            &buffers,
+            &ImportOptions::default(),
+            &mut AssetManager::new(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn spot_lights_keep_their_cones_and_range() {
+        let scene = read(SPOT_LIGHT_GLTF);
+
+        let lights = scene.lights.values().collect::<Vec<_>>();
+        let [Light::Spot(spot)] = lights.as_slice() else {
+            panic!("expected one spot light, got {:?}", lights);
+        };
+        assert!(spot.position.abs_diff_eq(Vec3::new(0.0, 2.0, 0.0), 1e-5));
+        assert!(spot.direction.abs_diff_eq(Vec3::NEG_Y, 1e-5));
+        assert_eq!(spot.color, Vec3::new(1.0, 0.5, 0.25));
+        assert_eq!(spot.intensity, 3.0);
+        assert!((spot.inner_angle - 0.2f32.to_degrees()).abs() < 1e-4);
+        assert!((spot.outer_angle - 0.5f32.to_degrees()).abs() < 1e-4);
+        assert_eq!(spot.range, 10.0);
+    }
+
+    #[test]
+    fn meshes_are_placed_under_their_parent_nodes() {
+        let scene = read(SPOT_LIGHT_GLTF);
+
+        let objects = scene.objects.values().collect::<Vec<_>>();
+        let [mesh] = objects.as_slice() else {
+            panic!("expected one object, got {}", objects.len());
+        };
+        let submesh = &mesh.submeshes[0];
+        assert_eq!(submesh.vertices.len(), 3);
+        assert_eq!(submesh.indices, vec![0, 1, 2]);
+        // the triangle follows the lamp, which is lifted and turned to face down
+        let corner = mesh
+            .world_transform
+            .transform_point3(Vec3::new(0.0, 1.0, 0.0));
+        assert!(corner.abs_diff_eq(Vec3::new(0.0, 2.0, -1.0), 1e-5));
+    }
+}
@@ -0,0 +1,47 @@
+use crate::graphics::primitives::Heightmap;
+use crate::graphics::texture::Texture;
+use crate::reader::error::FileError;
+
+// any grayscale or color image, colors are reduced to their luminance
+pub fn read_heightmap(path: &str) -> Result<Heightmap, FileError> {
+    let image = image::ImageReader::open(path)
+        .map_err(FileError::IoError)?
+        .with_guessed_format()
+        .map_err(FileError::IoError)?
+        .decode()
+        .map_err(|e| FileError::InvalidFormat(format!("{}: {}", path, e)))?;
+
+    // 16 bit keeps the precision of 16 bit heightmaps
+    let luma = image.into_luma16();
+
+    Ok(Heightmap {
+        width: luma.width(),
+        height: luma.height(),
+        samples: luma
+            .pixels()
+            .map(|pixel| pixel.0[0] as f32 / u16::MAX as f32)
+            .collect(),
+    })
+}
+
+pub fn read_texture(path: &str) -> Result<Texture, FileError> {
+    let image = image::ImageReader::open(path)
+        .map_err(FileError::IoError)?
+        .with_guessed_format()
+        .map_err(FileError::IoError)?
+        .decode()
+        .map_err(|e| FileError::InvalidFormat(format!("{}: {}", path, e)))?;
+
+    let rgba = image.into_rgba8();
+
+    Ok(Texture {
+        name: std::path::Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(path)
+            .to_string(),
+        width: rgba.width(),
+        height: rgba.height(),
+        pixels: rgba.into_raw(),
+    })
+}
@@ -1,8 +1,34 @@
 pub mod error;
+pub mod gltf_reader;
+pub mod image_reader;
 pub mod obj_reader;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImportOptions {
+    // report broken geometry of every submesh
+    pub validate_meshes: bool,
+    // fix broken geometry before anything else touches it
+    pub repair_meshes: bool,
+    pub repair_options: crate::graphics::mesh::RepairOptions,
+    // reorder the triangles and vertices of every submesh for the gpu caches
+    pub optimize_meshes: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions {
+            validate_meshes: true,
+            repair_meshes: false,
+            repair_options: crate::graphics::mesh::RepairOptions::default(),
+            optimize_meshes: false,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FileType {
     Obj,
+    Gltf,
     Fbx,
     Mtl,
 }
@@ -16,6 +42,7 @@ impl std::fmt::Display for FileType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             FileType::Obj => write!(f, "OBJ"),
+            FileType::Gltf => write!(f, "glTF"),
             FileType::Fbx => write!(f, "FBX"),
             FileType::Mtl => write!(f, "MTL"),
         }
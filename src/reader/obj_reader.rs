@@ -1,19 +1,45 @@
 use glam::{Vec2, Vec3};
 
-use crate::graphics::material::Material;
-use crate::graphics::{self, vertex::Vertex};
+use crate::graphics::{
+    self,
+    assets::{Asset, AssetManager},
+    vertex::Vertex,
+};
+use crate::reader::ImportOptions;
 use crate::reader::error::FileError;
 use std::collections::HashMap;
 use std::fs::{self};
 
 pub fn read_file(path: &str) -> Result<graphics::scene::Scene, FileError> {
+    read_file_with_options(path, &ImportOptions::default())
+}
+
+pub fn read_file_with_options(
+    path: &str,
+    options: &ImportOptions,
+) -> Result<graphics::scene::Scene, FileError> {
+    read_file_with_assets(path, options, &mut AssetManager::new())
+}
+
+// the geometry, materials and textures of the file are shared with equal assets
+// that are already alive in `assets`, so importing a file twice stores it once
+pub fn read_file_with_assets(
+    path: &str,
+    options: &ImportOptions,
+    assets: &mut AssetManager,
+) -> Result<graphics::scene::Scene, FileError> {
     // Read the OBJ file and populate the Scene
 
-    let file = fs::read_to_string(path).map_err(|e| FileError::IoError(e))?;
+    let file = fs::read_to_string(path).map_err(FileError::IoError)?;
 
-    Ok(parse_file(path, &file)?)
+    parse_file(path, &file, options, assets)
 }
-fn parse_file(path: &str, file: &str) -> Result<graphics::scene::Scene, FileError> {
+fn parse_file(
+    path: &str,
+    file: &str,
+    options: &ImportOptions,
+    assets: &mut AssetManager,
+) -> Result<graphics::scene::Scene, FileError> {
     // Parse the file content and populate the Scene
     let mut scene = graphics::scene::Scene::new();
 
@@ -24,12 +50,20 @@ fn parse_file(path: &str, file: &str) -> Result<graphics::scene::Scene, FileErro
     let mut normals = std::vec::Vec::<Vec3>::new();
     let mut tex_coords = std::vec::Vec::<Vec2>::new();
 
-    let mut mesh = graphics::mesh::Mesh {
-        submeshes: Vec::new(),
-        world_transform: glam::Mat4::IDENTITY,
-    };
+    // the objects and groups of the file, faces always go to the last one
+    let mut obj_parts = Vec::<ObjPart>::new();
+    let mut current_object: Option<usize> = None;
+    let mut current_material = None;
+
+    let file_name = std::path::Path::new(path)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or("OBJ")
+        .to_string();
 
-    let mut material_map: HashMap<String, graphics::material::Material> = HashMap::new();
+    let mut material_map: HashMap<String, Asset<graphics::material::Material>> = HashMap::new();
+    // materials are added to the scene the first time a face uses them
+    let mut material_handles = HashMap::new();
     for (line_number, line) in file.lines().enumerate() {
         if line.starts_with('#') || line.is_empty() {
             continue; // Skip comments and empty lines
@@ -142,17 +176,25 @@ fn parse_file(path: &str, file: &str) -> Result<graphics::scene::Scene, FileErro
                 }
 
                 let mut face_vertex_indices: Vec<u32> = Vec::new();
+
+                // faces before any o or g statement belong to the file itself
+                if obj_parts.is_empty() {
+                    obj_parts.push(ObjPart::new(file_name.clone(), None));
+                }
+                let mesh = &mut obj_parts.last_mut().unwrap().mesh;
+
                 // get submesh from previous created from usemtl
 
                 // get last submesh
                 let last_submesh = match mesh.submeshes.last_mut() {
                     Some(submesh) => submesh,
                     None => {
-                        mesh.submeshes.push(graphics::mesh::SubMesh {
-                            vertices: Vec::new(),
-                            indices: Vec::new(),
-                            material: Material::default(),
-                        });
+                        hash_map.clear();
+                        mesh.submeshes.push(graphics::mesh::SubMesh::new(
+                            Vec::new(),
+                            Vec::new(),
+                            current_material,
+                        ));
                         mesh.submeshes.last_mut().unwrap()
                     }
                 };
@@ -202,12 +244,11 @@ fn parse_file(path: &str, file: &str) -> Result<graphics::scene::Scene, FileErro
 
                     face_vertex_indices.push(*index);
                 }
-                let first_index = face_vertex_indices[0];
-                for i in 1..(face_vertex_indices.len() - 1) {
-                    last_submesh.indices.push(first_index);
-                    last_submesh.indices.push(face_vertex_indices[i]);
-                    last_submesh.indices.push(face_vertex_indices[i + 1]);
-                }
+                // triangulated once the whole file is read
+                last_submesh
+                    .polygons
+                    .get_or_insert_with(graphics::mesh::Polygons::default)
+                    .push(&face_vertex_indices);
             }
             "mtllib" => {
                 if parts.len() < 2 {
@@ -230,7 +271,7 @@ fn parse_file(path: &str, file: &str) -> Result<graphics::scene::Scene, FileErro
                 let mtl_path_str = mtl_path.to_str().unwrap_or("error.mtl");
 
                 println!("Loading MTL file: {}", mtl_path_str);
-                match parse_mtl_file(&mtl_path_str) {
+                match parse_mtl_file(&mtl_path_str, assets) {
                     Ok(materials) => {
                         material_map.extend(materials);
                     }
@@ -249,36 +290,149 @@ fn parse_file(path: &str, file: &str) -> Result<graphics::scene::Scene, FileErro
                 }
                 let material_name = parts[1].to_string();
 
-                // Check if the material exists in the map
-                if let Some(material) = material_map.get(&material_name) {
-                    // Create a submesh with the current vertices and indices
-                    mesh.submeshes.push(graphics::mesh::SubMesh {
-                        vertices: Vec::new(),
-                        indices: Vec::new(),
-                        material: material.clone(),
-                    });
+                // Check if the material exists in the map, a missing one falls back to the default
+                current_material = material_map.get(&material_name).map(|material| {
+                    *material_handles
+                        .entry(material_name.clone())
+                        .or_insert_with(|| scene.materials.insert(material.clone()))
+                });
+
+                // Create a submesh with the current vertices and indices
+                if let Some(part) = obj_parts.last_mut() {
+                    hash_map.clear();
+                    part.mesh.submeshes.push(graphics::mesh::SubMesh::new(
+                        Vec::new(),
+                        Vec::new(),
+                        current_material,
+                    ));
+                }
+            }
+            "o" | "g" => {
+                // objects are roots of the file, groups belong to the object they appear in
+                let name = if parts.len() > 1 {
+                    parts[1..].join(" ")
                 } else {
-                    // Material not found, create a default submesh
-                    mesh.submeshes.push(graphics::mesh::SubMesh {
-                        vertices: Vec::new(),
-                        indices: Vec::new(),
-                        material: Material::default(),
-                    });
+                    "default".to_string()
+                };
+
+                let parent = if parts[0] == "o" {
+                    None
+                } else {
+                    current_object
+                };
+                obj_parts.push(ObjPart::new(name, parent));
+                if parts[0] == "o" {
+                    current_object = Some(obj_parts.len() - 1);
                 }
             }
             _ => {}
         }
     }
 
-    mesh.normalize();
+    for part in &mut obj_parts {
+        let mesh = &mut part.mesh;
+        for (i, submesh) in mesh.submeshes.iter_mut().enumerate() {
+            // the polygons are only worth keeping for subdivision if they are not all triangles
+            if let Some(polygons) = submesh.polygons.take() {
+                submesh.indices = polygons.triangulate();
+                if polygons.has_non_triangles() {
+                    submesh.polygons = Some(polygons);
+                }
+            }
+
+            if options.repair_meshes {
+                let report = graphics::mesh::repair(submesh, &options.repair_options);
+                println!("Repaired submesh {} of {}: {}", i, part.name, report);
+            }
+
+            if options.validate_meshes {
+                let report = graphics::mesh::validate(submesh);
+                if !report.is_valid() {
+                    println!(
+                        "Warning: submesh {} of {} in {} is broken: {}",
+                        i, part.name, path, report
+                    );
+                }
+            }
+        }
+
+        mesh.geometry_changed();
+
+        if options.optimize_meshes {
+            for (i, report) in mesh.optimize().iter().enumerate() {
+                println!("Optimized submesh {} of {}: {}", i, part.name, report);
+            }
+        }
+
+        for submesh in &mut mesh.submeshes {
+            assets.share_geometry(submesh);
+        }
+    }
+
+    // the whole file is scaled to unit size around the origin by its root node,
+    // so the parts keep their placement relative to each other
+    let bounds = obj_parts
+        .iter()
+        .map(|part| part.mesh.bounds().aabb)
+        .filter(|aabb| !aabb.is_empty())
+        .reduce(|a, b| a.union(&b));
+    let root_transform = match bounds {
+        Some(aabb) if aabb.size().max_element() > 0.0 => {
+            glam::Mat4::from_scale(Vec3::splat(1.0 / aabb.size().max_element()))
+                * glam::Mat4::from_translation(-aabb.center())
+        }
+        _ => glam::Mat4::IDENTITY,
+    };
 
-    scene.objects.push(mesh);
+    let to_error = |error: graphics::error::GraphicsError| {
+        FileError::InvalidFormat(format!("{}: {}", path, error))
+    };
+    let root = scene
+        .add_node(file_name, root_transform, None)
+        .map_err(to_error)?;
+
+    // parents always come before their groups, so their nodes already exist
+    let mut part_nodes = Vec::with_capacity(obj_parts.len());
+    for part in obj_parts {
+        let parent = part.parent.map_or(root, |parent| part_nodes[parent]);
+        let node = if part.mesh.submeshes.is_empty() {
+            scene.add_node(part.name, glam::Mat4::IDENTITY, Some(parent))
+        } else {
+            scene.add_object(part.name, part.mesh, Some(parent))
+        }
+        .map_err(to_error)?;
+        part_nodes.push(node);
+    }
 
     Ok(scene)
 }
 
-fn parse_mtl_file(path: &str) -> Result<HashMap<String, graphics::material::Material>, FileError> {
+// an object or group of the file, with the faces listed under it
+struct ObjPart {
+    name: String,
+    // index of the object a group belongs to
+    parent: Option<usize>,
+    mesh: graphics::mesh::Mesh,
+}
+
+impl ObjPart {
+    fn new(name: String, parent: Option<usize>) -> Self {
+        ObjPart {
+            name,
+            parent,
+            mesh: graphics::mesh::Mesh::new(Vec::new(), glam::Mat4::IDENTITY),
+        }
+    }
+}
+
+fn parse_mtl_file(
+    path: &str,
+    assets: &mut AssetManager,
+) -> Result<HashMap<String, Asset<graphics::material::Material>>, FileError> {
     let file = fs::read_to_string(path).map_err(|e| FileError::IoError(e))?;
+    let mtl_dir = std::path::Path::new(path)
+        .parent()
+        .unwrap_or(std::path::Path::new(""));
     let mut current_material: Option<graphics::material::Material> = Option::None;
     let mut materials = HashMap::new();
 
@@ -305,6 +459,8 @@ fn parse_mtl_file(path: &str) -> Result<HashMap<String, graphics::material::Mate
                 current_material = Some(graphics::material::Material {
                     name: parts[1].to_string(),
                     properties: graphics::material::GpuMaterials::default(),
+                    diffuse_texture: None,
+                    emission: Vec3::ZERO,
                 });
             }
 
@@ -362,6 +518,24 @@ fn parse_mtl_file(path: &str) -> Result<HashMap<String, graphics::material::Mate
                 }
             }
 
+            "Ke" => {
+                // Emissive color
+                if parts.len() < 4 {
+                    return Err(FileError::FormatError(
+                        "Invalid emissive color".to_string(),
+                        crate::reader::FileType::Mtl,
+                        line_number,
+                    ));
+                }
+                if let Some(material) = &mut current_material {
+                    material.emission = Vec3 {
+                        x: parts[1].parse().unwrap_or(0.0),
+                        y: parts[2].parse().unwrap_or(0.0),
+                        z: parts[3].parse().unwrap_or(0.0),
+                    };
+                }
+            }
+
             "Ns" => {
                 // Specular exponent
                 if parts.len() < 2 {
@@ -376,6 +550,28 @@ fn parse_mtl_file(path: &str) -> Result<HashMap<String, graphics::material::Mate
                 }
             }
 
+            "map_Kd" => {
+                // Diffuse texture, the options before the file name are ignored
+                let Some(texture_file_name) = parts.last().filter(|_| parts.len() > 1) else {
+                    return Err(FileError::FormatError(
+                        "Invalid diffuse texture".to_string(),
+                        crate::reader::FileType::Mtl,
+                        line_number,
+                    ));
+                };
+                let texture_path = mtl_dir.join(texture_file_name);
+                let texture_path_str = texture_path.to_str().unwrap_or("error.png");
+
+                if let Some(material) = &mut current_material {
+                    match assets.load_texture(texture_path_str) {
+                        Ok(texture) => material.diffuse_texture = Some(texture),
+                        Err(e) => {
+                            println!("Failed to load texture {}: {}", texture_path_str, e);
+                        }
+                    }
+                }
+            }
+
             _ => {}
         }
     }
@@ -384,5 +580,8 @@ fn parse_mtl_file(path: &str) -> Result<HashMap<String, graphics::material::Mate
         materials.insert(material.name.clone(), material);
     }
     println!("materials: {:#?}", materials);
-    Ok(materials)
+    Ok(materials
+        .into_iter()
+        .map(|(name, material)| (name, assets.add_material(material)))
+        .collect())
 }